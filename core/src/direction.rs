@@ -0,0 +1,77 @@
+//! Orientations of the rails and the senses in which the loop travels them.
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Horizontal {
+    Left,
+    Center,
+    Right,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Vertical {
+    Top,
+    Center,
+    Bottom,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Direction {
+    Horizontal,
+    Vertical,
+}
+
+/// A position along a rail, independent of its direction.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Spot {
+    Start,
+    Center,
+    End,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Sense {
+    Forwards,
+    Backwards,
+}
+
+impl Horizontal {
+    pub fn opposite(&self) -> Self {
+        match self {
+            Horizontal::Left => Horizontal::Right,
+            Horizontal::Center => Horizontal::Center,
+            Horizontal::Right => Horizontal::Left,
+        }
+    }
+}
+
+impl Vertical {
+    pub fn opposite(&self) -> Self {
+        match self {
+            Vertical::Top => Vertical::Bottom,
+            Vertical::Center => Vertical::Center,
+            Vertical::Bottom => Vertical::Top,
+        }
+    }
+}
+
+impl From<Spot> for Horizontal {
+    fn from(spot: Spot) -> Self {
+        match spot {
+            Spot::Start => Horizontal::Left,
+            Spot::Center => Horizontal::Center,
+            Spot::End => Horizontal::Right,
+        }
+    }
+}
+
+impl From<Spot> for Vertical {
+    fn from(spot: Spot) -> Self {
+        match spot {
+            Spot::Start => Vertical::Top,
+            Spot::Center => Vertical::Center,
+            Spot::End => Vertical::Bottom,
+        }
+    }
+}
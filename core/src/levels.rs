@@ -0,0 +1,1240 @@
+use crate::direction::{Horizontal, Vertical};
+use crate::logic::constraints::{Constraints, RailCoord};
+use crate::logic::grid::{get, get_cell, Grid};
+use crate::math::ivec2;
+use crate::{generate_nested_vec, AnyError};
+use std::fmt::{Display, Formatter};
+
+// pub const raw_levels() -> Result<Levels, AnyError> {
+pub const RAW_LEVELS: RawLevels = RawLevels {
+    sections: &[
+        RawSection {
+            header: r#"format=2
+name=Tutorial
+author=jmmut
+"#,
+            levels: &[
+                r#"name=First loop
+hint=Enable blocks until the rails go through every station in the direction of its arrow
+tags=introduces stations
+.-.-.-.-.
+---------
+.-x-x-x-.
+---------
+.-x-.-x-.
+---->->--
+.-x^%-*v.
+----<-<--
+.-x-x-x-.
+---------
+.-x-x-x-.
+---------
+.-.-.-.-.
+"#,
+                r#"name=Two blocks
+.-.-.-.-.
+---------
+.-x-x-x-.
+---------
+.-x-.-x-.
+---->----
+.-x-%-x-.
+---------
+.-x-*-x-.
+----<----
+.-x-x-x-.
+---------
+.-.-.-.-.
+"#,
+                r#"name=Upwards
+.-.-.-.-.
+---------
+.-x-x-x-.
+---------
+.-*^.-x-.
+-->->----
+.-x^%-x-.
+---------
+.-x-x-x-.
+---------
+.-x-x-x-.
+---------
+.-.-.-.-.
+"#,
+                r#"name=Detour
+.-.-.-.-.
+---------
+.-x-x-x-.
+---------
+.-x-.-x-.
+---->----
+.-x-%-x-.
+---------
+.-x-*-*-.
+------<--
+.-x-x-x-.
+---------
+.-.-.-.-.
+"#,
+                r#"name=Head start
+hint=Some blocks start enabled, but you can still disable them
+tags=introduces pre-enabled blocks
+.-.-.-.-.
+---------
+.-x-x-x-.
+---------
+.-x-.-x-.
+---->----
+.-x-%-x-.
+---------
+.-x-?-O-.
+------>--
+.-x-x-x-.
+---------
+.-.-.-.-.
+"#,
+                r#"name=Blockades
+hint=Rails can't go through a blockade, so the blocks at both sides of it must be equal
+tags=introduces blockades
+.-.-.-.-.
+---------
+.-*=*-x-.
+--"------
+.-*-.-x-.
+--"->-"--
+.-O=%-x-.
+---------
+.-x-x-x-.
+---------
+.-x-x-x-.
+---------
+.-.-.-.-.
+"#,
+                r#"name=Roadworks
+.-.-.-.-.
+------<--
+.-*-*-*-.
+----<----
+.v*-.-*-.
+---->----
+.=x-%-*-.
+---------
+.=x-x-x-.
+----"----
+.-x=x=x-.
+------"--
+.-.-.-.-.
+"#,
+            ],
+        },
+        RawSection {
+            header: r#"format=2
+name=Warming up
+author=jmmut
+"#,
+            levels: &[
+                r#"name=Zigzag
+.-.-.-.-.-.-.
+-------------
+.=xv*-x-x-x-.
+----------"--
+.v*=*-.-x-x-.
+---->->-"----
+.-x-x^%-x-x-.
+-------------
+.-x-x-*=*-x-.
+----"--------
+.-x-x-*-*-x-.
+------<-<----
+.-.-.-.-.-.-.
+"#,
+                r#"name=Rush hour
+.-.-.-.-.-.-.
+----<--------
+.=xvO-x-x-x-.
+----------"--
+.vO=O-.-*-x-.
+---->->-"----
+.v*^x^%-*vx-.
+------>------
+.-x-*-?=?-*-.
+----"--------
+.-x-*-O-O=*-.
+----<-<-<----
+.-.-.-.-.-.-.
+"#,
+                r#"name=Full house
+.-.-.-.-.-.-.
+----<--------
+.=xvO-*-*-x-.
+----------"--
+.vO=O-.-O-x-.
+---->->-"----
+.vO^xv%-O^x-.
+------>------
+.-x-O-x=x-O-.
+----"--------
+.-x-O-O-O=O-.
+----<-<-<----
+.-.-.-.-.-.-.
+"#,
+            ],
+        },
+        RawSection {
+            header: r#"format=2
+name=Big boards
+author=jmmut
+"#,
+            levels: &[
+                r#"name=Suburbs
+.-.-.-.-.-.-.-.-.
+--"------->------
+.-x-x-x-x-*-*-*-.
+----"------->----
+.-x-x-*-*-x=x-*-.
+--------<-<-<----
+.-x-*=*-.-*-*=*-.
+-->---"->---"----
+.-*-x-*-%=*-*-x-.
+--<-<-----<------
+.=x^*-x^*-x^*-x-.
+--"-<---<--------
+.-x-x-x-x=x=xv*-.
+----"------------
+.-.-.-.-.-.-.-.-.
+"#,
+                r#"name=Scattered
+.-.-.-.-.-.-.-.-.
+-----------------
+.-x-x-x-*-*-x-x=.
+-----------------
+.v*-*-*-x-*=*-*-.
+-----------------
+.-xv*-x-.-*^xv*-.
+-------->----->--
+.-xv*vx-%-x^*-x-.
+---------->------
+.-x-x-*-x-*-*-x-.
+--------<-<------
+.-x-x-*-*-x-x-x-.
+-----------------
+.-.-.-.-.-.-.-.-.
+"#,
+                r#"name=Interchange
+.-.-.-.-.-.-.-.-.
+--------"--------
+.v*=*-*-xv*=*-*^.
+------>----------
+.-*-*^x-*-x-*-*^.
+------"-----<----
+.-x=x-x-.v*-x-*-.
+-------->--->----
+.-x=x-x^%-x-*-*-.
+------"-----<-"--
+.-x-x=x=x-*vx-*-.
+----"---------"--
+.-x-x-x-x-xv*=*^.
+----"--------->--
+.-.-.-.-.-.-.-.-.
+"#,
+                //               r#".-.-.-.-.-.-.-.-.
+                // -----------------
+                // .-x-x-x-x-x-x-x-.
+                // -----------------
+                // .-x-x-x-x-x-x-x-.
+                // -----------------
+                // .-x-x-x-.-x-x-x-.
+                // -------->--------
+                // .-x-x-x-%-x-x-x-.
+                // -----------------
+                // .-x-x-x-x-x-x-x-.
+                // -----------------
+                // .-x-x-x-x-x-x-x-.
+                // -----------------
+                // .-.-.-.-.-.-.-.-.
+                // "#,
+                r#"name=Capital
+.-.-.-.-.-.-.-.-.-.-.
+----"->----->----->--
+.-x-x-*-*-*-*vx^*-*v.
+------"---------"-<--
+.-x-x-*-*-*-x-*=*-x-.
+--------<-"-<---"----
+.=x-x-*-x^*-*-x^*-x=.
+--"-<--------->------
+.-x-*-x-*-.^*=*=*vx=.
+---------->-------"--
+.-x-x-*vx^%^x-*-*-x-.
+------<-------"---<--
+.-x-*^x=x-x-*-*-xv*-.
+--------------<------
+.-*-x-x-x-*-x=x-*-*-.
+------------>----->--
+.-*-*vx=x=x^*=*-x=x=.
+----------------"----
+.-.-.-.-.-.-.-.-.-.-.
+"#,
+            ],
+        },
+    ],
+};
+#[derive(Debug)]
+pub struct Levels {
+    pub sections: Vec<Section>,
+}
+pub struct RawLevels<'a> {
+    sections: &'a [RawSection<'a>],
+}
+
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Section {
+    pub metadata: Metadata,
+    pub levels: Vec<Level>,
+}
+pub struct RawSection<'a> {
+    header: &'a str,
+    levels: &'a [&'a str],
+}
+
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(try_from = "LevelJson"))]
+pub struct Level {
+    pub metadata: Metadata,
+    pub initial_grid: Grid,
+    pub constraints: Constraints,
+    pub solution: Grid,
+}
+
+/// What a `Level` looks like in JSON. Each grid is checked on its own, this checks that they
+/// agree with each other and that the clues are inside them.
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+struct LevelJson {
+    metadata: Metadata,
+    initial_grid: Grid,
+    constraints: Constraints,
+    solution: Grid,
+}
+
+#[cfg(feature = "serde")]
+impl TryFrom<LevelJson> for Level {
+    type Error = String;
+
+    fn try_from(json: LevelJson) -> Result<Self, Self::Error> {
+        let (rows, columns) = (json.initial_grid.rows(), json.initial_grid.columns());
+        if json.solution.rows() != rows || json.solution.columns() != columns {
+            return Err(format!(
+                "the solution has {} rows and {} columns, but the initial grid has {} and {}",
+                json.solution.rows(),
+                json.solution.columns(),
+                rows,
+                columns
+            ));
+        }
+        for clue in &json.constraints.rails {
+            let (row, column) = match *clue {
+                RailCoord::Horizontal { row, column, .. } => (row, column),
+                RailCoord::Vertical { row, column, .. } => (row, column),
+            };
+            if !(1..rows).contains(&row) || !(1..columns).contains(&column) {
+                return Err(format!(
+                    "the clue at row {}, column {} is not inside a grid of {} rows and {} columns",
+                    row, column, rows, columns
+                ));
+            }
+        }
+        Ok(Level {
+            metadata: json.metadata,
+            initial_grid: json.initial_grid,
+            constraints: json.constraints,
+            solution: json.solution,
+        })
+    }
+}
+
+/// Optional `key=value` lines written before the grid of a level, or before the levels of a
+/// section. Keys that we don't know about are kept in `unknown` so that they survive a round-trip.
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Metadata {
+    pub format_version: Option<u32>,
+    pub name: Option<String>,
+    pub author: Option<String>,
+    pub hint: Option<String>,
+    pub par_time_seconds: Option<u32>,
+    pub tags: Vec<String>,
+    pub unknown: Vec<(String, String)>,
+}
+
+/// Version 1 used spaces as filler between cells, version 2 uses '-'.
+pub const CURRENT_FORMAT_VERSION: u32 = 2;
+
+const FORMAT: &str = "format";
+const NAME: &str = "name";
+const AUTHOR: &str = "author";
+const HINT: &str = "hint";
+const PAR_TIME: &str = "par_time";
+const TAGS: &str = "tags";
+
+impl Levels {
+    pub fn get() -> Result<Levels, AnyError> {
+        let mut sections = Vec::new();
+        for raw_section in RAW_LEVELS.sections {
+            let metadata = Metadata::from_str(raw_section.header)?;
+            let mut levels = Vec::new();
+            for raw_level in raw_section.levels {
+                let level = Level::from_str_strict_in_section(raw_level, metadata.format_version)?;
+                levels.push(level);
+            }
+            sections.push(Section { metadata, levels });
+        }
+        Ok(Levels { sections })
+    }
+    pub fn get_level(&self, section: usize, level: usize) -> &Level {
+        self.sections
+            .get(section)
+            .unwrap()
+            .levels
+            .get(level)
+            .unwrap()
+    }
+    pub fn maybe_get_level(&self, section: i32, level: i32) -> Option<&Level> {
+        self.sections
+            .get(section as usize)?
+            .levels
+            .get(level as usize)
+    }
+}
+impl Metadata {
+    pub fn from_str(s: &str) -> Result<Metadata, AnyError> {
+        let (metadata, rest) = Self::split_header(s)?;
+        if rest.trim().is_empty() {
+            Ok(metadata)
+        } else {
+            Err(format!(
+                "expected only 'key=value' lines but got '{}'",
+                first_line(rest)
+            )
+            .into())
+        }
+    }
+
+    /// Parses the leading `key=value` lines and returns the text after them. The header ends at
+    /// the first line that is not `key=value`, or that starts with '.', which is how the top
+    /// border of a grid starts.
+    pub fn split_header(s: &str) -> Result<(Metadata, &str), AnyError> {
+        let mut metadata = Metadata::default();
+        let mut header_len = 0;
+        for line in s.split_inclusive('\n') {
+            let Some((key, value)) = parse_header_line(line) else {
+                break;
+            };
+            header_len += line.len();
+            match key {
+                FORMAT => {
+                    let version = value.parse().map_err(|e| {
+                        format!("error parsing {} '{}' as a version: {}", FORMAT, value, e)
+                    })?;
+                    metadata.format_version = Some(version)
+                }
+                NAME => metadata.name = Some(value.to_string()),
+                AUTHOR => metadata.author = Some(value.to_string()),
+                HINT => metadata.hint = Some(value.to_string()),
+                PAR_TIME => {
+                    let seconds = value.parse().map_err(|e| {
+                        format!("error parsing {} '{}' as seconds: {}", PAR_TIME, value, e)
+                    })?;
+                    metadata.par_time_seconds = Some(seconds)
+                }
+                TAGS => {
+                    metadata.tags = value
+                        .split(',')
+                        .map(str::trim)
+                        .filter(|tag| !tag.is_empty())
+                        .map(str::to_string)
+                        .collect()
+                }
+                _ => metadata.unknown.push((key.to_string(), value.to_string())),
+            }
+        }
+        Ok((metadata, &s[header_len..]))
+    }
+    pub fn is_empty(&self) -> bool {
+        *self == Metadata::default()
+    }
+}
+
+fn parse_header_line(line: &str) -> Option<(&str, &str)> {
+    if line.starts_with('.') {
+        return None;
+    }
+    let (key, value) = line.split_once('=')?;
+    let key = key.trim();
+    let is_identifier = !key.is_empty() && key.chars().all(|c| c.is_ascii_lowercase() || c == '_');
+    if is_identifier {
+        Some((key, value.trim()))
+    } else {
+        None
+    }
+}
+
+fn first_line(s: &str) -> &str {
+    s.trim_start().lines().next().unwrap_or_default()
+}
+
+impl Display for Metadata {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if let Some(version) = self.format_version {
+            writeln!(f, "{}={}", FORMAT, version)?;
+        }
+        if let Some(name) = &self.name {
+            writeln!(f, "{}={}", NAME, name)?;
+        }
+        if let Some(author) = &self.author {
+            writeln!(f, "{}={}", AUTHOR, author)?;
+        }
+        if let Some(hint) = &self.hint {
+            writeln!(f, "{}={}", HINT, hint)?;
+        }
+        if let Some(par_time) = self.par_time_seconds {
+            writeln!(f, "{}={}", PAR_TIME, par_time)?;
+        }
+        if !self.tags.is_empty() {
+            writeln!(f, "{}={}", TAGS, self.tags.join(","))?;
+        }
+        for (key, value) in &self.unknown {
+            writeln!(f, "{}={}", key, value)?;
+        }
+        Ok(())
+    }
+}
+
+impl Section {
+    /// A section is its own header, followed by its levels. Each of them is separated from the
+    /// next by an empty line.
+    pub fn from_str(s: &str) -> Result<Section, AnyError> {
+        let mut chunks = split_by_empty_lines(s);
+        let metadata = match chunks.first() {
+            Some(first) if Metadata::split_header(first)?.1.trim().is_empty() => {
+                let metadata = Metadata::from_str(first)?;
+                chunks.remove(0);
+                metadata
+            }
+            _ => Metadata::default(),
+        };
+        let mut levels = Vec::new();
+        for (i, chunk) in chunks.iter().enumerate() {
+            let level = Level::from_str_in_section(chunk, metadata.format_version)
+                .map_err(|e| format!("in level {} of the section: {}", i, e))?;
+            levels.push(level);
+        }
+        Ok(Section { metadata, levels })
+    }
+
+    /// Reads a section in any supported format and writes it in the current one, with the
+    /// version marker only in the section header.
+    pub fn upgrade_format(s: &str) -> Result<String, AnyError> {
+        let mut section = Section::from_str(s)?;
+        section.metadata.format_version = Some(CURRENT_FORMAT_VERSION);
+        for level in &mut section.levels {
+            level.metadata.format_version = None;
+        }
+        Ok(section.to_string())
+    }
+}
+
+fn split_by_empty_lines(s: &str) -> Vec<String> {
+    let mut chunks = Vec::new();
+    let mut current = String::new();
+    for line in s.lines() {
+        if line.is_empty() {
+            if !current.is_empty() {
+                chunks.push(std::mem::take(&mut current));
+            }
+        } else {
+            current.push_str(line);
+            current.push('\n');
+        }
+    }
+    if !current.is_empty() {
+        chunks.push(current);
+    }
+    chunks
+}
+
+impl Display for Section {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.metadata)?;
+        for level in &self.levels {
+            writeln!(f)?;
+            write!(f, "{}", level)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Filler {
+    Space,
+    Dash,
+}
+impl Filler {
+    pub fn from_version(version: u32) -> Result<Filler, AnyError> {
+        match version {
+            1 => Ok(Filler::Space),
+            2 => Ok(Filler::Dash),
+            _ => Err(format!(
+                "unsupported level format version {}, the latest supported is {}",
+                version, CURRENT_FORMAT_VERSION
+            )
+            .into()),
+        }
+    }
+    pub fn from_char(letter: char) -> Option<Filler> {
+        match letter {
+            ' ' => Some(Filler::Space),
+            '-' => Some(Filler::Dash),
+            _ => None,
+        }
+    }
+    pub fn to_char(self) -> char {
+        match self {
+            Filler::Space => ' ',
+            Filler::Dash => '-',
+        }
+    }
+}
+
+/// Returns the filler used in the grid, if there's any, or an error if both fillers are used.
+fn detect_filler(grid: &str) -> Result<Option<Filler>, AnyError> {
+    let mut first_seen: Option<(Filler, usize)> = None;
+    for (i_line, line) in grid.lines().enumerate() {
+        for letter in line.chars() {
+            let Some(filler) = Filler::from_char(letter) else {
+                continue;
+            };
+            match first_seen {
+                None => first_seen = Some((filler, i_line + 1)),
+                Some((seen, seen_line)) if seen != filler => {
+                    return Err(format!(
+                        "mixed fillers: (1-based) line {} uses '{}' but line {} uses '{}'",
+                        seen_line,
+                        seen.to_char(),
+                        i_line + 1,
+                        filler.to_char()
+                    )
+                    .into())
+                }
+                Some(_) => {}
+            }
+        }
+    }
+    Ok(first_seen.map(|(filler, _)| filler))
+}
+
+fn check_line_widths(grid: &str) -> Result<(), AnyError> {
+    let mut lines = grid.lines().enumerate();
+    let Some((_, first)) = lines.next() else {
+        return Err("empty grid".into());
+    };
+    let width = first.chars().count();
+    for (i_line, line) in lines {
+        let line_width = line.chars().count();
+        if line_width != width {
+            return Err(format!(
+                "(1-based) line {} has {} letters but the first line has {}",
+                i_line + 1,
+                line_width,
+                width
+            )
+            .into());
+        }
+    }
+    Ok(())
+}
+
+impl Level {
+    /// Accepts any supported format version. Without a version marker, the version is deduced
+    /// from the filler used between cells.
+    pub fn from_str(s: &str) -> Result<Level, AnyError> {
+        Self::from_str_with_default_version(s, None)
+    }
+
+    /// Only accepts the current format version, with an explicit version marker.
+    pub fn from_str_strict(s: &str) -> Result<Level, AnyError> {
+        Self::from_str_strict_in_section(s, None)
+    }
+
+    /// Like `from_str_strict`, but the version marker can also come from the section header.
+    fn from_str_strict_in_section(
+        s: &str,
+        section_version: Option<u32>,
+    ) -> Result<Level, AnyError> {
+        let (metadata, grid) = Metadata::split_header(s)?;
+        match metadata.format_version.or(section_version) {
+            Some(CURRENT_FORMAT_VERSION) => {}
+            Some(version) => {
+                return Err(format!(
+                    "expected level format version {} but got {}, try upgrading it",
+                    CURRENT_FORMAT_VERSION, version
+                )
+                .into())
+            }
+            None => {
+                return Err(format!(
+                    "missing the level format version, expected a '{}={}' line",
+                    FORMAT, CURRENT_FORMAT_VERSION
+                )
+                .into())
+            }
+        }
+        check_line_widths(grid)?;
+        Self::from_str_with_default_version(s, section_version)
+    }
+
+    /// A declared version chooses the parser: the current one is read strictly, older ones and
+    /// levels without a version are read leniently.
+    fn from_str_in_section(s: &str, section_version: Option<u32>) -> Result<Level, AnyError> {
+        let (metadata, _) = Metadata::split_header(s)?;
+        match metadata.format_version.or(section_version) {
+            Some(CURRENT_FORMAT_VERSION) => Self::from_str_strict_in_section(s, section_version),
+            version => Self::from_str_with_default_version(s, version),
+        }
+    }
+
+    /// Reads a level in any supported format and writes it in the current one.
+    pub fn upgrade_format(s: &str) -> Result<String, AnyError> {
+        let mut level = Level::from_str(s)?;
+        level.metadata.format_version = Some(CURRENT_FORMAT_VERSION);
+        Ok(level.to_string())
+    }
+
+    fn from_str_with_default_version(
+        s: &str,
+        default_version: Option<u32>,
+    ) -> Result<Level, AnyError> {
+        let (metadata, s) = Metadata::split_header(s)?;
+        let filler = detect_filler(s)?;
+        if let Some(version) = metadata.format_version.or(default_version) {
+            let expected = Filler::from_version(version)?;
+            if let Some(filler) = filler {
+                if filler != expected {
+                    return Err(format!(
+                        "level format version {} uses '{}' as filler but the grid uses '{}'",
+                        version,
+                        expected.to_char(),
+                        filler.to_char()
+                    )
+                    .into());
+                }
+            }
+        }
+        let mut max_columns = 0;
+        let mut cells = Vec::new();
+        let mut fixed_cells = Vec::new();
+        let mut solution_cells = Vec::new();
+        let mut rails: Vec<RailCoord> = Vec::new();
+        let mut cell_count = 0;
+        let mut line_count = 0;
+        let lines = s.lines();
+        let mut root = None;
+        enum Code {
+            Cell {
+                is_root: bool,
+                cell: bool,
+                fixed_cell: bool,
+                solution: bool,
+            },
+            Constraint(RailCoord),
+            NoRailConstraint,
+        }
+        for line in lines {
+            line_count += 1;
+            let mut cell_row = Vec::new();
+            let mut fixed_cell_row = Vec::new();
+            let mut solution_cell_row = Vec::new();
+            let mut letter_count = 0;
+            for letter in line.chars() {
+                letter_count += 1;
+                #[rustfmt::skip]
+                let code = match letter {
+                    '%' => Code::Cell{is_root: true, cell: true, fixed_cell: true, solution: true},
+                    '@' => Code::Cell{is_root: false, cell: true, fixed_cell: true, solution: true},
+                    '*' => Code::Cell{is_root: false, cell: false, fixed_cell: false, solution: true},
+                    'O' => Code::Cell{is_root: false, cell: true, fixed_cell: false, solution: true},
+                    'x' => Code::Cell{is_root: false, cell: false, fixed_cell: false, solution: false},
+                    '?' => Code::Cell{is_root: false, cell: true, fixed_cell: false, solution: false},
+                    '.' => Code::Cell{is_root: false, cell: false, fixed_cell: true, solution: false},
+                    ' ' | '-' => Code::NoRailConstraint,
+                    'v' => Code::Constraint(RailCoord::Vertical {row: (line_count-1)/2, column: (letter_count+1)/2, sense: Vertical::Bottom}),
+                    '=' => Code::Constraint(RailCoord::Vertical {row: (line_count-1)/2, column: (letter_count+1)/2, sense: Vertical::Center}),
+                    '^' => Code::Constraint(RailCoord::Vertical {row: (line_count-1)/2, column: (letter_count+1)/2, sense: Vertical::Top}),
+                    '>' => Code::Constraint(RailCoord::Horizontal {row: (line_count+1)/2, column: (letter_count-1)/2, sense: Horizontal::Right}),
+                    '"' => Code::Constraint(RailCoord::Horizontal {row: (line_count+1)/2, column: (letter_count-1)/2, sense: Horizontal::Center}),
+                    '<' => Code::Constraint(RailCoord::Horizontal {row: (line_count+1)/2, column: (letter_count-1)/2, sense: Horizontal::Left}),
+                    _ => {
+                        return Err(format!(
+                            "Wrong format for grid at (1-based) line {}, letter {}: unknown symbol '{}'",
+                            line_count, letter_count, letter
+                        )
+                            .into())
+                    }
+                };
+                match code {
+                    Code::Cell {
+                        is_root,
+                        cell,
+                        fixed_cell,
+                        solution,
+                    } => {
+                        if is_root {
+                            root = Some(ivec2((letter_count - 1) / 2, (line_count - 1) / 2));
+                        }
+                        if solution {
+                            cell_count += 1;
+                        }
+                        cell_row.push(cell);
+                        solution_cell_row.push(solution);
+                        fixed_cell_row.push(fixed_cell);
+                    }
+                    Code::Constraint(rail_coord) => rails.push(rail_coord),
+                    Code::NoRailConstraint => {}
+                }
+            }
+            if cell_row.len() > max_columns {
+                max_columns = cell_row.len();
+            }
+            if line_count % 2 == 1 {
+                cells.push(cell_row);
+                fixed_cells.push(fixed_cell_row);
+                solution_cells.push(solution_cell_row);
+            }
+        }
+        let Some(root) = root else {
+            return Err("missing a root cell ('%')".into());
+        };
+        for row in &mut cells {
+            row.resize(max_columns, false);
+        }
+        for row in &mut solution_cells {
+            row.resize(max_columns, false);
+        }
+        for row in &mut fixed_cells {
+            row.resize(max_columns, false);
+        }
+        let mut initial_grid = Grid::new_from_cells(
+            cells.len() as i32,
+            max_columns as i32,
+            root,
+            cells,
+            fixed_cells.clone(),
+        );
+        initial_grid.recalculate_rails();
+        let mut solution = Grid::new_from_cells(
+            solution_cells.len() as i32,
+            max_columns as i32,
+            root,
+            solution_cells,
+            fixed_cells,
+        );
+        solution.recalculate_rails();
+        let constraints = Constraints { rails, cell_count };
+        Ok(Level {
+            metadata,
+            initial_grid,
+            constraints,
+            solution,
+        })
+    }
+}
+impl Display for Level {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let columns = self.initial_grid.columns();
+        let rows = self.initial_grid.rows();
+        write!(f, "{}", self.metadata)?;
+        let mut lines = generate_nested_vec(rows as usize * 2 - 1, columns as usize * 2 - 1, '-');
+        for row in 0..rows {
+            for column in 0..columns {
+                let letter = if self.initial_grid.root == ivec2(column, row) {
+                    '%'
+                } else {
+                    let cell = get_cell(&self.initial_grid, row, column);
+                    let solution_cell = get_cell(&self.solution, row, column);
+                    let fixed_cell = get(&self.solution.fixed_cells, row, column);
+                    match (cell, fixed_cell, solution_cell) {
+                        (true, true, true) => '@',
+                        (true, false, true) => 'O',
+                        (true, false, false) => '?',
+                        (false, true, false) => '.',
+                        (false, false, false) => 'x',
+                        (false, false, true) => '*',
+                        _ => panic!("logic error, should not reach here"),
+                    }
+                };
+                lines[row as usize * 2][column as usize * 2] = letter;
+            }
+        }
+        for constraint in &self.constraints.rails {
+            match *constraint {
+                RailCoord::Horizontal {
+                    row,
+                    column,
+                    sense: direction,
+                } => {
+                    lines[row as usize * 2 - 1][column as usize * 2] = match direction {
+                        Horizontal::Left => '<',
+                        Horizontal::Center => '"',
+                        Horizontal::Right => '>',
+                    };
+                }
+                RailCoord::Vertical {
+                    row,
+                    column,
+                    sense: direction,
+                } => {
+                    lines[row as usize * 2][column as usize * 2 - 1] = match direction {
+                        Vertical::Top => '^',
+                        Vertical::Center => '=',
+                        Vertical::Bottom => 'v',
+                    };
+                }
+            }
+        }
+        write!(
+            f,
+            "{}",
+            lines
+                .into_iter()
+                .map(|mut chars| {
+                    chars.push('\n');
+                    chars.into_iter().collect::<String>()
+                })
+                .collect::<Vec<_>>()
+                .join("")
+        )?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::logic::constraints::{compute_satisfaction, Goal, Satisfaction};
+
+    const RAW_LEVEL: &str = r#".-.-.-.-.-.
+-----------
+.-x-x-x-x-.
+-----------
+.-x-.-x-x-.
+--"->->----
+.=?^%=*vx-.
+--"-<-<----
+.-x-x-x-x-.
+-----------
+.-.-.-.-.-.
+"#;
+
+    #[test]
+    fn basic_level() {
+        let level = Level::from_str(RAW_LEVEL).unwrap();
+        assert_eq!(
+            level.initial_grid.to_string(),
+            r#"......
+.    .
+. .  .
+.O%  .
+.    .
+......
+"#
+        );
+        assert_eq!(
+            level.solution.to_string(),
+            r#"......
+.    .
+. .  .
+. %O .
+.    .
+......
+"#
+        );
+        assert!(compute_satisfaction(&level.solution, &level.constraints).success());
+        assert_eq!(
+            compute_satisfaction(&level.initial_grid, &level.constraints),
+            Satisfaction {
+                stations: Goal::new(2, 10),
+                cell_count: Goal::new(2, 2),
+                reachable: Goal::new(6, 6),
+            }
+        );
+    }
+
+    #[test]
+    fn roundtrip() {
+        let level = Level::from_str(RAW_LEVEL).unwrap();
+        let serialized = level.to_string();
+        assert_eq!(serialized, RAW_LEVEL);
+    }
+
+    const RAW_HEADER: &str = r#"name=Crossroads
+author=jmmut
+hint=Follow the arrows
+par_time=90
+tags=introduces blockades,crossings
+difficulty=easy
+"#;
+
+    #[test]
+    fn metadata() {
+        let raw_level = format!("{}{}", RAW_HEADER, RAW_LEVEL);
+        let level = Level::from_str(&raw_level).unwrap();
+        assert_eq!(
+            level.metadata,
+            Metadata {
+                format_version: None,
+                name: Some("Crossroads".to_string()),
+                author: Some("jmmut".to_string()),
+                hint: Some("Follow the arrows".to_string()),
+                par_time_seconds: Some(90),
+                tags: vec!["introduces blockades".to_string(), "crossings".to_string()],
+                unknown: vec![("difficulty".to_string(), "easy".to_string())],
+            }
+        );
+        assert_eq!(level.to_string(), raw_level);
+    }
+
+    #[test]
+    fn lenient_metadata() {
+        let metadata = Metadata::from_str("name = Crossroads \ntags= a, b,\n").unwrap();
+        assert_eq!(metadata.name, Some("Crossroads".to_string()));
+        assert_eq!(metadata.tags, vec!["a".to_string(), "b".to_string()]);
+        assert!(Metadata::from_str("par_time=soon\n").is_err());
+    }
+
+    #[test]
+    fn section_roundtrip() {
+        let raw_section = format!("name=Basics\n\n{}\n{}{}", RAW_LEVEL, RAW_HEADER, RAW_LEVEL);
+        let section = Section::from_str(&raw_section).unwrap();
+        assert_eq!(section.metadata.name, Some("Basics".to_string()));
+        assert_eq!(section.levels.len(), 2);
+        assert!(section.levels[0].metadata.is_empty());
+        assert_eq!(
+            section.levels[1].metadata.name,
+            Some("Crossroads".to_string())
+        );
+        assert_eq!(section.to_string(), raw_section);
+    }
+
+    #[test]
+    fn legacy_format() {
+        let legacy = RAW_LEVEL.replace('-', " ");
+        let level = Level::from_str(&legacy).unwrap();
+        assert_eq!(level.to_string(), RAW_LEVEL);
+        let versioned = format!("{}=1\n{}", FORMAT, legacy);
+        assert!(Level::from_str(&versioned).is_ok());
+    }
+
+    #[test]
+    fn upgrade_format() {
+        let legacy = RAW_LEVEL.replace('-', " ");
+        assert!(Level::from_str_strict(&legacy).is_err());
+        let upgraded = Level::upgrade_format(&legacy).unwrap();
+        assert_eq!(upgraded, format!("{}=2\n{}", FORMAT, RAW_LEVEL));
+        assert!(Level::from_str_strict(&upgraded).is_ok());
+    }
+
+    #[test]
+    fn upgrade_section_format() {
+        let legacy = RAW_LEVEL.replace('-', " ");
+        let raw_section = format!("name=Old\n\n{}\nname=Older\n{}", legacy, legacy);
+        let upgraded = Section::upgrade_format(&raw_section).unwrap();
+        let expected = format!(
+            "{}=2\nname=Old\n\n{}\nname=Older\n{}",
+            FORMAT, RAW_LEVEL, RAW_LEVEL
+        );
+        assert_eq!(upgraded, expected);
+        let section = Section::from_str(&upgraded).unwrap();
+        assert_eq!(
+            section.metadata.format_version,
+            Some(CURRENT_FORMAT_VERSION)
+        );
+    }
+
+    #[test]
+    fn wrong_formats() {
+        let mixed = RAW_LEVEL.replacen('-', " ", 3);
+        let error = Level::from_str(&mixed).unwrap_err().to_string();
+        assert!(error.contains("mixed fillers"), "{}", error);
+
+        let wrong_version = format!("{}=1\n{}", FORMAT, RAW_LEVEL);
+        let error = Level::from_str(&wrong_version).unwrap_err().to_string();
+        assert!(error.contains("version 1"), "{}", error);
+
+        let future_version = format!("{}=3\n{}", FORMAT, RAW_LEVEL);
+        let error = Level::from_str(&future_version).unwrap_err().to_string();
+        assert!(error.contains("unsupported"), "{}", error);
+
+        let mut ragged = format!("{}=2\n{}", FORMAT, RAW_LEVEL);
+        ragged.insert(ragged.find('\n').unwrap() + 1, '.');
+        let error = Level::from_str_strict(&ragged).unwrap_err().to_string();
+        assert!(error.contains("letters"), "{}", error);
+    }
+
+    #[test]
+    fn declared_format_chooses_parser() {
+        let mut ragged = RAW_LEVEL.to_string();
+        ragged.insert(ragged.find('\n').unwrap() + 1, '.');
+        let current = format!("{}=2\nname=New\n\n{}", FORMAT, ragged);
+        let error = Section::from_str(&current).unwrap_err().to_string();
+        assert!(error.contains("letters"), "{}", error);
+
+        let legacy = RAW_LEVEL.replace('-', " ");
+        let old = format!("{}=1\nname=Old\n\n{}", FORMAT, legacy);
+        assert!(Section::from_str(&old).is_ok());
+        assert!(Section::from_str(&format!("name=Undeclared\n\n{}", legacy)).is_ok());
+    }
+
+    #[test]
+    fn campaign_levels_parse() {
+        for section in RAW_LEVELS.sections {
+            let metadata = Metadata::from_str(section.header).unwrap();
+            assert_eq!(metadata.format_version, Some(CURRENT_FORMAT_VERSION));
+        }
+        let levels = Levels::get().unwrap();
+        for section in &levels.sections {
+            assert!(section.metadata.name.is_some());
+            for level in &section.levels {
+                assert!(level.metadata.name.is_some());
+            }
+        }
+    }
+}
+
+#[cfg(all(test, feature = "serde"))]
+mod serde_tests {
+    use super::*;
+    use crate::logic::constraints::compute_satisfaction;
+    use crate::logic::grid::{get_mut, Note};
+    use serde_json::json;
+
+    const SMALL_LEVEL: &str = r#".-.-.-.-.
+---------
+.-x-.-x-.
+---------
+.-*=%-x-.
+---------
+.-x-x-x-.
+---------
+.-.-.-.-.
+"#;
+
+    #[test]
+    fn campaign_round_trips_through_json() {
+        for section in Levels::get().unwrap().sections {
+            for level in section.levels {
+                let json = serde_json::to_string(&level).unwrap();
+                let parsed: Level = serde_json::from_str(&json).unwrap();
+                assert_eq!(parsed.to_string(), level.to_string());
+                assert_eq!(
+                    compute_satisfaction(&parsed.solution, &parsed.constraints),
+                    compute_satisfaction(&level.solution, &level.constraints),
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn schema() {
+        let level = Level::from_str(SMALL_LEVEL).unwrap();
+        let value = serde_json::to_value(&level).unwrap();
+        assert_eq!(
+            value["constraints"],
+            json!({
+                "rails": [{"direction": "Vertical", "row": 2, "column": 2, "sense": "Center"}],
+                "cell_count": 2,
+            })
+        );
+        let solution = &value["solution"];
+        assert_eq!(solution["rows"], 5);
+        assert_eq!(solution["columns"], 5);
+        assert_eq!(solution["root"], json!({"x": 2, "y": 2}));
+        assert_eq!(
+            solution["cells"][2],
+            json!([false, true, true, false, false])
+        );
+        assert_eq!(
+            solution["intersections"][2][2],
+            json!({"crossing": "Single"})
+        );
+        assert_eq!(solution["rails"]["horizontal"][2][1], json!("Right"));
+        assert_eq!(
+            solution["user_fixes"]["vertical"][0][0],
+            json!({"blockade": false, "station_forward": false, "station_backwards": false})
+        );
+
+        let satisfaction = compute_satisfaction(&level.solution, &level.constraints);
+        assert_eq!(
+            serde_json::to_value(satisfaction).unwrap(),
+            json!({
+                "stations": {"actual": 1, "expected": 1},
+                "cell_count": {"actual": 2, "expected": 2},
+                "reachable": {"actual": 6, "expected": 6},
+            })
+        );
+    }
+
+    #[test]
+    fn user_fixes_survive() {
+        let mut level = Level::from_str(SMALL_LEVEL).unwrap();
+        get_mut(&mut level.initial_grid.fixed_rails.horizontal, 2, 1).blockade = true;
+        let json = serde_json::to_string(&level.initial_grid).unwrap();
+        let parsed: Grid = serde_json::from_str(&json).unwrap();
+        assert!(parsed.fixed_rails.horizontal[2][1].blockade);
+    }
+
+    #[test]
+    fn notes_survive() {
+        let mut level = Level::from_str(SMALL_LEVEL).unwrap();
+        level.initial_grid.notes[1][2] = Note::Outside;
+        let json = serde_json::to_string(&level.initial_grid).unwrap();
+        let parsed: Grid = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed.notes, level.initial_grid.notes);
+
+        let mut without_notes = serde_json::to_value(&level.initial_grid).unwrap();
+        without_notes.as_object_mut().unwrap().remove("notes");
+        let parsed: Grid = serde_json::from_value(without_notes).unwrap();
+        assert_eq!(parsed.notes[1][2], Note::Unknown);
+    }
+
+    #[test]
+    fn invalid_grids() {
+        let level = Level::from_str(SMALL_LEVEL).unwrap();
+        let valid = serde_json::to_value(&level.solution).unwrap();
+
+        let mut wrong_size = valid.clone();
+        wrong_size["rows"] = json!(6);
+        let error = serde_json::from_value::<Grid>(wrong_size).unwrap_err();
+        assert!(error.to_string().contains("6 rows"), "{}", error);
+
+        let mut wrong_root = valid;
+        wrong_root["root"] = json!({"x": 2, "y": 0});
+        assert!(serde_json::from_value::<Grid>(wrong_root).is_err());
+
+        let levels = Levels::get().unwrap();
+        let small = serde_json::to_value(levels.get_level(0, 0)).unwrap();
+        let mut clue_outside = small.clone();
+        clue_outside["constraints"]["rails"][0]["row"] = json!(99);
+        let error = serde_json::from_value::<Level>(clue_outside).unwrap_err();
+        assert!(error.to_string().contains("row 99"), "{}", error);
+
+        let mut clue_on_border = small.clone();
+        clue_on_border["constraints"]["rails"][0]["column"] = json!(0);
+        assert!(serde_json::from_value::<Level>(clue_on_border).is_err());
+
+        let mut mixed_sizes = serde_json::to_value(levels.get_level(2, 3)).unwrap();
+        mixed_sizes["solution"] = small["solution"].clone();
+        let error = serde_json::from_value::<Level>(mixed_sizes).unwrap_err();
+        assert!(error.to_string().contains("the solution has"), "{}", error);
+    }
+}
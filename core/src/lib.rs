@@ -0,0 +1,28 @@
+//! The rules of Metro Loop: grids, clues, level parsing, the solver and the level generator.
+//! It only depends on std, so that tools and tests don't need the graphics stack.
+
+pub mod direction;
+pub mod logic {
+    pub mod constraints;
+    pub mod deduction;
+    pub mod generator;
+    pub mod grid;
+    pub mod intersection;
+    pub mod pixel_grid;
+    pub mod rails;
+    pub mod solver;
+}
+pub mod levels;
+pub mod math;
+pub mod random;
+
+pub const MAX_CELLS_COEF: f32 = 0.5;
+pub const CLUE_PERCENTAGE: u32 = 30;
+
+pub type AnyError = Box<dyn std::error::Error>;
+
+fn generate_nested_vec<T: Clone>(num_rows: usize, num_columns: usize, default: T) -> Vec<Vec<T>> {
+    let row = vec![default; num_columns];
+    let rows = vec![row; num_rows];
+    rows
+}
@@ -1,11 +1,14 @@
+use crate::direction::{Direction, Horizontal, Sense, Spot, Vertical};
 use crate::logic::grid::{get_cell, Grid};
+use crate::logic::intersection::Crossing;
+use crate::logic::rails::Rails;
+use crate::math::{vec2, Vec2};
+use crate::random::rand;
 use crate::CLUE_PERCENTAGE;
-use juquad::widgets::anchor::{Direction, Horizontal, Sense, Spot, Vertical};
-use macroquad::math::vec2;
-use macroquad::prelude::Vec2;
-use macroquad::rand::rand;
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(tag = "direction"))]
 pub enum RailCoord {
     Horizontal {
         row: i32,
@@ -43,6 +46,19 @@ impl RailCoord {
             RailCoord::Vertical { row, column, .. } => (*row, *column),
         }
     }
+    pub fn direction(&self) -> Direction {
+        match self {
+            RailCoord::Horizontal { .. } => Direction::Horizontal,
+            RailCoord::Vertical { .. } => Direction::Vertical,
+        }
+    }
+    pub fn is(&self, direction: Direction) -> bool {
+        matches!(
+            (self, direction),
+            (RailCoord::Horizontal { .. }, Direction::Horizontal)
+                | (RailCoord::Vertical { .. }, Direction::Vertical)
+        )
+    }
     pub fn vec2(&self) -> Vec2 {
         match self {
             RailCoord::Horizontal {
@@ -82,12 +98,14 @@ impl RailCoord {
 }
 
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Constraints {
     pub rails: Vec<RailCoord>,
     pub cell_count: i32,
 }
 
 #[derive(Copy, Clone, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Goal {
     pub actual: i32,
     pub expected: i32,
@@ -104,6 +122,7 @@ impl Goal {
     }
 }
 #[derive(Eq, PartialEq, Debug, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Satisfaction {
     pub stations: Goal,
     pub cell_count: Goal,
@@ -156,7 +175,7 @@ pub fn choose_constraints(grid: &Grid) -> Constraints {
     Constraints { rails, cell_count }
 }
 
-fn count_cells(grid: &Grid) -> i32 {
+pub fn count_cells(grid: &Grid) -> i32 {
     let mut cell_count = 0;
     for row in &grid.cells {
         for cell in row {
@@ -168,13 +187,23 @@ fn count_cells(grid: &Grid) -> i32 {
     cell_count
 }
 
+/// Rails on the outer edge or between two border cells can never be part of the loop, so a
+/// clue there would be meaningless.
+pub fn is_border_rail(grid: &Grid, row: i32, column: i32, direction: Direction) -> bool {
+    let (rows, columns) = (grid.rows(), grid.columns());
+    let inside = match direction {
+        Direction::Horizontal => row > 0 && row < rows && column > 0 && column < columns - 1,
+        Direction::Vertical => row > 0 && row < rows - 1 && column > 0 && column < columns,
+    };
+    !inside
+}
+
 pub fn compute_satisfaction(grid: &Grid, constraints: &Constraints) -> Satisfaction {
     let stations = compute_rail_failures(grid, &constraints.rails);
     let cell_count = Goal {
         actual: count_cells(grid),
         expected: constraints.cell_count,
     };
-    // let unconnected_loops = (count_loops(grid) - 1).abs();
     let reachable = count_unreachable_rails(grid);
     Satisfaction {
         stations,
@@ -214,6 +243,132 @@ pub fn count_loops(grid: &Grid) -> i32 {
     active - adjacents
 }
 
+/// Which loop each rail belongs to. The loop through the root is 0, the others are numbered in the
+/// order they are found. Missing rails are None.
+pub struct Loops {
+    pub rails: Rails<Option<usize>, Option<usize>>,
+    pub count: usize,
+}
+impl Loops {
+    pub fn extra(&self) -> usize {
+        self.count.saturating_sub(1)
+    }
+}
+
+#[derive(Copy, Clone)]
+enum Segment {
+    Horizontal(i32, i32),
+    Vertical(i32, i32),
+}
+
+/// Unlike `Grid::recalculate_rails`, this labels every loop, not only the one through the root.
+pub fn find_loops(grid: &Grid) -> Loops {
+    let rails = Rails::new(grid.rows(), grid.columns(), None, None);
+    let mut loops = Loops { rails, count: 0 };
+    label_loop(
+        grid,
+        &mut loops,
+        Segment::Horizontal(grid.root.y, grid.root.x),
+    );
+    for row in 0..grid.rails.horiz_rows() {
+        for column in 0..grid.rails.horiz_columns() {
+            label_loop(grid, &mut loops, Segment::Horizontal(row, column));
+        }
+    }
+    for row in 0..grid.rails.vert_rows() {
+        for column in 0..grid.rails.vert_columns() {
+            label_loop(grid, &mut loops, Segment::Vertical(row, column));
+        }
+    }
+    loops
+}
+
+fn label_loop(grid: &Grid, loops: &mut Loops, start: Segment) {
+    if !has_rail(grid, start) || label(loops, start).is_some() {
+        return;
+    }
+    let index = loops.count;
+    loops.count += 1;
+    let mut pending = vec![start];
+    while let Some(segment) = pending.pop() {
+        if label(loops, segment).is_some() {
+            continue;
+        }
+        match segment {
+            Segment::Horizontal(row, column) => {
+                *loops.rails.get_horiz_mut(row, column) = Some(index);
+            }
+            Segment::Vertical(row, column) => *loops.rails.get_vert_mut(row, column) = Some(index),
+        }
+        for next in connected(grid, segment) {
+            if has_rail(grid, next) && label(loops, next).is_none() {
+                pending.push(next);
+            }
+        }
+    }
+}
+
+fn label(loops: &Loops, segment: Segment) -> Option<usize> {
+    match segment {
+        Segment::Horizontal(row, column) => loops.rails.get_horiz(row, column),
+        Segment::Vertical(row, column) => loops.rails.get_vert(row, column),
+    }
+}
+
+fn has_rail(grid: &Grid, segment: Segment) -> bool {
+    let rails = &grid.rails;
+    match segment {
+        Segment::Horizontal(row, column) => {
+            row >= 0
+                && column >= 0
+                && row < rails.horiz_rows()
+                && column < rails.horiz_columns()
+                && rails.get_horiz(row, column) != Horizontal::Center
+        }
+        Segment::Vertical(row, column) => {
+            row >= 0
+                && column >= 0
+                && row < rails.vert_rows()
+                && column < rails.vert_columns()
+                && rails.get_vert(row, column) != Vertical::Center
+        }
+    }
+}
+
+/// Rails that continue this one at both of its ends. At crossings the loops go straight.
+fn connected(grid: &Grid, segment: Segment) -> Vec<Segment> {
+    let mut next = Vec::new();
+    let ends = match segment {
+        Segment::Horizontal(row, column) => [(row, column), (row, column + 1)],
+        Segment::Vertical(row, column) => [(row, column), (row + 1, column)],
+    };
+    for (row, column) in ends {
+        if row <= 0 || column <= 0 || row >= grid.rows() || column >= grid.columns() {
+            continue;
+        }
+        let horizontals = [
+            Segment::Horizontal(row, column - 1),
+            Segment::Horizontal(row, column),
+        ];
+        let verticals = [
+            Segment::Vertical(row - 1, column),
+            Segment::Vertical(row, column),
+        ];
+        match grid.intersections.get(row, column).crossing {
+            Crossing::None => {}
+            Crossing::Single => {
+                next.extend(horizontals);
+                next.extend(verticals);
+            }
+            _ => match segment {
+                Segment::Horizontal(..) => next.extend(horizontals),
+                Segment::Vertical(..) => next.extend(verticals),
+            },
+        }
+    }
+    next
+}
+
 fn compute_rail_failures(grid: &Grid, rail_constraints: &Vec<RailCoord>) -> Goal {
     let mut failures = 0;
     for constraint in rail_constraints {
@@ -305,13 +460,61 @@ pub fn matches_constraint_and_reachable(
     }
 }
 
+/// Why the clue is satisfied or not, in the words of the tooltip shown when hovering it.
+pub fn explain_constraint(grid: &Grid, constraint: &RailCoord) -> String {
+    let (success, _, reachable) = matches_constraint_and_reachable(grid, constraint);
+    let (expected, actual) = match *constraint {
+        RailCoord::Horizontal { row, column, sense } => (
+            horizontal_word(sense),
+            horizontal_word(grid.rails.get_horiz(row, column)),
+        ),
+        RailCoord::Vertical { row, column, sense } => (
+            vertical_word(sense),
+            vertical_word(grid.rails.get_vert(row, column)),
+        ),
+    };
+    match (expected, actual) {
+        (None, _) if success => "Bridge satisfied: there is no rail under it".to_string(),
+        (None, _) => "Bridge expects no rail under it, but there is one".to_string(),
+        (Some(expected), _) if success => {
+            format!("Station satisfied: the loop goes {} here", expected)
+        }
+        (Some(expected), None) => format!(
+            "Station expects the loop to go {}, but there is no rail here",
+            expected
+        ),
+        (Some(_), Some(_)) if !reachable => "This rail is not part of the loop".to_string(),
+        (Some(expected), Some(actual)) => format!(
+            "Station expects the loop to go {}, but it goes {}",
+            expected, actual
+        ),
+    }
+}
+
+fn horizontal_word(sense: Horizontal) -> Option<&'static str> {
+    match sense {
+        Horizontal::Left => Some("left"),
+        Horizontal::Center => None,
+        Horizontal::Right => Some("right"),
+    }
+}
+
+fn vertical_word(sense: Vertical) -> Option<&'static str> {
+    match sense {
+        Vertical::Top => Some("up"),
+        Vertical::Center => None,
+        Vertical::Bottom => Some("down"),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::logic::grid::{Cell, UserFix};
+    use crate::generate_nested_vec;
+    use crate::logic::grid::{get_cell_mut, Cell, Note, UserFix};
     use crate::logic::intersection::Intersections;
     use crate::logic::rails::Rails;
-    use macroquad::prelude::IVec2;
+    use crate::math::IVec2;
 
     fn mock_grid(cells: Vec<Vec<Cell>>) -> Grid {
         let rails = Rails::new(0, 0, Horizontal::Center, Vertical::Center);
@@ -320,11 +523,14 @@ mod tests {
         let root = IVec2::default();
         let intersections = Intersections::new(0, 0);
         let fixed_cells = cells.clone();
+        let rows = cells.len();
+        let columns = cells.first().unwrap().len();
         Grid {
-            num_rows: cells.len() as i32,
-            num_columns: cells.first().unwrap().len() as i32,
+            num_rows: rows as i32,
+            num_columns: columns as i32,
             cells,
             fixed_cells,
+            notes: generate_nested_vec(rows, columns, Note::Unknown),
             rails,
             reachable_rails,
             fixed_rails,
@@ -438,4 +644,76 @@ mod tests {
         let loops = count_loops(&grid);
         assert_eq!(loops, 0); // arguably, the inner circuit is a separate loop, counted as negative
     }
+
+    #[test]
+    fn test_find_loops() {
+        let mut grid = Grid::new(7, 7, IVec2::new(1, 2));
+        *get_cell_mut(&mut grid, 3, 2) = true;
+        grid.recalculate_rails();
+        let loops = find_loops(&grid);
+        assert_eq!(loops.count, 1, "the diagonal crossing joins both blocks");
+
+        *get_cell_mut(&mut grid, 2, 4) = true;
+        *get_cell_mut(&mut grid, 5, 5) = true;
+        grid.recalculate_rails();
+        let loops = find_loops(&grid);
+        assert_eq!(loops.count, 3);
+        assert_eq!(loops.extra(), 2);
+        assert_eq!(loops.rails.get_horiz(2, 4), Some(1));
+        assert_eq!(loops.rails.get_vert(5, 6), Some(2));
+        assert_eq!(loops.rails.get_horiz(1, 1), None);
+        for row in 0..grid.rails.horiz_rows() {
+            for column in 0..grid.rails.horiz_columns() {
+                let in_root_loop = loops.rails.get_horiz(row, column) == Some(0);
+                assert_eq!(in_root_loop, grid.reachable_rails.get_horiz(row, column));
+            }
+        }
+        for row in 0..grid.rails.vert_rows() {
+            for column in 0..grid.rails.vert_columns() {
+                let in_root_loop = loops.rails.get_vert(row, column) == Some(0);
+                assert_eq!(in_root_loop, grid.reachable_rails.get_vert(row, column));
+            }
+        }
+    }
+
+    #[test]
+    fn test_explain_constraint() {
+        let levels = crate::levels::Levels::get().unwrap();
+        let level = levels.get_level(0, 0);
+        for constraint in &level.constraints.rails {
+            let explanation = explain_constraint(&level.solution, constraint);
+            assert!(explanation.contains("satisfied"), "{}", explanation);
+        }
+        let station = level
+            .constraints
+            .rails
+            .iter()
+            .find(|clue| matches!(clue.type_(), Constraint::Station(_)))
+            .unwrap();
+        let initial = &level.initial_grid;
+        let without_rail = level
+            .constraints
+            .rails
+            .iter()
+            .filter(|clue| matches!(clue.type_(), Constraint::Station(_)))
+            .find(|clue| matches_constraint_and_reachable(initial, clue).1 == Reverse::None)
+            .unwrap();
+        assert!(explain_constraint(initial, without_rail).ends_with("no rail here"));
+
+        let reversed = match *station {
+            RailCoord::Horizontal { row, column, sense } => RailCoord::Horizontal {
+                row,
+                column,
+                sense: sense.opposite(),
+            },
+            RailCoord::Vertical { row, column, sense } => RailCoord::Vertical {
+                row,
+                column,
+                sense: sense.opposite(),
+            },
+        };
+        let explanation = explain_constraint(&level.solution, &reversed);
+        assert!(explanation.starts_with("Station expects the loop to go "));
+        assert!(explanation.contains(", but it goes "), "{}", explanation);
+    }
 }
@@ -0,0 +1,337 @@
+//! Cells whose value follows from the clues and the known cells, without guessing. Known cells
+//! are the fixed ones: the border, the root, and the ones locked in `Grid::fixed_cells`.
+
+use crate::direction::{Horizontal, Vertical};
+use crate::logic::constraints::{Constraints, RailCoord};
+use crate::logic::grid::{get, get_cell, is_system_fixed, Grid};
+use crate::logic::solver::{solve, SolveLimits};
+
+/// How many solutions are compared before giving up on finding cells they all agree on.
+pub const MAX_COMPARED_SOLUTIONS: usize = 20;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Deduction {
+    pub row: i32,
+    pub column: i32,
+    pub enabled: bool,
+    pub reason: Reason,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Reason {
+    /// A station has an enabled block on one side and a disabled one on the other, and the
+    /// block at `from` was already known.
+    Station { clue: RailCoord, from: (i32, i32) },
+    /// A blockade has the same value on both sides, and the block at `from` was already known.
+    Blockade { clue: RailCoord, from: (i32, i32) },
+    /// Every solution that agrees with the known cells has this value.
+    AllSolutions,
+}
+
+type Known = Vec<Vec<Option<bool>>>;
+
+/// Every deduction, in the order they can be made. Stops early if the known cells contradict
+/// the clues.
+pub fn deduce(grid: &Grid, constraints: &Constraints) -> Vec<Deduction> {
+    let mut known = known_cells(grid);
+    let mut deductions = Vec::new();
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for clue in &constraints.rails {
+            let (first, second, station) = sides(clue);
+            for (from, to) in [(first, second), (second, first)] {
+                if let Some(from_enabled) = known[from.0 as usize][from.1 as usize] {
+                    let already_known = known[to.0 as usize][to.1 as usize].is_some();
+                    let clue = clue.clone();
+                    let reason = if station {
+                        Reason::Station { clue, from }
+                    } else {
+                        Reason::Blockade { clue, from }
+                    };
+                    let enabled = from_enabled != station;
+                    if !learn(&mut known, &mut deductions, to.0, to.1, enabled, reason) {
+                        return deductions;
+                    }
+                    changed |= !already_known;
+                }
+            }
+        }
+    }
+    deductions.extend(agreed_by_all_solutions(grid, constraints, &known));
+    deductions
+}
+
+/// The first deduction that the player's board doesn't follow yet.
+pub fn hint(grid: &Grid, constraints: &Constraints) -> Option<Deduction> {
+    deduce(grid, constraints)
+        .into_iter()
+        .find(|deduction| *get_cell(grid, deduction.row, deduction.column) != deduction.enabled)
+}
+
+impl Deduction {
+    pub fn explain(&self) -> String {
+        let value = if self.enabled { "active" } else { "inactive" };
+        match &self.reason {
+            Reason::Station { .. } => format!(
+                "This block must be {}, unlike the block on the other side of the station",
+                value
+            ),
+            Reason::Blockade { .. } => format!(
+                "This block must be {}, like the block on the other side of the bridge",
+                value
+            ),
+            Reason::AllSolutions => format!("This block is {} in every possible solution", value),
+        }
+    }
+}
+
+/// Makes the deductions of a board one at a time, back and forth, to show how it can be solved.
+/// Decided blocks are locked in `grid`, like the blocks that were known from the start.
+#[derive(Debug)]
+pub struct Walkthrough {
+    start: Grid,
+    grid: Grid,
+    deductions: Vec<Deduction>,
+    applied: usize,
+}
+
+impl Walkthrough {
+    pub fn new(grid: &Grid, constraints: &Constraints) -> Self {
+        Self {
+            start: grid.clone(),
+            grid: grid.clone(),
+            deductions: deduce(grid, constraints),
+            applied: 0,
+        }
+    }
+
+    /// Returns false if there were no deductions left.
+    pub fn forward(&mut self) -> bool {
+        self.go_to(self.applied + 1)
+    }
+
+    /// Returns false if no deduction was made yet.
+    pub fn back(&mut self) -> bool {
+        self.applied > 0 && self.go_to(self.applied - 1)
+    }
+
+    fn go_to(&mut self, applied: usize) -> bool {
+        if applied > self.deductions.len() {
+            return false;
+        }
+        self.applied = applied;
+        self.grid = self.start.clone();
+        for deduction in &self.deductions[..applied] {
+            let (row, column) = (deduction.row as usize, deduction.column as usize);
+            self.grid.cells[row][column] = deduction.enabled;
+            self.grid.fixed_cells[row][column] = true;
+        }
+        self.grid.recalculate_rails();
+        true
+    }
+
+    pub fn grid(&self) -> &Grid {
+        &self.grid
+    }
+    /// The last deduction that was made.
+    pub fn current(&self) -> Option<&Deduction> {
+        self.applied.checked_sub(1).map(|i| &self.deductions[i])
+    }
+    /// How many deductions were made, and how many there are.
+    pub fn progress(&self) -> (usize, usize) {
+        (self.applied, self.deductions.len())
+    }
+}
+
+fn known_cells(grid: &Grid) -> Known {
+    let mut known = Vec::new();
+    for row in 0..grid.rows() {
+        let mut known_row = Vec::new();
+        for column in 0..grid.columns() {
+            let fixed = *get(&grid.fixed_cells, row, column) || is_system_fixed(grid, row, column);
+            known_row.push(fixed.then(|| *get_cell(grid, row, column)));
+        }
+        known.push(known_row);
+    }
+    known
+}
+
+/// Returns false if the cell was already known with the other value.
+fn learn(
+    known: &mut Known,
+    deductions: &mut Vec<Deduction>,
+    row: i32,
+    column: i32,
+    enabled: bool,
+    reason: Reason,
+) -> bool {
+    let cell = &mut known[row as usize][column as usize];
+    match *cell {
+        Some(value) => value == enabled,
+        None => {
+            *cell = Some(enabled);
+            deductions.push(Deduction {
+                row,
+                column,
+                enabled,
+                reason,
+            });
+            true
+        }
+    }
+}
+
+/// The blocks on both sides of a clue, and whether it's a station. A station needs a rail, so
+/// one block is enabled and the other disabled. A blockade needs both blocks equal.
+fn sides(clue: &RailCoord) -> ((i32, i32), (i32, i32), bool) {
+    let (row, column) = clue.row_column();
+    match *clue {
+        RailCoord::Horizontal { sense, .. } => (
+            (row - 1, column),
+            (row, column),
+            sense != Horizontal::Center,
+        ),
+        RailCoord::Vertical { sense, .. } => {
+            ((row, column - 1), (row, column), sense != Vertical::Center)
+        }
+    }
+}
+
+fn agreed_by_all_solutions(
+    grid: &Grid,
+    constraints: &Constraints,
+    known: &Known,
+) -> Vec<Deduction> {
+    let mut template = grid.clone();
+    for (row, known_row) in known.iter().enumerate() {
+        for (column, cell) in known_row.iter().enumerate() {
+            template.fixed_cells[row][column] = cell.is_some();
+            if let Some(enabled) = *cell {
+                template.cells[row][column] = enabled;
+            }
+        }
+    }
+    let limits = SolveLimits {
+        max_solutions: MAX_COMPARED_SOLUTIONS,
+        ..Default::default()
+    };
+    let solutions = solve(&template, constraints, &limits);
+    let mut deductions = Vec::new();
+    let Some(first) = solutions.grids.first() else {
+        return deductions;
+    };
+    if !solutions.complete {
+        return deductions;
+    }
+    for (row, known_row) in known.iter().enumerate() {
+        for (column, cell) in known_row.iter().enumerate() {
+            let enabled = first.cells[row][column];
+            let agreed = solutions
+                .grids
+                .iter()
+                .all(|solution| solution.cells[row][column] == enabled);
+            if cell.is_none() && agreed {
+                deductions.push(Deduction {
+                    row: row as i32,
+                    column: column as i32,
+                    enabled,
+                    reason: Reason::AllSolutions,
+                });
+            }
+        }
+    }
+    deductions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::levels::{Level, Levels};
+
+    #[test]
+    fn test_deductions_match_the_solution() {
+        let levels = Levels::get().unwrap();
+        for level in &levels.sections[0].levels {
+            for deduction in deduce(&level.initial_grid, &level.constraints) {
+                let expected = *get_cell(&level.solution, deduction.row, deduction.column);
+                assert_eq!(deduction.enabled, expected, "{:?}", level.metadata.name);
+            }
+        }
+    }
+
+    #[test]
+    fn test_hint() {
+        let level = Level::from_str(
+            r#".-.-.-.-.
+---------
+.-x-.-x-.
+---------
+.-*=%-x-.
+---------
+.-x-x-x-.
+---------
+.-.-.-.-.
+"#,
+        )
+        .unwrap();
+        let deductions = deduce(&level.initial_grid, &level.constraints);
+        let first = &deductions[0];
+        assert_eq!((first.row, first.column, first.enabled), (2, 1, true));
+        assert!(matches!(
+            first.reason,
+            Reason::Blockade { from: (2, 2), .. }
+        ));
+
+        let mut grid = level.initial_grid.clone();
+        let hint = hint(&grid, &level.constraints).unwrap();
+        assert_eq!(&hint, first);
+
+        grid.cells[2][1] = true;
+        grid.recalculate_rails();
+        let next = super::hint(&grid, &level.constraints);
+        assert!(next.is_none_or(|h| h.reason == Reason::AllSolutions));
+        assert_eq!(super::hint(&level.solution, &level.constraints), None);
+    }
+
+    #[test]
+    fn test_walkthrough() {
+        let levels = Levels::get().unwrap();
+        let level = &levels.sections[0].levels[0];
+        let mut walkthrough = Walkthrough::new(&level.initial_grid, &level.constraints);
+        let (_, total) = walkthrough.progress();
+        assert!(total > 0);
+        assert!(!walkthrough.back());
+        assert_eq!(walkthrough.current(), None);
+
+        assert!(walkthrough.forward());
+        let first = walkthrough.current().unwrap().clone();
+        assert_eq!(walkthrough.progress(), (1, total));
+        let grid = walkthrough.grid();
+        assert_eq!(*get_cell(grid, first.row, first.column), first.enabled);
+        assert!(*get(&grid.fixed_cells, first.row, first.column));
+
+        while walkthrough.forward() {}
+        assert_eq!(walkthrough.progress(), (total, total));
+        for _ in 0..total {
+            assert!(walkthrough.back());
+        }
+        assert_eq!(walkthrough.grid().cells, level.initial_grid.cells);
+    }
+
+    #[test]
+    fn test_sides() {
+        let clue = RailCoord::Horizontal {
+            row: 2,
+            column: 3,
+            sense: Horizontal::Left,
+        };
+        assert_eq!(sides(&clue), ((1, 3), (2, 3), true));
+        let clue = RailCoord::Vertical {
+            row: 2,
+            column: 3,
+            sense: Vertical::Center,
+        };
+        assert_eq!(sides(&clue), ((2, 2), (2, 3), false));
+    }
+}
@@ -0,0 +1,249 @@
+use crate::levels::{Level, Metadata};
+use crate::logic::constraints::{choose_constraints, count_unreachable_rails};
+use crate::logic::grid::{count_neighbours, get_cell, get_cell_mut, in_range, Grid};
+use crate::logic::intersection::count_crossings;
+use crate::logic::solver::{solve, SolveLimits};
+use crate::math::ivec2;
+use crate::random::{rand, srand};
+use crate::MAX_CELLS_COEF;
+use std::ops::Range;
+
+/// Grows a random shape from the root of the grid, one cell per `step`, so that the growth can
+/// be shown while it happens.
+pub struct GridGenerator {
+    grid: Grid,
+    enabled: Vec<(i32, i32)>,
+    max_cells: usize,
+    attempts: i32,
+}
+
+impl GridGenerator {
+    pub fn new(rows: i32, columns: i32) -> Self {
+        let grid = Grid::new(rows, columns, ivec2(columns / 2, rows / 2));
+        let enabled = vec![(grid.root.y, grid.root.x)];
+        let max_cells = ((rows - 2) as f32 * (columns - 2) as f32 * MAX_CELLS_COEF) as usize;
+        Self {
+            grid,
+            enabled,
+            max_cells,
+            attempts: 0,
+        }
+    }
+    pub fn grid(&self) -> &Grid {
+        &self.grid
+    }
+    pub fn into_grid(self) -> Grid {
+        self.grid
+    }
+
+    /// Tries to enable one more cell. Returns false when the shape is finished.
+    pub fn step(&mut self) -> bool {
+        if self.enabled.len() >= self.max_cells {
+            return false;
+        }
+        self.attempts += 1;
+        if self.attempts > 1000 {
+            return false;
+        }
+        // TODO: simplify and allow diagonal cells
+
+        let solution = &mut self.grid;
+        let enabled = &mut self.enabled;
+        let mut index = enabled.len() - 1;
+        let mut low_neighbours_attempts = 0;
+        let (new_row, new_column) = loop {
+            let (row, column) = enabled[index];
+            let candidate = rand() % 8;
+            let (new_row, new_column) = match candidate {
+                0 => (row - 1, column),
+                1 => (row, column + 1),
+                2 => (row + 1, column),
+                3 => (row, column - 1),
+                4 => (row - 1, column - 1),
+                5 => (row - 1, column + 1),
+                6 => (row + 1, column + 1),
+                7 => (row + 1, column - 1),
+                _ => panic!(),
+            };
+            low_neighbours_attempts += 1;
+            if in_range(solution, new_row, new_column) {
+                let neighbours = count_neighbours(solution, new_row, new_column);
+                let already_enabled = get_cell(solution, new_row, new_column);
+                if !already_enabled && neighbours <= 2 || low_neighbours_attempts > 50 {
+                    break (new_row, new_column);
+                }
+            }
+            if low_neighbours_attempts % 10 == 0 {
+                index = rand() as usize % enabled.len();
+            }
+            if low_neighbours_attempts > 100 {
+                panic!();
+            }
+        };
+        let above_root = (solution.root.y - 1, solution.root.x);
+        // if the chosen neighbour is already enabled, choose another neighbour
+        if in_range(solution, new_row, new_column) && (new_row, new_column) != above_root {
+            *get_cell_mut(solution, new_row, new_column) = true;
+            enabled.push((new_row, new_column));
+        }
+        true
+    }
+}
+
+pub fn generate_grid(rows: i32, columns: i32) -> Grid {
+    let mut generator = GridGenerator::new(rows, columns);
+    while generator.step() {}
+    generator.into_grid()
+}
+
+/// Generates shapes until one of them is a single loop, and chooses random clues for it.
+pub fn generate_procedural(rows: i32, columns: i32) -> Level {
+    let mut solution = generate_grid(rows, columns);
+    solution.recalculate_rails();
+    while !count_unreachable_rails(&solution).success() {
+        solution = generate_grid(rows, columns);
+        solution.recalculate_rails();
+    }
+    level_from_solution(solution)
+}
+
+/// Builds a level with an empty board and random clues. The solution must have its rails
+/// already calculated.
+pub fn level_from_solution(solution: Grid) -> Level {
+    let mut grid = Grid::new(solution.rows(), solution.columns(), solution.root);
+    grid.recalculate_rails();
+    let constraints = choose_constraints(&solution);
+    Level {
+        metadata: Metadata::default(),
+        initial_grid: grid,
+        constraints,
+        solution,
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Difficulty {
+    Easy,
+    Medium,
+    Hard,
+}
+impl Difficulty {
+    pub const ALL: [Difficulty; 3] = [Difficulty::Easy, Difficulty::Medium, Difficulty::Hard];
+
+    pub fn text(&self) -> &'static str {
+        match self {
+            Difficulty::Easy => "easy",
+            Difficulty::Medium => "medium",
+            Difficulty::Hard => "hard",
+        }
+    }
+    pub fn from_text(text: &str) -> Option<Difficulty> {
+        Self::ALL.into_iter().find(|d| d.text() == text)
+    }
+}
+
+const EASY_MAX_STEPS: u64 = 100;
+const MEDIUM_MAX_STEPS: u64 = 2_000;
+
+/// Measures how much the solver has to guess to find a solution of the level.
+pub fn estimate_difficulty(level: &Level) -> Difficulty {
+    let limits = SolveLimits {
+        max_solutions: 1,
+        max_steps: MEDIUM_MAX_STEPS,
+    };
+    let solutions = solve(&level.initial_grid, &level.constraints, &limits);
+    if solutions.grids.is_empty() {
+        Difficulty::Hard
+    } else if solutions.steps <= EASY_MAX_STEPS {
+        Difficulty::Easy
+    } else {
+        Difficulty::Medium
+    }
+}
+
+/// Conditions that a generated level must meet to be kept. `None` accepts anything.
+#[derive(Clone, Debug, Default)]
+pub struct LevelFilter {
+    pub difficulty: Option<Difficulty>,
+    pub min_loop_length: Option<i32>,
+    pub max_loop_length: Option<i32>,
+    pub min_crossings: Option<i32>,
+    pub max_crossings: Option<i32>,
+}
+impl LevelFilter {
+    /// The difficulty of the level if it is accepted. The difficulty is only estimated after the
+    /// cheaper checks pass.
+    pub fn accepts(&self, level: &Level) -> Option<Difficulty> {
+        let in_bounds = |value: i32, min: Option<i32>, max: Option<i32>| {
+            min.is_none_or(|min| value >= min) && max.is_none_or(|max| value <= max)
+        };
+        let loop_length = level.solution.total_rails;
+        let crossings = count_crossings(&level.solution.intersections);
+        if !in_bounds(loop_length, self.min_loop_length, self.max_loop_length)
+            || !in_bounds(crossings, self.min_crossings, self.max_crossings)
+        {
+            return None;
+        }
+        let difficulty = estimate_difficulty(level);
+        self.difficulty
+            .is_none_or(|wanted| wanted == difficulty)
+            .then_some(difficulty)
+    }
+}
+
+/// Generates one level per seed until `count` of them pass the filter or the seeds run out.
+/// Each level is named after its seed, so it can be generated again.
+pub fn generate_batch(
+    rows: i32,
+    columns: i32,
+    seeds: Range<u64>,
+    count: usize,
+    filter: &LevelFilter,
+) -> Vec<Level> {
+    let mut levels = Vec::new();
+    for seed in seeds {
+        if levels.len() >= count {
+            break;
+        }
+        srand(seed);
+        let mut level = generate_procedural(rows, columns);
+        if let Some(difficulty) = filter.accepts(&level) {
+            level.metadata.name = Some(format!("Random {}", seed));
+            level.metadata.tags = vec![difficulty.text().to_string()];
+            levels.push(level);
+        }
+    }
+    levels
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::logic::constraints::compute_satisfaction;
+
+    #[test]
+    fn test_batch_is_deterministic_and_valid() {
+        let filter = LevelFilter::default();
+        let levels = generate_batch(7, 9, 10..15, 3, &filter);
+        assert_eq!(levels.len(), 3);
+        for level in &levels {
+            assert!(compute_satisfaction(&level.solution, &level.constraints).success());
+        }
+        let again = generate_batch(7, 9, 10..15, 3, &filter);
+        let texts = |levels: &Vec<Level>| levels.iter().map(|l| l.to_string()).collect::<Vec<_>>();
+        assert_eq!(texts(&levels), texts(&again));
+    }
+
+    #[test]
+    fn test_filter() {
+        let filter = LevelFilter {
+            min_crossings: Some(1),
+            max_loop_length: Some(30),
+            ..Default::default()
+        };
+        for level in generate_batch(7, 9, 0..50, 5, &filter) {
+            assert!(count_crossings(&level.solution.intersections) >= 1);
+            assert!(level.solution.total_rails <= 30);
+        }
+    }
+}
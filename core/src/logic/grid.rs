@@ -1,22 +1,44 @@
+use crate::direction::{Horizontal, Vertical};
 use crate::logic::intersection::{
     crossing_to_char, horiz_to_char, vert_to_char, Crossing, Intersection, Intersections,
 };
 use crate::logic::pixel_grid::Coord;
 use crate::logic::rails::Rails;
+use crate::math::{ivec2, IVec2};
 use crate::{generate_nested_vec, AnyError};
-use juquad::widgets::anchor::{Horizontal, Vertical};
-use macroquad::prelude::{ivec2, IVec2};
 use std::fmt::{Debug, Display, Formatter};
-use std::str::FromStr;
 
 pub type Cell = bool;
 
+/// A pencil mark: what the player believes about a cell, without changing or locking it.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Note {
+    #[default]
+    Unknown,
+    Inside,
+    Outside,
+}
+
+impl Note {
+    pub fn next(self) -> Self {
+        match self {
+            Note::Unknown => Note::Inside,
+            Note::Inside => Note::Outside,
+            Note::Outside => Note::Unknown,
+        }
+    }
+}
+
 #[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(into = "GridJson", try_from = "GridJson"))]
 pub struct Grid {
     pub num_rows: i32,
     pub num_columns: i32,
     pub cells: Vec<Vec<Cell>>,
     pub fixed_cells: Vec<Vec<Cell>>,
+    pub notes: Vec<Vec<Note>>,
     pub rails: Rails<Horizontal, Vertical>,
     pub reachable_rails: Rails<bool, bool>,
     pub fixed_rails: Rails<UserFix, UserFix>,
@@ -60,11 +82,13 @@ impl Grid {
             UserFix::default(),
         );
         let intersections = Intersections::new(num_rows, num_columns);
+        let notes = generate_nested_vec(num_rows as usize, num_columns as usize, Note::Unknown);
         Self {
             num_rows,
             num_columns,
             cells,
             fixed_cells,
+            notes,
             rails,
             reachable_rails,
             fixed_rails,
@@ -439,16 +463,8 @@ pub fn rails_to_string(grid: &Grid) -> String {
     GridAndRails { grid }.to_string()
 }
 
-const ROOT_ROW: &str = "root_row";
-const ROOT_COLUMN: &str = "root_column";
-
 impl Display for Grid {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        // write!(
-        //     f,
-        //     "{}={},{}={},\n",
-        //     ROOT_ROW, self.root.y, ROOT_COLUMN, self.root.x
-        // )?;
         for row in 0..self.rows() {
             for column in 0..self.columns() {
                 let cell = get(&self.cells, row, column);
@@ -534,60 +550,96 @@ impl Grid {
             fixed_cells,
         ))
     }
-
-    #[allow(unused)]
-    fn parse_metadata_line(first_line: Option<&str>) -> Result<IVec2, AnyError> {
-        if let Some(first_line) = first_line {
-            let mut root_x = None;
-            let mut root_y = None;
-            let without_spaces = first_line.replace(' ', "");
-            let pairs = without_spaces.split_terminator(',').collect::<Vec<_>>();
-            for pair in pairs {
-                let words = pair.split('=').collect::<Vec<_>>();
-                if words.len() != 2 {
-                    return Err(format!(
-                        "expected 2 words but got {} words in '{}'",
-                        words.len(),
-                        pair
-                    )
-                    .into());
-                }
-                let name = words[0];
-                let value = words[1];
-                if name == ROOT_ROW {
-                    root_y = Some(i32::from_str(value)?)
-                } else if name == ROOT_COLUMN {
-                    root_x = Some(i32::from_str(value)?);
-                } else {
-                    // future compatible: ignore unknown keys
-                }
-            }
-            match (root_x, root_y) {
-                (Some(x), Some(y)) => Ok(ivec2(x, y)),
-                (_, None) => Err(format!("missing {}", ROOT_ROW).into()),
-                (None, _) => Err(format!("missing {}", ROOT_COLUMN).into()),
-            }
-        } else {
-            Err("empty grid string".into())
-        }
-    }
 }
 
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct UserFix {
     pub blockade: bool,
     pub station_forward: bool,
     pub station_backwards: bool,
 }
-impl Default for UserFix {
-    fn default() -> Self {
+/// What a `Grid` looks like in JSON. Only the cells, the root, the user marks and the notes are
+/// read back; the rails and crossings are written for the convenience of external tools, and
+/// recalculated when reading. Grids written before notes existed have none.
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize, serde::Deserialize)]
+struct GridJson {
+    rows: i32,
+    columns: i32,
+    root: IVec2,
+    cells: Vec<Vec<Cell>>,
+    fixed_cells: Vec<Vec<Cell>>,
+    user_fixes: Rails<UserFix, UserFix>,
+    #[serde(default)]
+    notes: Option<Vec<Vec<Note>>>,
+    #[serde(skip_deserializing)]
+    rails: Option<Rails<Horizontal, Vertical>>,
+    #[serde(skip_deserializing)]
+    intersections: Option<Intersections>,
+}
+
+#[cfg(feature = "serde")]
+impl From<Grid> for GridJson {
+    fn from(grid: Grid) -> Self {
         Self {
-            blockade: false,
-            station_forward: false,
-            station_backwards: false,
+            rows: grid.num_rows,
+            columns: grid.num_columns,
+            root: grid.root,
+            cells: grid.cells,
+            fixed_cells: grid.fixed_cells,
+            user_fixes: grid.fixed_rails,
+            notes: Some(grid.notes),
+            rails: Some(grid.rails),
+            intersections: Some(grid.intersections),
         }
     }
 }
+
+#[cfg(feature = "serde")]
+impl TryFrom<GridJson> for Grid {
+    type Error = String;
+
+    fn try_from(json: GridJson) -> Result<Self, Self::Error> {
+        let (rows, columns) = (json.rows, json.columns);
+        let (r, c) = (rows.max(0) as usize, columns.max(0) as usize);
+        let fixes = &json.user_fixes;
+        if !has_shape(&json.cells, r, c)
+            || !has_shape(&json.fixed_cells, r, c)
+            || !has_shape(&fixes.horizontal, r + 1, c)
+            || !has_shape(&fixes.vertical, r, c + 1)
+            || !json
+                .notes
+                .as_ref()
+                .is_none_or(|notes| has_shape(notes, r, c))
+        {
+            return Err(format!(
+                "the cells or user fixes don't match a grid of {} rows and {} columns",
+                rows, columns
+            ));
+        }
+        let root = json.root;
+        if root.y < 1 || !in_expanded_range_inner(root.y, root.x, rows, columns) {
+            return Err(format!(
+                "the root at row {}, column {} is not a valid root position",
+                root.y, root.x
+            ));
+        }
+        let mut grid = Grid::new_from_cells(rows, columns, root, json.cells, json.fixed_cells);
+        grid.fixed_rails = json.user_fixes;
+        if let Some(notes) = json.notes {
+            grid.notes = notes;
+        }
+        grid.recalculate_rails();
+        Ok(grid)
+    }
+}
+
+#[cfg(feature = "serde")]
+fn has_shape<T>(cells: &[Vec<T>], rows: usize, columns: usize) -> bool {
+    cells.len() == rows && cells.iter().all(|row| row.len() == columns)
+}
+
 #[cfg(test)]
 mod grid_serde_tests {
     use super::*;
@@ -640,17 +692,6 @@ mod grid_serde_tests {
         assert_eq!(parsed.fixed_cells, grid.fixed_cells);
         assert_eq!(parsed.root, grid.root);
     }
-    #[test]
-    fn test_lenient_metadata() {
-        let parsed = Grid::parse_metadata_line(Some("root_row=3,root_column=5,"));
-        assert_eq!(parsed.unwrap(), ivec2(5, 3));
-        let parsed = Grid::parse_metadata_line(Some("root_row=3,root_column=5"));
-        assert_eq!(parsed.unwrap(), ivec2(5, 3));
-        let parsed = Grid::parse_metadata_line(Some("root_row = 3 , root_column = 5 "));
-        assert_eq!(parsed.unwrap(), ivec2(5, 3));
-        let parsed = Grid::parse_metadata_line(Some("root_row = 3 , root_column = 5 , "));
-        assert_eq!(parsed.unwrap(), ivec2(5, 3));
-    }
 }
 
 #[cfg(test)]
@@ -1,6 +1,6 @@
+use crate::direction::{Horizontal, Vertical};
 use crate::generate_nested_vec;
 use crate::logic::grid::Grid;
-use juquad::widgets::anchor::{Horizontal, Vertical};
 use std::fmt::{Debug, Display, Formatter};
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
@@ -39,6 +39,7 @@ impl From<Horizontal> for Direction {
 }
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Crossing {
     None,
     Single,
@@ -48,6 +49,7 @@ pub enum Crossing {
     HorizontalOnTop,
 }
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Intersection {
     // pub right: Direction,
     // pub left: Direction,
@@ -73,6 +75,8 @@ impl Default for Intersection {
     }
 }
 #[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(transparent))]
 pub struct Intersections {
     inner: Vec<Vec<Intersection>>,
 }
@@ -146,6 +150,17 @@ pub(crate) fn vert_to_char(vertical: Vertical) -> char {
     }
 }
 
+/// Number of intersections where the loop crosses itself.
+pub fn count_crossings(intersections: &Intersections) -> i32 {
+    let mut crossings = 0;
+    for row in &intersections.inner {
+        for intersection in row {
+            crossings += !matches!(intersection.crossing, Crossing::None | Crossing::Single) as i32;
+        }
+    }
+    crossings
+}
+
 pub fn crossing_to_char(inter: Intersection) -> char {
     match inter.crossing {
         Crossing::None => '•',
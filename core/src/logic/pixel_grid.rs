@@ -1,4 +1,4 @@
-use macroquad::math::{vec2, IVec2, Vec2};
+use crate::math::{vec2, IVec2, Vec2};
 use std::ops::{Add, Sub};
 
 #[derive(Copy, Clone)]
@@ -13,7 +13,7 @@ impl CellSpot {
         let pos: Vec2 = pos.into();
         let sum = pos.x.fract() + pos.y.fract();
         let diff = (pos.x.fract() - pos.y.fract()).abs();
-        let corner = sum < 0.5 || sum > 1.5 || diff > 0.5;
+        let corner = !(0.5..=1.5).contains(&sum) || diff > 0.5;
         let rounded = pos.round().into();
         let floored = pos.floor().into();
         CellSpot {
@@ -148,56 +148,56 @@ mod tests {
     fn test_00_corner() {
         let pos = Coord::new_f(1.2, 1.25);
         let spot = CellSpot::new(pos);
-        assert_eq!(spot.is_corner(), true);
+        assert!(spot.is_corner());
         assert_eq!(spot.quadrant(), Coord::new_i(0, 0))
     }
     #[test]
     fn test_00_center() {
         let pos = Coord::new_f(1.3, 1.25);
         let spot = CellSpot::new(pos);
-        assert_eq!(spot.is_corner(), false);
+        assert!(!spot.is_corner());
         assert_eq!(spot.quadrant(), Coord::new_i(0, 0))
     }
     #[test]
     fn test_01_corner() {
         let pos = Coord::new_f(1.2, 1.9);
         let spot = CellSpot::new(pos);
-        assert_eq!(spot.is_corner(), true);
+        assert!(spot.is_corner());
         assert_eq!(spot.quadrant(), Coord::new_i(0, 1))
     }
     #[test]
     fn test_01_center() {
         let pos = Coord::new_f(1.2, 1.55);
         let spot = CellSpot::new(pos);
-        assert_eq!(spot.is_corner(), false);
+        assert!(!spot.is_corner());
         assert_eq!(spot.quadrant(), Coord::new_i(0, 1))
     }
     #[test]
     fn test_10_corner() {
         let pos = Coord::new_f(1.8, 1.25);
         let spot = CellSpot::new(pos);
-        assert_eq!(spot.is_corner(), true);
+        assert!(spot.is_corner());
         assert_eq!(spot.quadrant(), Coord::new_i(1, 0))
     }
     #[test]
     fn test_10_center() {
         let pos = Coord::new_f(1.6, 1.25);
         let spot = CellSpot::new(pos);
-        assert_eq!(spot.is_corner(), false);
+        assert!(!spot.is_corner());
         assert_eq!(spot.quadrant(), Coord::new_i(1, 0))
     }
     #[test]
     fn test_11_corner() {
         let pos = Coord::new_f(1.8, 1.9);
         let spot = CellSpot::new(pos);
-        assert_eq!(spot.is_corner(), true);
+        assert!(spot.is_corner());
         assert_eq!(spot.quadrant(), Coord::new_i(1, 1))
     }
     #[test]
     fn test_11_center() {
         let pos = Coord::new_f(1.6, 1.55);
         let spot = CellSpot::new(pos);
-        assert_eq!(spot.is_corner(), false);
+        assert!(!spot.is_corner());
         assert_eq!(spot.quadrant(), Coord::new_i(1, 1))
     }
 }
@@ -1,6 +1,7 @@
 use crate::generate_nested_vec;
 
 #[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Rails<H, V> {
     pub horizontal: Vec<Vec<H>>,
     pub vertical: Vec<Vec<V>>,
@@ -0,0 +1,542 @@
+use crate::direction::{Horizontal, Vertical};
+use crate::logic::constraints::{compute_satisfaction, Constraints, RailCoord};
+use crate::logic::grid::{get, Grid, Note};
+use crate::logic::rails::Rails;
+
+/// Stops the search early. `max_steps` counts visited partial boards.
+#[derive(Copy, Clone, Debug)]
+pub struct SolveLimits {
+    pub max_solutions: usize,
+    pub max_steps: u64,
+}
+impl Default for SolveLimits {
+    fn default() -> Self {
+        Self {
+            max_solutions: 2,
+            max_steps: 200_000,
+        }
+    }
+}
+
+pub struct Solutions {
+    pub grids: Vec<Grid>,
+    /// True if the whole search space was explored, so `grids` has every solution.
+    pub complete: bool,
+    /// Partial boards visited, a rough measure of how much guessing the level needs.
+    pub steps: u64,
+}
+impl Solutions {
+    pub fn is_unique(&self) -> bool {
+        self.complete && self.grids.len() == 1
+    }
+}
+
+/// Finds the cell assignments that satisfy the constraints. Cells marked in
+/// `grid.fixed_cells` keep the value they have in `grid`, every other cell is free.
+pub fn solve(grid: &Grid, constraints: &Constraints, limits: &SolveLimits) -> Solutions {
+    let mut search = Search::new(grid, constraints, limits);
+    search.start();
+    Solutions {
+        complete: !search.aborted && search.solutions.len() < limits.max_solutions,
+        grids: search.solutions,
+        steps: search.steps,
+    }
+}
+
+/// How many solutions `wrong_in_every_solution` compares before giving up.
+pub const MAX_CHECKED_SOLUTIONS: usize = 100;
+
+/// The cells that the player set in `board` whose value is wrong in every solution of the level,
+/// where `level_grid` is the level's initial grid. The player set the enabled cells, and the ones
+/// they locked or noted; untouched cells are never wrong. None if the level has too many
+/// solutions to compare them all, or none at all.
+pub fn wrong_in_every_solution(
+    level_grid: &Grid,
+    constraints: &Constraints,
+    board: &Grid,
+) -> Option<Vec<(i32, i32)>> {
+    let limits = SolveLimits {
+        max_solutions: MAX_CHECKED_SOLUTIONS,
+        ..Default::default()
+    };
+    let solutions = solve(level_grid, constraints, &limits);
+    if !solutions.complete || solutions.grids.is_empty() {
+        return None;
+    }
+    let mut wrong = Vec::new();
+    for row in 0..board.rows() {
+        for column in 0..board.columns() {
+            let cell = *get(&board.cells, row, column);
+            let set_by_player = !*get(&level_grid.fixed_cells, row, column)
+                && (cell
+                    || *get(&board.fixed_cells, row, column)
+                    || *get(&board.notes, row, column) != Note::Unknown);
+            if !set_by_player {
+                continue;
+            }
+            let in_every = solutions
+                .grids
+                .iter()
+                .all(|solution| *get(&solution.cells, row, column) != cell);
+            if in_every {
+                wrong.push((row, column));
+            }
+        }
+    }
+    Some(wrong)
+}
+
+/// The solutions of the level sorted by how many cells differ from `board`, closest first.
+/// If the level has more than `MAX_CHECKED_SOLUTIONS`, only that many are compared, plus
+/// `known_solution`, so the closest is never further than the stored solution.
+pub fn closest_solutions(
+    level_grid: &Grid,
+    constraints: &Constraints,
+    known_solution: &Grid,
+    board: &Grid,
+) -> Vec<Grid> {
+    let limits = SolveLimits {
+        max_solutions: MAX_CHECKED_SOLUTIONS,
+        ..Default::default()
+    };
+    closest_solutions_within(level_grid, constraints, known_solution, board, &limits)
+}
+
+fn closest_solutions_within(
+    level_grid: &Grid,
+    constraints: &Constraints,
+    known_solution: &Grid,
+    board: &Grid,
+    limits: &SolveLimits,
+) -> Vec<Grid> {
+    let mut grids = solve(level_grid, constraints, limits).grids;
+    let known_found = grids
+        .iter()
+        .any(|grid| count_different_cells(grid, known_solution) == 0);
+    if !known_found {
+        grids.push(known_solution.clone());
+    }
+    grids.sort_by_key(|grid| count_different_cells(grid, board));
+    grids
+}
+
+pub fn count_different_cells(grid: &Grid, other: &Grid) -> usize {
+    grid.cells
+        .iter()
+        .flatten()
+        .zip(other.cells.iter().flatten())
+        .filter(|(cell, other_cell)| cell != other_cell)
+        .count()
+}
+
+type Known = Option<bool>;
+
+struct Search<'a> {
+    template: &'a Grid,
+    constraints: &'a Constraints,
+    limits: &'a SolveLimits,
+    cells: Vec<Vec<Known>>,
+    /// Some(true) if the rail must exist (station), Some(false) if it must not (blockade).
+    required_rails: Rails<Known, Known>,
+    free: Vec<(i32, i32)>,
+    enabled: i32,
+    unknown: i32,
+    steps: u64,
+    aborted: bool,
+    solutions: Vec<Grid>,
+    scratch: Grid,
+    visited: Vec<u32>,
+    stamp: u32,
+    pending: Vec<(i32, i32)>,
+}
+
+type Trail = Vec<(i32, i32)>;
+
+impl<'a> Search<'a> {
+    fn new(template: &'a Grid, constraints: &'a Constraints, limits: &'a SolveLimits) -> Self {
+        let mut cells = Vec::new();
+        let mut free = Vec::new();
+        let mut enabled = 0;
+        for row in 0..template.rows() {
+            let mut cell_row = Vec::new();
+            for column in 0..template.columns() {
+                if *get(&template.fixed_cells, row, column) {
+                    let cell = *get(&template.cells, row, column);
+                    enabled += cell as i32;
+                    cell_row.push(Some(cell));
+                } else {
+                    free.push((row, column));
+                    cell_row.push(None);
+                }
+            }
+            cells.push(cell_row);
+        }
+        let mut required_rails = Rails::new(template.rows(), template.columns(), None, None);
+        for constraint in &constraints.rails {
+            match *constraint {
+                RailCoord::Horizontal { row, column, sense } => {
+                    *required_rails.get_horiz_mut(row, column) = Some(sense != Horizontal::Center);
+                }
+                RailCoord::Vertical { row, column, sense } => {
+                    *required_rails.get_vert_mut(row, column) = Some(sense != Vertical::Center);
+                }
+            }
+        }
+        Self {
+            template,
+            constraints,
+            limits,
+            cells,
+            required_rails,
+            unknown: free.len() as i32,
+            free,
+            enabled,
+            steps: 0,
+            aborted: false,
+            solutions: Vec::new(),
+            scratch: template.clone(),
+            visited: vec![0; (template.rows() * template.columns()) as usize],
+            stamp: 0,
+            pending: Vec::new(),
+        }
+    }
+
+    fn start(&mut self) {
+        let mut trail = Trail::new();
+        for row in 0..self.template.rows() {
+            for column in 0..self.template.columns() {
+                if let Some(value) = self.known(row, column) {
+                    if !self.propagate(row, column, value, &mut trail) {
+                        return;
+                    }
+                }
+            }
+        }
+        let all_known = (0..self.template.rows())
+            .flat_map(|row| (0..self.template.columns()).map(move |column| (row, column)))
+            .collect();
+        if self.regions_ok(&all_known) {
+            self.search(0);
+        }
+    }
+
+    fn search(&mut self, mut index: usize) {
+        if self.aborted || self.solutions.len() >= self.limits.max_solutions {
+            return;
+        }
+        self.steps += 1;
+        if self.steps > self.limits.max_steps {
+            self.aborted = true;
+            return;
+        }
+        let expected = self.constraints.cell_count;
+        if self.enabled > expected || self.enabled + self.unknown < expected {
+            return;
+        }
+        while let Some(&(row, column)) = self.free.get(index) {
+            if self.known(row, column).is_none() {
+                break;
+            }
+            index += 1;
+        }
+        let Some(&(row, column)) = self.free.get(index) else {
+            self.check_complete();
+            return;
+        };
+        for value in [true, false] {
+            let mut trail = Trail::new();
+            if self.assign(row, column, value, &mut trail) && self.regions_ok(&trail) {
+                self.search(index + 1);
+            }
+            self.undo(trail);
+        }
+    }
+
+    /// Sets a cell and every cell forced by the stations and blockades around it. Returns
+    /// false if some clue can't be satisfied anymore.
+    fn assign(&mut self, row: i32, column: i32, value: bool, trail: &mut Trail) -> bool {
+        self.cells[row as usize][column as usize] = Some(value);
+        self.enabled += value as i32;
+        self.unknown -= 1;
+        trail.push((row, column));
+        self.propagate(row, column, value, trail)
+    }
+
+    fn propagate(&mut self, row: i32, column: i32, value: bool, trail: &mut Trail) -> bool {
+        let mut pending = vec![(row, column, value)];
+        while let Some((row, column, value)) = pending.pop() {
+            let rails = &self.required_rails;
+            let neighbours = [
+                (rails.get_horiz(row, column), row - 1, column),
+                (rails.get_horiz(row + 1, column), row + 1, column),
+                (rails.get_vert(row, column), row, column - 1),
+                (rails.get_vert(row, column + 1), row, column + 1),
+            ];
+            for (required, other_row, other_column) in neighbours {
+                let Some(rail) = required else {
+                    continue;
+                };
+                let forced = value != rail;
+                match self.known(other_row, other_column) {
+                    Some(other) if other != forced => return false,
+                    Some(_) => {}
+                    None => {
+                        self.cells[other_row as usize][other_column as usize] = Some(forced);
+                        self.enabled += forced as i32;
+                        self.unknown -= 1;
+                        trail.push((other_row, other_column));
+                        pending.push((other_row, other_column, forced));
+                    }
+                }
+            }
+        }
+        true
+    }
+
+    fn undo(&mut self, trail: Trail) {
+        for (row, column) in trail {
+            let cell = &mut self.cells[row as usize][column as usize];
+            self.enabled -= cell.take().unwrap() as i32;
+            self.unknown += 1;
+        }
+    }
+
+    fn known(&self, row: i32, column: i32) -> Known {
+        self.cells
+            .get(row as usize)
+            .and_then(|cells| cells.get(column as usize))
+            .copied()
+            .flatten()
+    }
+
+    /// A region of known cells that can't grow anymore must not split the loop: enabled
+    /// regions must contain all the enabled cells, and disabled regions must touch the border.
+    /// Only the regions around the recently assigned cells can have been closed.
+    fn regions_ok(&mut self, trail: &Trail) -> bool {
+        for &(row, column) in trail {
+            for (d_row, d_column) in [(0, 0)].iter().chain(&NEIGHBOURS) {
+                if let Some(value) = self.known(row + d_row, column + d_column) {
+                    if !self.region_ok(row + d_row, column + d_column, value) {
+                        return false;
+                    }
+                }
+            }
+        }
+        true
+    }
+
+    fn region_ok(&mut self, start_row: i32, start_column: i32, value: bool) -> bool {
+        let rows = self.template.rows();
+        let columns = self.template.columns();
+        self.stamp += 1;
+        self.pending.clear();
+        self.pending.push((start_row, start_column));
+        self.visited[(start_row * columns + start_column) as usize] = self.stamp;
+        let mut size = 0;
+        while let Some((row, column)) = self.pending.pop() {
+            size += 1;
+            let border = row == 0 || column == 0 || row == rows - 1 || column == columns - 1;
+            if border && !value {
+                return true;
+            }
+            for (d_row, d_column) in NEIGHBOURS {
+                let (n_row, n_column) = (row + d_row, column + d_column);
+                if n_row < 0 || n_column < 0 || n_row >= rows || n_column >= columns {
+                    continue;
+                }
+                match self.known(n_row, n_column) {
+                    None => return true,
+                    Some(other) if other == value => {
+                        let seen = &mut self.visited[(n_row * columns + n_column) as usize];
+                        if *seen != self.stamp {
+                            *seen = self.stamp;
+                            self.pending.push((n_row, n_column));
+                        }
+                    }
+                    Some(_) => {}
+                }
+            }
+        }
+        value && size == self.constraints.cell_count
+    }
+
+    fn check_complete(&mut self) {
+        for (row, cells) in self.cells.iter().enumerate() {
+            for (column, cell) in cells.iter().enumerate() {
+                self.scratch.cells[row][column] = cell.unwrap();
+            }
+        }
+        self.scratch.recalculate_rails();
+        if compute_satisfaction(&self.scratch, self.constraints).success() {
+            self.solutions.push(self.scratch.clone());
+        }
+    }
+}
+
+#[rustfmt::skip]
+const NEIGHBOURS: [(i32, i32); 8] = [
+    (-1, -1), (-1, 0), (-1, 1),
+    (0, -1), (0, 1),
+    (1, -1), (1, 0), (1, 1),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::levels::{Level, Levels};
+
+    fn cells(grid: &Grid) -> &Vec<Vec<bool>> {
+        &grid.cells
+    }
+
+    #[test]
+    fn test_campaign_solutions_are_found() {
+        let levels = Levels::get().unwrap();
+        for level in &levels.sections[0].levels {
+            let limits = SolveLimits {
+                max_solutions: 10,
+                ..Default::default()
+            };
+            let solutions = solve(&level.initial_grid, &level.constraints, &limits);
+            assert!(solutions.complete, "{:?}", level.metadata.name);
+            assert!(solutions
+                .grids
+                .iter()
+                .any(|grid| cells(grid) == cells(&level.solution)));
+        }
+    }
+
+    #[test]
+    fn test_unique_and_ambiguous() {
+        let unique = r#".-.-.-.-.
+---------
+.-x-.-x-.
+---------
+.-*=%-x-.
+---------
+.-x-x-x-.
+---------
+.-.-.-.-.
+"#;
+        let level = Level::from_str(unique).unwrap();
+        let solutions = solve(
+            &level.initial_grid,
+            &level.constraints,
+            &SolveLimits::default(),
+        );
+        assert!(solutions.is_unique());
+        assert_eq!(cells(&solutions.grids[0]), cells(&level.solution));
+
+        let ambiguous = unique.replace('=', "-");
+        let level = Level::from_str(&ambiguous).unwrap();
+        let solutions = solve(
+            &level.initial_grid,
+            &level.constraints,
+            &SolveLimits::default(),
+        );
+        assert!(!solutions.is_unique());
+        assert_eq!(solutions.grids.len(), 2);
+    }
+
+    #[test]
+    fn test_wrong_in_every_solution() {
+        let unique = r#".-.-.-.-.
+---------
+.-x-.-x-.
+---------
+.-*=%-x-.
+---------
+.-x-x-x-.
+---------
+.-.-.-.-.
+"#;
+        let level = Level::from_str(unique).unwrap();
+        let check = |level: &Level, board: &Grid| {
+            wrong_in_every_solution(&level.initial_grid, &level.constraints, board).unwrap()
+        };
+        let mut board = level.initial_grid.clone();
+        assert_eq!(check(&level, &board), vec![]);
+        board.cells[3][2] = true;
+        assert_eq!(check(&level, &board), vec![(3, 2)]);
+        board.notes[2][1] = Note::Outside;
+        assert_eq!(check(&level, &board), vec![(2, 1), (3, 2)]);
+        board.notes[2][1] = Note::Unknown;
+        board.fixed_cells[2][1] = true;
+        assert_eq!(check(&level, &board), vec![(2, 1), (3, 2)]);
+        assert_eq!(check(&level, &level.solution), vec![]);
+
+        // any block next to the root makes a solution, not only the stored one
+        let ambiguous = Level::from_str(&unique.replace('=', "-")).unwrap();
+        let mut board = ambiguous.initial_grid.clone();
+        board.cells[3][2] = true;
+        assert_eq!(check(&ambiguous, &board), vec![]);
+    }
+
+    #[test]
+    fn test_closest_solutions() {
+        let ambiguous = r#".-.-.-.-.
+---------
+.-x-.-x-.
+---------
+.-*-%-x-.
+---------
+.-x-x-x-.
+---------
+.-.-.-.-.
+"#;
+        let level = Level::from_str(ambiguous).unwrap();
+        let mut board = level.initial_grid.clone();
+        board.cells[3][2] = true;
+        let solutions = closest_solutions(
+            &level.initial_grid,
+            &level.constraints,
+            &level.solution,
+            &board,
+        );
+        assert_eq!(solutions.len(), 7);
+        assert_eq!(count_different_cells(&solutions[0], &board), 0);
+        assert_eq!(count_different_cells(&solutions[1], &board), 2);
+        assert_ne!(cells(&solutions[0]), cells(&level.solution));
+
+        let cut_short = SolveLimits {
+            max_solutions: 1,
+            ..Default::default()
+        };
+        let board = level.solution.clone();
+        let solutions = closest_solutions_within(
+            &level.initial_grid,
+            &level.constraints,
+            &level.solution,
+            &board,
+            &cut_short,
+        );
+        assert_eq!(
+            solutions.len(),
+            2,
+            "the stored solution was not among the ones found"
+        );
+        assert_eq!(cells(&solutions[0]), cells(&level.solution));
+    }
+
+    #[test]
+    fn test_unsolvable() {
+        let level = r#".-.-.-.-.
+---------
+.-x-.-x-.
+---------
+.-x-%v@-.
+---------
+.-x-x-x-.
+---------
+.-.-.-.-.
+"#;
+        let level = Level::from_str(level).unwrap();
+        let solutions = solve(
+            &level.initial_grid,
+            &level.constraints,
+            &SolveLimits::default(),
+        );
+        assert!(solutions.complete);
+        assert!(solutions.grids.is_empty());
+    }
+}
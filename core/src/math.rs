@@ -0,0 +1,90 @@
+//! The few vector operations that the logic needs. The field names and constructors follow
+//! the ones of the vectors used for rendering.
+
+use std::ops::{Add, Sub};
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct IVec2 {
+    pub x: i32,
+    pub y: i32,
+}
+
+pub const fn ivec2(x: i32, y: i32) -> IVec2 {
+    IVec2 { x, y }
+}
+
+impl IVec2 {
+    pub const ZERO: IVec2 = ivec2(0, 0);
+
+    pub const fn new(x: i32, y: i32) -> Self {
+        ivec2(x, y)
+    }
+    pub fn abs(self) -> IVec2 {
+        ivec2(self.x.abs(), self.y.abs())
+    }
+    pub fn max(self, other: IVec2) -> IVec2 {
+        ivec2(self.x.max(other.x), self.y.max(other.y))
+    }
+    pub fn min(self, other: IVec2) -> IVec2 {
+        ivec2(self.x.min(other.x), self.y.min(other.y))
+    }
+}
+
+impl From<(i32, i32)> for IVec2 {
+    fn from((x, y): (i32, i32)) -> Self {
+        ivec2(x, y)
+    }
+}
+
+impl Add for IVec2 {
+    type Output = IVec2;
+    fn add(self, other: IVec2) -> IVec2 {
+        ivec2(self.x + other.x, self.y + other.y)
+    }
+}
+
+impl Sub for IVec2 {
+    type Output = IVec2;
+    fn sub(self, other: IVec2) -> IVec2 {
+        ivec2(self.x - other.x, self.y - other.y)
+    }
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct Vec2 {
+    pub x: f32,
+    pub y: f32,
+}
+
+pub const fn vec2(x: f32, y: f32) -> Vec2 {
+    Vec2 { x, y }
+}
+
+impl Vec2 {
+    pub const ZERO: Vec2 = vec2(0.0, 0.0);
+
+    pub const fn new(x: f32, y: f32) -> Self {
+        vec2(x, y)
+    }
+    pub fn floor(self) -> Vec2 {
+        vec2(self.x.floor(), self.y.floor())
+    }
+    pub fn round(self) -> Vec2 {
+        vec2(self.x.round(), self.y.round())
+    }
+}
+
+impl Add for Vec2 {
+    type Output = Vec2;
+    fn add(self, other: Vec2) -> Vec2 {
+        vec2(self.x + other.x, self.y + other.y)
+    }
+}
+
+impl Sub for Vec2 {
+    type Output = Vec2;
+    fn sub(self, other: Vec2) -> Vec2 {
+        vec2(self.x - other.x, self.y - other.y)
+    }
+}
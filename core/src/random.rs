@@ -0,0 +1,23 @@
+//! Small seedable pseudo random generator, so that procedural levels can be reproduced from
+//! their seed. The state is per thread.
+
+use std::cell::Cell;
+
+thread_local! {
+    static STATE: Cell<u64> = const { Cell::new(0x853c_49e6_748f_ea9b) };
+}
+
+pub fn srand(seed: u64) {
+    STATE.with(|state| state.set(seed));
+}
+
+pub fn rand() -> u32 {
+    STATE.with(|state| {
+        let next = state
+            .get()
+            .wrapping_mul(6_364_136_223_846_793_005)
+            .wrapping_add(1_442_695_040_888_963_407);
+        state.set(next);
+        (next >> 33) as u32
+    })
+}
@@ -0,0 +1,470 @@
+//! Headless tool to check, inspect and generate level packs without opening a window.
+//!
+//! Usage:
+//! - `metro-loop-cli <validate|stats|solve> [PACK_FILE...] [--max-solutions=N]`
+//! - `metro-loop-cli generate OUTPUT_FILE [--count=N] [--rows=N] [--columns=N] [--seed=N]
+//!   [--seeds=N] [--difficulty=easy|medium|hard] [--min-loop=N] [--max-loop=N]
+//!   [--min-crossings=N] [--max-crossings=N]`
+//! - `metro-loop-cli booklet OUTPUT_FILE [PACK_FILE...] [--per-page=N] [--generate=N]
+//!   [GENERATE_FLAGS...]`
+//!
+//! The booklet is a printable HTML file with the puzzles and their answers at the end. With
+//! `--generate=N` it has N random levels, chosen with the same flags as `generate`, instead of the
+//! levels of the packs.
+//!
+//! Without pack files it works on the built-in campaign. A pack file is a section: an optional
+//! header followed by levels separated by empty lines.
+
+use metro_loop::export::{export_booklet, BookletOptions};
+use metro_loop::levels::{Level, Levels, Metadata, Section, CURRENT_FORMAT_VERSION};
+use metro_loop::logic::constraints::{
+    compute_satisfaction, is_border_rail, Constraint, Constraints,
+};
+use metro_loop::logic::generator::{generate_batch, Difficulty, LevelFilter};
+use metro_loop::logic::grid::rails_to_string;
+use metro_loop::logic::intersection::count_crossings;
+use metro_loop::logic::solver::{solve, SolveLimits};
+use metro_loop::{AnyError, NUM_COLUMNS, NUM_ROWS};
+use std::str::FromStr;
+
+const USAGE: &str =
+    "usage: metro-loop-cli <validate|stats|solve> [PACK_FILE...] [--max-solutions=N]
+       metro-loop-cli generate OUTPUT_FILE [--count=N] [--rows=N] [--columns=N] [--seed=N] \
+[--seeds=N] [--difficulty=easy|medium|hard] [--min-loop=N] [--max-loop=N] [--min-crossings=N] \
+[--max-crossings=N]
+       metro-loop-cli booklet OUTPUT_FILE [PACK_FILE...] [--per-page=N] [--generate=N] \
+[GENERATE_FLAGS...]";
+
+fn main() {
+    if let Err(e) = run() {
+        eprintln!("error: {}", e);
+        std::process::exit(1);
+    }
+}
+
+fn run() -> Result<(), AnyError> {
+    let raw_args = std::env::args().collect::<Vec<_>>();
+    let args = parse_args_pure(&raw_args)?;
+    if let Command::Generate = args.command {
+        return generate(&args.files, &args.generate);
+    }
+    if let Command::Booklet = args.command {
+        return booklet(&args.files, &args.booklet, &args.generate);
+    }
+    let packs = load_packs(&args.files)?;
+    match args.command {
+        Command::Validate => validate(&packs),
+        Command::Stats => {
+            stats(&packs);
+            Ok(())
+        }
+        Command::Solve => {
+            solve_all(&packs, &args.limits);
+            Ok(())
+        }
+        Command::Generate | Command::Booklet => unreachable!(),
+    }
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+enum Command {
+    Validate,
+    Stats,
+    Solve,
+    Generate,
+    Booklet,
+}
+
+#[derive(Debug)]
+struct Args {
+    command: Command,
+    files: Vec<String>,
+    limits: SolveLimits,
+    generate: GenerateOptions,
+    booklet: BookletArgs,
+}
+
+#[derive(Debug)]
+struct BookletArgs {
+    per_page: usize,
+    /// Print random levels instead of the levels of the packs.
+    generated: bool,
+}
+impl Default for BookletArgs {
+    fn default() -> Self {
+        Self {
+            per_page: BookletOptions::default().puzzles_per_page,
+            generated: false,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct GenerateOptions {
+    count: usize,
+    rows: i32,
+    columns: i32,
+    first_seed: u64,
+    seeds: u64,
+    filter: LevelFilter,
+}
+impl Default for GenerateOptions {
+    fn default() -> Self {
+        Self {
+            count: 10,
+            rows: NUM_ROWS,
+            columns: NUM_COLUMNS,
+            first_seed: 0,
+            seeds: 1000,
+            filter: LevelFilter::default(),
+        }
+    }
+}
+
+fn parse_args_pure<S: AsRef<str>>(raw_args: &[S]) -> Result<Args, AnyError> {
+    let mut positional = Vec::new();
+    let mut limits = SolveLimits::default();
+    let mut generate = GenerateOptions::default();
+    let mut booklet = BookletArgs::default();
+    for arg in raw_args.iter().skip(1).map(|arg| arg.as_ref()) {
+        if let Some(flag) = arg.strip_prefix("--") {
+            let (name, value) = flag
+                .split_once('=')
+                .ok_or_else(|| format!("flag '{}' needs a value, like --{}=VALUE", arg, flag))?;
+            let filter = &mut generate.filter;
+            match name {
+                "max-solutions" => limits.max_solutions = parse_as(value, "usize")?,
+                "count" => generate.count = parse_as(value, "usize")?,
+                "rows" => generate.rows = parse_as(value, "i32")?,
+                "columns" => generate.columns = parse_as(value, "i32")?,
+                "seed" => generate.first_seed = parse_as(value, "u64")?,
+                "seeds" => generate.seeds = parse_as(value, "u64")?,
+                "difficulty" => {
+                    let difficulty = Difficulty::from_text(value)
+                        .ok_or_else(|| format!("unknown difficulty '{}'", value))?;
+                    filter.difficulty = Some(difficulty);
+                }
+                "min-loop" => filter.min_loop_length = Some(parse_as(value, "i32")?),
+                "max-loop" => filter.max_loop_length = Some(parse_as(value, "i32")?),
+                "min-crossings" => filter.min_crossings = Some(parse_as(value, "i32")?),
+                "max-crossings" => filter.max_crossings = Some(parse_as(value, "i32")?),
+                "per-page" => booklet.per_page = parse_as(value, "usize")?,
+                "generate" => {
+                    generate.count = parse_as(value, "usize")?;
+                    booklet.generated = true;
+                }
+                _ => return Err(format!("unknown flag '{}'\n{}", arg, USAGE).into()),
+            }
+        } else {
+            positional.push(arg.to_string());
+        }
+    }
+    if positional.is_empty() {
+        return Err(USAGE.into());
+    }
+    let command = match positional.remove(0).as_str() {
+        "validate" => Command::Validate,
+        "stats" => Command::Stats,
+        "solve" => Command::Solve,
+        "generate" => Command::Generate,
+        "booklet" => Command::Booklet,
+        other => return Err(format!("unknown command '{}'\n{}", other, USAGE).into()),
+    };
+    Ok(Args {
+        command,
+        files: positional,
+        limits,
+        generate,
+        booklet,
+    })
+}
+
+fn parse_as<T: FromStr>(value: &str, type_name: &str) -> Result<T, AnyError>
+where
+    T::Err: std::fmt::Display,
+{
+    value
+        .parse()
+        .map_err(|e| format!("error parsing '{}' as {}: {}", value, type_name, e).into())
+}
+
+fn generate(files: &[String], options: &GenerateOptions) -> Result<(), AnyError> {
+    let [output] = files else {
+        return Err(format!("generate needs exactly one output file\n{}", USAGE).into());
+    };
+    let seeds = options.first_seed..options.first_seed + options.seeds;
+    let levels = generate_batch(
+        options.rows,
+        options.columns,
+        seeds.clone(),
+        options.count,
+        &options.filter,
+    );
+    if levels.len() < options.count {
+        eprintln!(
+            "only {} out of {} levels passed the filters with seeds {}..{}",
+            levels.len(),
+            options.count,
+            seeds.start,
+            seeds.end
+        );
+    }
+    let section = Section {
+        metadata: Metadata {
+            format_version: Some(CURRENT_FORMAT_VERSION),
+            ..Default::default()
+        },
+        levels,
+    };
+    std::fs::write(output, section.to_string())
+        .map_err(|e| format!("could not write {}: {}", output, e))?;
+    println!("wrote {} levels to {}", section.levels.len(), output);
+    Ok(())
+}
+
+fn booklet(
+    files: &[String],
+    args: &BookletArgs,
+    generate: &GenerateOptions,
+) -> Result<(), AnyError> {
+    let Some((output, pack_files)) = files.split_first() else {
+        return Err(format!("booklet needs an output file\n{}", USAGE).into());
+    };
+    let mut levels = Vec::new();
+    if args.generated {
+        if !pack_files.is_empty() {
+            return Err("booklet takes either pack files or --generate, not both".into());
+        }
+        let seeds = generate.first_seed..generate.first_seed + generate.seeds;
+        for level in generate_batch(
+            generate.rows,
+            generate.columns,
+            seeds,
+            generate.count,
+            &generate.filter,
+        ) {
+            let label = level.metadata.name.clone().unwrap_or_default();
+            levels.push((label, level));
+        }
+    } else {
+        let packs = load_packs(pack_files)?;
+        for_each_level(&packs, |label, level| levels.push((label, level.clone())));
+    }
+    let options = BookletOptions {
+        puzzles_per_page: args.per_page,
+        ..Default::default()
+    };
+    std::fs::write(output, export_booklet(&levels, &options))
+        .map_err(|e| format!("could not write {}: {}", output, e))?;
+    println!("wrote {} puzzles to {}", levels.len(), output);
+    Ok(())
+}
+
+/// A named list of sections, so that messages can say where a level comes from.
+struct Pack {
+    name: String,
+    sections: Vec<Section>,
+}
+
+fn load_packs(files: &[String]) -> Result<Vec<Pack>, AnyError> {
+    if files.is_empty() {
+        let levels = Levels::get()?;
+        return Ok(vec![Pack {
+            name: "campaign".to_string(),
+            sections: levels.sections,
+        }]);
+    }
+    let mut packs = Vec::new();
+    for file in files {
+        let text =
+            std::fs::read_to_string(file).map_err(|e| format!("could not read {}: {}", file, e))?;
+        let section = Section::from_str(&text).map_err(|e| format!("in {}: {}", file, e))?;
+        packs.push(Pack {
+            name: file.clone(),
+            sections: vec![section],
+        });
+    }
+    Ok(packs)
+}
+
+fn for_each_level(packs: &[Pack], mut f: impl FnMut(String, &Level)) {
+    for pack in packs {
+        for (i_section, section) in pack.sections.iter().enumerate() {
+            for (i_level, level) in section.levels.iter().enumerate() {
+                let mut label = format!("{} {}-{}", pack.name, i_section, i_level);
+                if let Some(name) = &level.metadata.name {
+                    label += &format!(" '{}'", name);
+                }
+                f(label, level);
+            }
+        }
+    }
+}
+
+fn validate(packs: &[Pack]) -> Result<(), AnyError> {
+    let mut failed = 0;
+    let mut total = 0;
+    for_each_level(packs, |label, level| {
+        total += 1;
+        let problems = validate_level(level);
+        if problems.is_empty() {
+            println!("{}: ok", label);
+        } else {
+            failed += 1;
+            for problem in problems {
+                println!("{}: {}", label, problem);
+            }
+        }
+    });
+    if failed == 0 {
+        println!("all {} levels are valid", total);
+        Ok(())
+    } else {
+        Err(format!("{} out of {} levels are invalid", failed, total).into())
+    }
+}
+
+fn validate_level(level: &Level) -> Vec<String> {
+    let mut problems = Vec::new();
+    let satisfaction = compute_satisfaction(&level.solution, &level.constraints);
+    if !satisfaction.success() {
+        problems.push(format!(
+            "the solution doesn't satisfy the constraints: stations {}, cells {}, reachable rails {}",
+            satisfaction.stations.format(),
+            satisfaction.cell_count.format(),
+            satisfaction.reachable.format(),
+        ));
+    }
+    let text = level.to_string();
+    match Level::from_str(&text) {
+        Ok(reparsed) => {
+            if reparsed.to_string() != text {
+                problems.push("the level changes when printed and parsed again".to_string());
+            }
+        }
+        Err(e) => problems.push(format!("the printed level can't be parsed: {}", e)),
+    }
+    for constraint in &level.constraints.rails {
+        let (row, column) = constraint.row_column();
+        if is_border_rail(&level.solution, row, column, constraint.direction()) {
+            problems.push(format!(
+                "the constraint at row {}, column {} is on a border rail",
+                row, column
+            ));
+        }
+    }
+    problems
+}
+
+fn stats(packs: &[Pack]) {
+    for_each_level(packs, |label, level| {
+        let (stations, blockades) = count_clues(&level.constraints);
+        println!(
+            "{}: {}x{}, {} stations, {} blockades, {} cells, loop length {}, {} crossings",
+            label,
+            level.solution.rows(),
+            level.solution.columns(),
+            stations,
+            blockades,
+            level.constraints.cell_count,
+            level.solution.total_rails,
+            count_crossings(&level.solution.intersections),
+        );
+    });
+}
+
+fn count_clues(constraints: &Constraints) -> (i32, i32) {
+    let mut stations = 0;
+    let mut blockades = 0;
+    for rail in &constraints.rails {
+        match rail.type_() {
+            Constraint::Station(_) => stations += 1,
+            Constraint::Blockade => blockades += 1,
+        }
+    }
+    (stations, blockades)
+}
+
+fn solve_all(packs: &[Pack], limits: &SolveLimits) {
+    for_each_level(packs, |label, level| {
+        let solutions = solve(&level.initial_grid, &level.constraints, limits);
+        let count = if solutions.complete {
+            format!("{}", solutions.grids.len())
+        } else {
+            format!("at least {}", solutions.grids.len())
+        };
+        println!("{}: {} solutions", label, count);
+        for grid in &solutions.grids {
+            println!("{}", rails_to_string(grid));
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_args() {
+        let args =
+            parse_args_pure(&["metro-loop-cli", "solve", "a.txt", "--max-solutions=5"]).unwrap();
+        assert_eq!(args.command, Command::Solve);
+        assert_eq!(args.files, vec!["a.txt".to_string()]);
+        assert_eq!(args.limits.max_solutions, 5);
+
+        assert!(parse_args_pure(&["metro-loop-cli"]).is_err());
+        assert!(parse_args_pure(&["metro-loop-cli", "play"]).is_err());
+        assert!(parse_args_pure(&["metro-loop-cli", "stats", "--max-solutions"]).is_err());
+
+        let args = parse_args_pure(&[
+            "metro-loop-cli",
+            "generate",
+            "pack.txt",
+            "--count=3",
+            "--difficulty=hard",
+            "--max-crossings=0",
+        ])
+        .unwrap();
+        assert_eq!(args.command, Command::Generate);
+        assert_eq!(args.generate.count, 3);
+        assert_eq!(args.generate.filter.difficulty, Some(Difficulty::Hard));
+        assert_eq!(args.generate.filter.max_crossings, Some(0));
+
+        let args = parse_args_pure(&[
+            "metro-loop-cli",
+            "booklet",
+            "out.html",
+            "--generate=6",
+            "--per-page=2",
+        ])
+        .unwrap();
+        assert_eq!(args.command, Command::Booklet);
+        assert_eq!(args.generate.count, 6);
+        assert!(args.booklet.generated);
+        assert_eq!(args.booklet.per_page, 2);
+    }
+
+    #[test]
+    fn test_campaign_is_valid() {
+        let packs = load_packs(&[]).unwrap();
+        for_each_level(&packs, |label, level| {
+            assert_eq!(validate_level(level), Vec::<String>::new(), "{}", label);
+        });
+    }
+
+    #[test]
+    fn test_invalid_level() {
+        let level = Level::from_str(
+            r#".-.-.-.
+"------
+.-%-x-.
+-------
+.-.-.-.
+"#,
+        )
+        .unwrap();
+        let problems = validate_level(&level);
+        assert_eq!(problems.len(), 1, "{:?}", problems);
+        assert!(problems[0].contains("border rail"));
+    }
+}
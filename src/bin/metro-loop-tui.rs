@@ -0,0 +1,667 @@
+//! Plays levels in a terminal, for SSH sessions and quick playtests of level packs.
+//!
+//! Usage: `metro-loop-tui [PACK_FILE]`. Without a pack file it plays the built-in campaign.
+//!
+//! Keys: arrows or `hjkl` move the cursor, space toggles a block, `f` locks a block, `b` and `s`
+//! followed by an arrow toggle a blockade or cycle a station on that side of the block, `n` and `p`
+//! change level, `r` restarts it and `q` quits.
+//!
+//! The terminal is switched to raw mode with `stty`, so this only works on unix-like systems.
+
+use macroquad::color::Color;
+use metro_loop::direction::{Direction, Horizontal, Sense, Vertical};
+use metro_loop::level_history::GameTrack;
+use metro_loop::levels::{Level, Levels, Section};
+use metro_loop::logic::constraints::{
+    compute_satisfaction, matches_constraint_and_reachable, Goal, RailCoord,
+};
+use metro_loop::logic::grid::{get, get_cell, is_system_fixed, Grid, UserFix};
+use metro_loop::logic::pixel_grid::Coord;
+use metro_loop::scenes::play::controller::{PlayController, PlayEffect, PlayInput, Rail};
+use metro_loop::scenes::play::Tooltips;
+use metro_loop::{
+    AnyError, DISABLED_CELL, ENABLED_CELL, FAILING, FIX_MARKER, HOVERED_CELL, LIGHTLIGHTGRAY, RAIL,
+    SUCCESS, UNREACHABLE_RAIL, USER_FIX_MARKER,
+};
+use std::io::{Read, Write};
+use std::process::{Command, Stdio};
+
+const KEYS_HELP: &str =
+    "arrows/hjkl: move  space: toggle  f: lock  b/s+arrow: blockade/station  n/p: level  r: restart  q: quit";
+
+fn main() {
+    if let Err(e) = run() {
+        eprintln!("error: {}", e);
+        std::process::exit(1);
+    }
+}
+
+fn run() -> Result<(), AnyError> {
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    let (name, levels) = load_levels(&args)?;
+    if levels
+        .sections
+        .iter()
+        .all(|section| section.levels.is_empty())
+    {
+        return Err("there are no levels to play".into());
+    }
+    let mut game = Game::new(&name, levels);
+    let _raw_mode = RawMode::enable()?;
+    let mut stdin = std::io::stdin();
+    let mut stdout = std::io::stdout();
+    let mut buffer = [0; 64];
+    loop {
+        let screen = game.render().replace('\n', "\r\n");
+        write!(stdout, "\x1b[2J\x1b[H{}", screen)?;
+        stdout.flush()?;
+        let read = stdin.read(&mut buffer)?;
+        if read == 0 {
+            return Ok(());
+        }
+        for key in parse_keys(&buffer[..read]) {
+            if game.press(key) == Flow::Quit {
+                return Ok(());
+            }
+        }
+    }
+}
+
+/// The name of the pack, and its levels.
+fn load_levels(args: &[String]) -> Result<(String, Levels), AnyError> {
+    match args {
+        [] => Ok(("campaign".to_string(), Levels::get()?)),
+        [file] => {
+            let text = std::fs::read_to_string(file)
+                .map_err(|e| format!("could not read {}: {}", file, e))?;
+            let section = Section::from_str(&text).map_err(|e| format!("in {}: {}", file, e))?;
+            let sections = vec![section];
+            Ok((file.clone(), Levels { sections }))
+        }
+        _ => Err("usage: metro-loop-tui [PACK_FILE]".into()),
+    }
+}
+
+/// Restores the previous terminal settings when dropped.
+struct RawMode {
+    saved: String,
+}
+impl RawMode {
+    fn enable() -> Result<Self, AnyError> {
+        let saved = stty(&["-g"])?;
+        stty(&["raw", "-echo"])?;
+        print!("\x1b[?25l");
+        Ok(Self {
+            saved: saved.trim().to_string(),
+        })
+    }
+}
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        print!("\x1b[?25h\r\n");
+        let _ = std::io::stdout().flush();
+        let _ = stty(&[&self.saved]);
+    }
+}
+
+fn stty(args: &[&str]) -> Result<String, AnyError> {
+    let output = Command::new("stty")
+        .args(args)
+        .stdin(Stdio::inherit())
+        .output()
+        .map_err(|e| format!("could not run stty: {}", e))?;
+    if !output.status.success() {
+        return Err(format!(
+            "stty failed, is this a terminal? {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )
+        .into());
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Key {
+    Up,
+    Down,
+    Left,
+    Right,
+    Char(char),
+}
+
+fn parse_keys(bytes: &[u8]) -> Vec<Key> {
+    let mut keys = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == 0x1b && i + 2 < bytes.len() && bytes[i + 1] == b'[' {
+            let arrow = match bytes[i + 2] {
+                b'A' => Some(Key::Up),
+                b'B' => Some(Key::Down),
+                b'C' => Some(Key::Right),
+                b'D' => Some(Key::Left),
+                _ => None,
+            };
+            if let Some(arrow) = arrow {
+                keys.push(arrow);
+                i += 3;
+                continue;
+            }
+        }
+        keys.push(match bytes[i] {
+            b'k' => Key::Up,
+            b'j' => Key::Down,
+            b'l' => Key::Right,
+            b'h' => Key::Left,
+            // ctrl-c, as raw mode doesn't turn it into a signal
+            3 => Key::Char('q'),
+            byte => Key::Char(byte as char),
+        });
+        i += 1;
+    }
+    keys
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Flow {
+    Continue,
+    Quit,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Mark {
+    Blockade,
+    Station,
+}
+
+/// Plays through the same `PlayController` as the window, so that the rules are the same.
+struct Game {
+    levels: Levels,
+    /// Section, level and label of every level, in the order that `n` and `p` go through them.
+    order: Vec<(i32, i32, String)>,
+    current: usize,
+    game_track: GameTrack,
+    /// Row and column of the cursor.
+    cursor: (i32, i32),
+    /// Set after `b` or `s`, waiting for the arrow that says which side of the block.
+    pending_mark: Option<Mark>,
+    message: String,
+}
+
+impl Game {
+    fn new(name: &str, levels: Levels) -> Self {
+        let mut order = Vec::new();
+        for (i_section, section) in levels.sections.iter().enumerate() {
+            for (i_level, level) in section.levels.iter().enumerate() {
+                let mut label = format!("{} {}-{}", name, i_section, i_level);
+                if let Some(level_name) = &level.metadata.name {
+                    label += &format!(" '{}'", level_name);
+                }
+                order.push((i_section as i32, i_level as i32, label));
+            }
+        }
+        let (section, level, _) = order[0];
+        let game_track = new_game_track(section, level, &levels);
+        let grid = &game_track.in_progress;
+        let cursor = (grid.root.y, grid.root.x);
+        Self {
+            levels,
+            order,
+            current: 0,
+            game_track,
+            cursor,
+            pending_mark: None,
+            message: String::new(),
+        }
+    }
+
+    fn level(&self) -> &Level {
+        self.game_track.get_current()
+    }
+
+    fn grid(&self) -> &Grid {
+        &self.game_track.in_progress
+    }
+
+    fn load(&mut self, index: usize) {
+        self.current = index;
+        let (section, level, _) = self.order[index];
+        self.game_track = new_game_track(section, level, &self.levels);
+        self.cursor = (self.grid().root.y, self.grid().root.x);
+        self.pending_mark = None;
+    }
+
+    fn press(&mut self, key: Key) -> Flow {
+        self.message.clear();
+        if let Some(mark) = self.pending_mark.take() {
+            match key {
+                Key::Up | Key::Down | Key::Left | Key::Right => self.toggle_mark(mark, key),
+                _ => self.message = "cancelled".to_string(),
+            }
+            return Flow::Continue;
+        }
+        let (row, column) = self.cursor;
+        let coord = Coord::new_i(row, column);
+        let levels = self.order.len();
+        match key {
+            Key::Up => self.cursor.0 = (row - 1).max(0),
+            Key::Down => self.cursor.0 = (row + 1).min(self.grid().rows() - 1),
+            Key::Left => self.cursor.1 = (column - 1).max(0),
+            Key::Right => self.cursor.1 = (column + 1).min(self.grid().columns() - 1),
+            Key::Char(' ') => self.apply(PlayInput::ToggleCell(coord)),
+            Key::Char('f') => self.apply(PlayInput::ToggleLock(coord)),
+            Key::Char('b') => self.pending_mark = Some(Mark::Blockade),
+            Key::Char('s') => self.pending_mark = Some(Mark::Station),
+            Key::Char('n') => self.load((self.current + 1) % levels),
+            Key::Char('p') => self.load((self.current + levels - 1) % levels),
+            Key::Char('r') => self.apply(PlayInput::Restart),
+            Key::Char('q') => return Flow::Quit,
+            Key::Char(_) => {}
+        }
+        Flow::Continue
+    }
+
+    fn apply(&mut self, input: PlayInput) {
+        let effects = PlayController::new(&mut self.game_track).apply(input);
+        for effect in effects {
+            self.message = match effect {
+                PlayEffect::Tooltip(Tooltips::FixedCell) => "this block can't change",
+                PlayEffect::Tooltip(Tooltips::UserFixedCell) => {
+                    "this block is locked, press f to unlock it"
+                }
+                _ => continue,
+            }
+            .to_string();
+        }
+    }
+
+    fn toggle_mark(&mut self, mark: Mark, side: Key) {
+        let (row, column) = self.cursor;
+        let (direction, row, column) = match side {
+            Key::Up => (Direction::Horizontal, row, column),
+            Key::Down => (Direction::Horizontal, row + 1, column),
+            Key::Left => (Direction::Vertical, row, column),
+            Key::Right => (Direction::Vertical, row, column + 1),
+            Key::Char(_) => unreachable!(),
+        };
+        let rail = Rail {
+            direction,
+            row,
+            column,
+        };
+        match mark {
+            Mark::Blockade => self.apply(PlayInput::ToggleBlockade(rail)),
+            Mark::Station => {
+                let rails = &self.grid().fixed_rails;
+                let fix = match direction {
+                    Direction::Horizontal => rails.get_horiz(row, column),
+                    Direction::Vertical => rails.get_vert(row, column),
+                };
+                // none -> forward -> backwards -> none
+                let toggled: &[Sense] = match (fix.station_forward, fix.station_backwards) {
+                    (false, false) => &[Sense::Forwards],
+                    (true, _) => &[Sense::Forwards, Sense::Backwards],
+                    (false, true) => &[Sense::Backwards],
+                };
+                for sense in toggled {
+                    self.apply(PlayInput::ToggleStation(rail, *sense));
+                }
+            }
+        }
+    }
+
+    fn render(&self) -> String {
+        let (_, _, label) = &self.order[self.current];
+        let level = self.level();
+        let mut screen = format!("{}\n\n", label);
+        screen += &render_board(level, self.grid(), Some(self.cursor));
+        let satisfaction = compute_satisfaction(self.grid(), &level.constraints);
+        screen += "\n";
+        if satisfaction.success() {
+            screen += &paint_fg("SOLVED!", SUCCESS);
+        } else {
+            screen += &render_goal("Satisfied bridges and stations", &satisfaction.stations);
+            screen += "  ";
+            screen += &render_goal("Active blocks", &satisfaction.cell_count);
+            screen += "  ";
+            screen += &render_goal("Reachable rails", &satisfaction.reachable);
+        }
+        screen += "\n";
+        if let Some(hint) = &level.metadata.hint {
+            screen += &format!("hint: {}\n", hint);
+        }
+        screen += "\n";
+        screen += match self.pending_mark {
+            Some(Mark::Blockade) => "blockade on which side? (arrow)",
+            Some(Mark::Station) => "station on which side? (arrow)",
+            None => &self.message,
+        };
+        screen += "\n";
+        screen += KEYS_HELP;
+        screen += "\n";
+        screen
+    }
+}
+
+fn new_game_track(section: i32, level: i32, levels: &Levels) -> GameTrack {
+    GameTrack::new(section, level, levels).expect("the level should be in the pack")
+}
+
+fn render_goal(text: &str, goal: &Goal) -> String {
+    let color = if goal.success() { SUCCESS } else { FAILING };
+    paint_fg(&format!("{}: {}", text, goal.format()), color)
+}
+
+/// Draws the board like `GridAndRails`, with box-drawing characters in the colors of the game.
+/// Each block takes 3 columns, and the rails run along the lines between blocks.
+fn render_board(level: &Level, grid: &Grid, cursor: Option<(i32, i32)>) -> String {
+    let mut out = String::new();
+    for row in 0..=grid.rows() {
+        for column in 0..=grid.columns() {
+            out += &paint_fg(&intersection_char(grid, row, column).to_string(), RAIL);
+            if column < grid.columns() {
+                out += &horizontal_rail(level, grid, row, column);
+            }
+        }
+        out += "\n";
+        if row == grid.rows() {
+            break;
+        }
+        for column in 0..=grid.columns() {
+            out += &vertical_rail(level, grid, row, column);
+            if column < grid.columns() {
+                out += &cell(grid, row, column, cursor == Some((row, column)));
+            }
+        }
+        out += "\n";
+    }
+    out
+}
+
+fn cell(grid: &Grid, row: i32, column: i32, is_cursor: bool) -> String {
+    let background = if is_cursor {
+        HOVERED_CELL
+    } else if *get_cell(grid, row, column) {
+        ENABLED_CELL
+    } else {
+        DISABLED_CELL
+    };
+    let (mark, color) = if is_system_fixed(grid, row, column) {
+        ('▪', FIX_MARKER)
+    } else if *get(&grid.fixed_cells, row, column) {
+        ('•', LIGHTLIGHTGRAY)
+    } else {
+        (' ', LIGHTLIGHTGRAY)
+    };
+    let (left, right) = if is_cursor { ('[', ']') } else { (' ', ' ') };
+    let text = format!("{}{}{}", left, mark, right);
+    paint(&text, color, background)
+}
+
+fn horizontal_rail(level: &Level, grid: &Grid, row: i32, column: i32) -> String {
+    let rail = grid.rails.get_horiz(row, column);
+    let line = if rail == Horizontal::Center {
+        ' '
+    } else {
+        '─'
+    };
+    let rail_color = if grid.reachable_rails.get_horiz(row, column) {
+        RAIL
+    } else {
+        UNREACHABLE_RAIL
+    };
+    let user_fix = grid.fixed_rails.get_horiz(row, column);
+    let (middle, color) = clue(level, grid, row, column, true)
+        .or_else(|| user_mark(user_fix, ('◀', '▶'), '┃'))
+        .unwrap_or(match rail {
+            Horizontal::Left => ('←', rail_color),
+            Horizontal::Center => (' ', rail_color),
+            Horizontal::Right => ('→', rail_color),
+        });
+    let line = paint_fg(&line.to_string(), rail_color);
+    format!("{}{}{}", line, paint_fg(&middle.to_string(), color), line)
+}
+
+fn vertical_rail(level: &Level, grid: &Grid, row: i32, column: i32) -> String {
+    let rail = grid.rails.get_vert(row, column);
+    let rail_color = if grid.reachable_rails.get_vert(row, column) {
+        RAIL
+    } else {
+        UNREACHABLE_RAIL
+    };
+    let user_fix = grid.fixed_rails.get_vert(row, column);
+    let (glyph, color) = clue(level, grid, row, column, false)
+        .or_else(|| user_mark(user_fix, ('▲', '▼'), '━'))
+        .unwrap_or(match rail {
+            Vertical::Top => ('↑', rail_color),
+            Vertical::Center => (' ', rail_color),
+            Vertical::Bottom => ('↓', rail_color),
+        });
+    paint_fg(&glyph.to_string(), color)
+}
+
+/// The clue of the level on this rail, colored by whether it's satisfied.
+fn clue(
+    level: &Level,
+    grid: &Grid,
+    row: i32,
+    column: i32,
+    horizontal: bool,
+) -> Option<(char, Color)> {
+    let constraint = level.constraints.rails.iter().find(|constraint| {
+        constraint.row_column() == (row, column)
+            && matches!(constraint, RailCoord::Horizontal { .. }) == horizontal
+    })?;
+    let (success, _, _) = matches_constraint_and_reachable(grid, constraint);
+    let color = if success { SUCCESS } else { FAILING };
+    let glyph = match constraint {
+        RailCoord::Horizontal { sense, .. } => match sense {
+            Horizontal::Left => '◀',
+            Horizontal::Center => '┃',
+            Horizontal::Right => '▶',
+        },
+        RailCoord::Vertical { sense, .. } => match sense {
+            Vertical::Top => '▲',
+            Vertical::Center => '━',
+            Vertical::Bottom => '▼',
+        },
+    };
+    Some((glyph, color))
+}
+
+/// The marks that the player wrote on a rail. `stations` are the backwards and forward glyphs.
+fn user_mark(fix: UserFix, stations: (char, char), blockade: char) -> Option<(char, Color)> {
+    let glyph = if fix.blockade {
+        blockade
+    } else if fix.station_forward {
+        stations.1
+    } else if fix.station_backwards {
+        stations.0
+    } else {
+        return None;
+    };
+    Some((glyph, USER_FIX_MARKER))
+}
+
+fn intersection_char(grid: &Grid, row: i32, column: i32) -> char {
+    let rails = &grid.rails;
+    let up = row > 0
+        && column < rails.vert_columns()
+        && rails.get_vert(row - 1, column) != Vertical::Center;
+    let down = row < rails.vert_rows()
+        && column < rails.vert_columns()
+        && rails.get_vert(row, column) != Vertical::Center;
+    let left = column > 0 && rails.get_horiz(row, column - 1) != Horizontal::Center;
+    let right =
+        column < rails.horiz_columns() && rails.get_horiz(row, column) != Horizontal::Center;
+    match (up, down, left, right) {
+        (false, false, false, false) => '·',
+        (true, true, true, true) => '┼',
+        (true, true, true, false) => '┤',
+        (true, true, false, true) => '├',
+        (true, false, true, true) => '┴',
+        (false, true, true, true) => '┬',
+        (true, true, false, false) => '│',
+        (false, false, true, true) => '─',
+        (true, false, true, false) => '┘',
+        (true, false, false, true) => '└',
+        (false, true, true, false) => '┐',
+        (false, true, false, true) => '┌',
+        (true, false, false, false) | (false, true, false, false) => '│',
+        (false, false, true, false) | (false, false, false, true) => '─',
+    }
+}
+
+fn paint_fg(text: &str, color: Color) -> String {
+    let [r, g, b, _]: [u8; 4] = color.into();
+    format!("\x1b[38;2;{};{};{}m{}\x1b[0m", r, g, b, text)
+}
+
+fn paint(text: &str, foreground: Color, background: Color) -> String {
+    let [r, g, b, _]: [u8; 4] = background.into();
+    let text = format!("\x1b[48;2;{};{};{}m{}", r, g, b, text);
+    let [r, g, b, _]: [u8; 4] = foreground.into();
+    format!("\x1b[38;2;{};{};{}m{}\x1b[0m", r, g, b, text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strip_colors(text: &str) -> String {
+        let mut plain = String::new();
+        let mut in_escape = false;
+        for letter in text.chars() {
+            if letter == '\x1b' {
+                in_escape = true;
+            } else if in_escape {
+                in_escape = letter != 'm';
+            } else {
+                plain.push(letter);
+            }
+        }
+        plain
+    }
+
+    #[test]
+    fn test_parse_keys() {
+        let keys = parse_keys(b"\x1b[A\x1b[Dx j\x03");
+        let expected = [
+            Key::Up,
+            Key::Left,
+            Key::Char('x'),
+            Key::Char(' '),
+            Key::Down,
+            Key::Char('q'),
+        ];
+        assert_eq!(keys, expected);
+    }
+
+    #[test]
+    fn test_render_board() {
+        let level = Level::from_str(
+            r#".-.-.-.-.
+---------
+.-.-x-.-.
+----"----
+.-.-%-.-.
+---------
+.-.-.-.-.
+"#,
+        )
+        .unwrap();
+        let board = strip_colors(&render_board(&level, &level.solution, None));
+        let expected = "\
+·   ·   ·   ·   ·   ·
+  ▪   ▪   ▪   ▪   ▪  
+·   ·   ·   ·   ·   ·
+  ▪   •   ▪   •   ▪  
+·   ·   ┌─┃─┐   ·   ·
+  ▪   • ↑ ▪ ↓ •   ▪  
+·   ·   └─←─┘   ·   ·
+  ▪   ▪   ▪   ▪   ▪  
+·   ·   ·   ·   ·   ·
+";
+        assert_eq!(board, expected, "\n{}", board);
+    }
+
+    #[test]
+    fn test_play() {
+        let (name, levels) = load_levels(&[]).unwrap();
+        let mut game = Game::new(&name, levels);
+        let level = game.level().clone();
+        assert!(!compute_satisfaction(game.grid(), &level.constraints).success());
+        let (row, column) = game.cursor;
+        game.press(Key::Char(' '));
+        assert!(!game.message.is_empty(), "the root can't be toggled");
+
+        for (key, side) in [(Key::Char('b'), Key::Left), (Key::Char('s'), Key::Up)] {
+            game.press(key);
+            game.press(side);
+        }
+        assert!(game.grid().fixed_rails.get_vert(row, column).blockade);
+        assert!(
+            game.grid()
+                .fixed_rails
+                .get_horiz(row, column)
+                .station_forward
+        );
+        game.press(Key::Char('s'));
+        game.press(Key::Up);
+        assert!(
+            game.grid()
+                .fixed_rails
+                .get_horiz(row, column)
+                .station_backwards
+        );
+
+        for row in 0..level.solution.rows() {
+            for column in 0..level.solution.columns() {
+                let wanted = *get_cell(&level.solution, row, column);
+                if *get_cell(game.grid(), row, column) != wanted {
+                    game.cursor = (row, column);
+                    game.press(Key::Char(' '));
+                }
+            }
+        }
+        assert!(compute_satisfaction(game.grid(), &level.constraints).success());
+        assert!(game.render().contains("SOLVED!"));
+
+        game.press(Key::Char('n'));
+        assert_eq!(game.current, 1);
+        assert_eq!(game.press(Key::Char('q')), Flow::Quit);
+    }
+
+    #[test]
+    fn test_level_locks() {
+        let level = Level::from_str(
+            r#".-.-.-.-.
+---------
+.-.-x-.-.
+---------
+.-@-%-x-.
+---------
+.-.-.-.-.
+"#,
+        )
+        .unwrap();
+        let metadata = Default::default();
+        let levels = vec![level];
+        let sections = vec![Section { metadata, levels }];
+        let mut game = Game::new("pack", Levels { sections });
+        game.cursor = (2, 1);
+        for key in [Key::Char('f'), Key::Char(' ')] {
+            game.press(key);
+            assert_eq!(game.message, "this block can't change");
+        }
+        assert!(*get(&game.grid().fixed_cells, 2, 1));
+        assert!(*get_cell(game.grid(), 2, 1));
+
+        game.cursor = (2, 3);
+        game.press(Key::Char('f'));
+        game.press(Key::Char(' '));
+        assert_eq!(game.message, "this block is locked, press f to unlock it");
+        game.press(Key::Char('f'));
+        game.press(Key::Char(' '));
+        assert!(*get_cell(game.grid(), 2, 3));
+    }
+}
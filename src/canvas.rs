@@ -0,0 +1,199 @@
+//! Drawing primitives that the board rendering goes through, so that the same code can draw on
+//! screen, into an SVG, or into a list of calls that tests can inspect.
+
+use juquad::draw::{draw_rect, draw_rect_lines};
+use macroquad::prelude::*;
+
+pub trait Canvas {
+    fn line(&mut self, start: Vec2, end: Vec2, thickness: f32, color: Color);
+    fn triangle(&mut self, p_1: Vec2, p_2: Vec2, p_3: Vec2, color: Color);
+    fn triangle_lines(&mut self, p_1: Vec2, p_2: Vec2, p_3: Vec2, thickness: f32, color: Color);
+    fn rect(&mut self, rect: Rect, color: Color);
+    /// The border is drawn inside `rect`.
+    fn rect_lines(&mut self, rect: Rect, thickness: f32, color: Color);
+
+    fn lines(&mut self, points: &[Vec2], thickness: f32, color: Color) {
+        assert!(points.len() >= 2);
+        for i in 1..points.len() {
+            self.line(points[i - 1], points[i], thickness, color);
+        }
+    }
+    /// Triangle strip: every vertex after the first two makes a triangle with the previous two.
+    fn triangles(&mut self, vertices: &[Vec2], color: Color) {
+        assert!(vertices.len() >= 3);
+        for i in 2..vertices.len() {
+            let (prev_prev, prev) = if i % 2 == 0 {
+                (i - 2, i - 1)
+            } else {
+                (i - 1, i - 2)
+            };
+            self.triangle(vertices[prev_prev], vertices[prev], vertices[i], color);
+        }
+    }
+    fn bordered_triangle(&mut self, p_1: Vec2, p_2: Vec2, p_3: Vec2, color: Color, border: Color) {
+        self.triangle(p_1, p_2, p_3, color);
+        self.triangle_lines(p_1, p_2, p_3, 1.0, border);
+    }
+}
+
+/// Draws on the screen, or on the current render target.
+pub struct MacroquadCanvas;
+
+impl Canvas for MacroquadCanvas {
+    fn line(&mut self, start: Vec2, end: Vec2, thickness: f32, color: Color) {
+        draw_line(start.x, start.y, end.x, end.y, thickness, color)
+    }
+    fn triangle(&mut self, p_1: Vec2, p_2: Vec2, p_3: Vec2, color: Color) {
+        draw_triangle(p_1, p_2, p_3, color)
+    }
+    fn triangle_lines(&mut self, p_1: Vec2, p_2: Vec2, p_3: Vec2, thickness: f32, color: Color) {
+        draw_triangle_lines(p_1, p_2, p_3, thickness, color)
+    }
+    fn rect(&mut self, rect: Rect, color: Color) {
+        draw_rect(rect, color)
+    }
+    fn rect_lines(&mut self, rect: Rect, thickness: f32, color: Color) {
+        draw_rect_lines(rect, thickness, color)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum DrawCall {
+    Line {
+        start: Vec2,
+        end: Vec2,
+        thickness: f32,
+        color: Color,
+    },
+    Triangle {
+        points: [Vec2; 3],
+        color: Color,
+    },
+    TriangleLines {
+        points: [Vec2; 3],
+        thickness: f32,
+        color: Color,
+    },
+    Rect {
+        rect: Rect,
+        color: Color,
+    },
+    RectLines {
+        rect: Rect,
+        thickness: f32,
+        color: Color,
+    },
+}
+
+impl DrawCall {
+    pub fn color(&self) -> Color {
+        match self {
+            DrawCall::Line { color, .. }
+            | DrawCall::Triangle { color, .. }
+            | DrawCall::TriangleLines { color, .. }
+            | DrawCall::Rect { color, .. }
+            | DrawCall::RectLines { color, .. } => *color,
+        }
+    }
+}
+
+/// Keeps every call instead of drawing, to check the rendering without a window.
+#[derive(Default)]
+pub struct RecordingCanvas {
+    pub calls: Vec<DrawCall>,
+}
+
+impl RecordingCanvas {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn count_color(&self, color: Color) -> usize {
+        self.calls
+            .iter()
+            .filter(|call| call.color() == color)
+            .count()
+    }
+}
+
+impl Canvas for RecordingCanvas {
+    fn line(&mut self, start: Vec2, end: Vec2, thickness: f32, color: Color) {
+        self.calls.push(DrawCall::Line {
+            start,
+            end,
+            thickness,
+            color,
+        });
+    }
+    fn triangle(&mut self, p_1: Vec2, p_2: Vec2, p_3: Vec2, color: Color) {
+        self.calls.push(DrawCall::Triangle {
+            points: [p_1, p_2, p_3],
+            color,
+        });
+    }
+    fn triangle_lines(&mut self, p_1: Vec2, p_2: Vec2, p_3: Vec2, thickness: f32, color: Color) {
+        self.calls.push(DrawCall::TriangleLines {
+            points: [p_1, p_2, p_3],
+            thickness,
+            color,
+        });
+    }
+    fn rect(&mut self, rect: Rect, color: Color) {
+        self.calls.push(DrawCall::Rect { rect, color });
+    }
+    fn rect_lines(&mut self, rect: Rect, thickness: f32, color: Color) {
+        self.calls.push(DrawCall::RectLines {
+            rect,
+            thickness,
+            color,
+        });
+    }
+}
+
+/// Draws on another canvas with every colour changed, for previews and highlights on top of
+/// what is there.
+pub struct Recolored<'a, F: Fn(Color) -> Color> {
+    canvas: &'a mut dyn Canvas,
+    recolor: F,
+}
+
+impl<'a, F: Fn(Color) -> Color> Recolored<'a, F> {
+    pub fn new(canvas: &'a mut dyn Canvas, recolor: F) -> Self {
+        Self { canvas, recolor }
+    }
+}
+
+/// Multiplies the opacity of every colour by `alpha`.
+pub fn translucent(canvas: &mut dyn Canvas, alpha: f32) -> Recolored<'_, impl Fn(Color) -> Color> {
+    Recolored::new(canvas, move |color| {
+        Color::new(color.r, color.g, color.b, color.a * alpha)
+    })
+}
+
+/// Moves every colour towards white, by `amount` between 0 and 1.
+pub fn lightened(canvas: &mut dyn Canvas, amount: f32) -> Recolored<'_, impl Fn(Color) -> Color> {
+    Recolored::new(canvas, move |color| {
+        let light = |channel: f32| channel + (1.0 - channel) * amount;
+        Color::new(light(color.r), light(color.g), light(color.b), color.a)
+    })
+}
+
+impl<F: Fn(Color) -> Color> Canvas for Recolored<'_, F> {
+    fn line(&mut self, start: Vec2, end: Vec2, thickness: f32, color: Color) {
+        self.canvas
+            .line(start, end, thickness, (self.recolor)(color))
+    }
+    fn triangle(&mut self, p_1: Vec2, p_2: Vec2, p_3: Vec2, color: Color) {
+        self.canvas.triangle(p_1, p_2, p_3, (self.recolor)(color))
+    }
+    fn triangle_lines(&mut self, p_1: Vec2, p_2: Vec2, p_3: Vec2, thickness: f32, color: Color) {
+        let color = (self.recolor)(color);
+        self.canvas.triangle_lines(p_1, p_2, p_3, thickness, color)
+    }
+    fn rect(&mut self, rect: Rect, color: Color) {
+        self.canvas.rect(rect, (self.recolor)(color))
+    }
+    fn rect_lines(&mut self, rect: Rect, thickness: f32, color: Color) {
+        self.canvas
+            .rect_lines(rect, thickness, (self.recolor)(color))
+    }
+}
@@ -0,0 +1,460 @@
+//! Draws a board as an SVG image through the same `render_cells`, `render_grid` and
+//! `render_constraints` that draw it on screen. It doesn't need a window, and the same input
+//! always produces the same text.
+
+use crate::canvas::Canvas;
+use crate::direction::Sense;
+use crate::levels::Level;
+use crate::logic::constraints::Constraint;
+use crate::logic::grid::Grid;
+use crate::render::{
+    cell_top_left, draw_blockade, draw_rail, draw_station, render_cells, render_constraints,
+    render_grid,
+};
+use crate::theme::Layout;
+use crate::{
+    BACKGROUND, CELL_HEIGHT, CELL_PAD, CELL_WIDTH, DISABLED_CELL, ENABLED_CELL, FONT_SIZE,
+    GRID_PAD, SUCCESS, SUCCESS_DARK,
+};
+use macroquad::color::Color;
+use macroquad::math::{vec2, Rect, Vec2};
+use std::fmt::Write;
+
+#[derive(Clone, Debug)]
+pub struct ExportOptions {
+    pub cell_width: f32,
+    pub cell_height: f32,
+    pub cell_pad: f32,
+    pub grid_pad: f32,
+    /// Paint the background color behind the board. Otherwise it's transparent.
+    pub background: bool,
+}
+
+impl Default for ExportOptions {
+    fn default() -> Self {
+        Self {
+            cell_width: CELL_WIDTH,
+            cell_height: CELL_HEIGHT,
+            cell_pad: CELL_PAD,
+            grid_pad: GRID_PAD,
+            background: true,
+        }
+    }
+}
+
+impl ExportOptions {
+    pub fn layout(&self, rows: i32, columns: i32) -> Layout {
+        let cell = vec2(self.cell_width, self.cell_height) + self.cell_pad;
+        let cells = vec2(columns as f32, rows as f32);
+        let size = cells * cell - self.cell_pad + self.grid_pad * 2.0;
+        Layout {
+            screen_width: size.x,
+            screen_height: size.y,
+            font_size: FONT_SIZE,
+            cell_width: self.cell_width,
+            cell_height: self.cell_height,
+            grid_pad: self.grid_pad,
+            cell_pad: self.cell_pad,
+            default_rows: rows,
+            default_columns: columns,
+        }
+    }
+}
+
+/// Draws `grid` with the clues of `level`. Pass `level.initial_grid` for a puzzle to print,
+/// `level.solution` for its answer, or the board of a player for a snapshot of their progress.
+pub fn export_svg(level: &Level, grid: &Grid, options: &ExportOptions) -> String {
+    let layout = options.layout(grid.rows(), grid.columns());
+    let mut canvas = SvgCanvas::new(layout.screen_width, layout.screen_height);
+    if options.background {
+        let size = canvas.size;
+        canvas.rect(Rect::new(0.0, 0.0, size.x, size.y), BACKGROUND);
+    }
+    render_cells(&mut canvas, grid, &None, &layout);
+    render_grid(&mut canvas, grid, &layout);
+    render_constraints(&mut canvas, &level.constraints, grid, &layout);
+    canvas.finish()
+}
+
+/// Writes every primitive as an SVG element, with the coordinates rounded to 2 decimals.
+pub struct SvgCanvas {
+    size: Vec2,
+    out: String,
+}
+
+impl SvgCanvas {
+    pub fn new(width: f32, height: f32) -> Self {
+        let mut out = String::new();
+        writeln!(
+            out,
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="{w}" height="{h}" viewBox="0 0 {w} {h}">"#,
+            w = num(width),
+            h = num(height)
+        )
+        .unwrap();
+        Self {
+            size: vec2(width, height),
+            out,
+        }
+    }
+
+    pub fn finish(mut self) -> String {
+        self.out.push_str("</svg>\n");
+        self.out
+    }
+
+    fn polygon(&mut self, points: &[Vec2], fill: String, stroke: String) {
+        writeln!(
+            self.out,
+            r#"<polygon points="{}" {} {}/>"#,
+            points_attribute(points),
+            fill,
+            stroke
+        )
+        .unwrap();
+    }
+}
+
+impl Canvas for SvgCanvas {
+    fn line(&mut self, start: Vec2, end: Vec2, thickness: f32, color: Color) {
+        writeln!(
+            self.out,
+            r#"<line x1="{}" y1="{}" x2="{}" y2="{}" {} stroke-width="{}"/>"#,
+            num(start.x),
+            num(start.y),
+            num(end.x),
+            num(end.y),
+            paint("stroke", color),
+            num(thickness)
+        )
+        .unwrap();
+    }
+    fn triangle(&mut self, p_1: Vec2, p_2: Vec2, p_3: Vec2, color: Color) {
+        self.polygon(&[p_1, p_2, p_3], paint("fill", color), String::new());
+    }
+    fn triangle_lines(&mut self, p_1: Vec2, p_2: Vec2, p_3: Vec2, thickness: f32, color: Color) {
+        let stroke = format!(
+            r#"{} stroke-width="{}""#,
+            paint("stroke", color),
+            num(thickness)
+        );
+        self.polygon(&[p_1, p_2, p_3], r#"fill="none""#.to_string(), stroke);
+    }
+    fn rect(&mut self, rect: Rect, color: Color) {
+        writeln!(
+            self.out,
+            r#"<rect x="{}" y="{}" width="{}" height="{}" {}/>"#,
+            num(rect.x),
+            num(rect.y),
+            num(rect.w),
+            num(rect.h),
+            paint("fill", color)
+        )
+        .unwrap();
+    }
+    fn rect_lines(&mut self, rect: Rect, thickness: f32, color: Color) {
+        let inset = thickness * 0.5;
+        writeln!(
+            self.out,
+            r#"<rect x="{}" y="{}" width="{}" height="{}" fill="none" {} stroke-width="{}"/>"#,
+            num(rect.x + inset),
+            num(rect.y + inset),
+            num(rect.w - thickness),
+            num(rect.h - thickness),
+            paint("stroke", color),
+            num(thickness)
+        )
+        .unwrap();
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct BookletOptions {
+    pub title: String,
+    pub puzzles_per_page: usize,
+    pub svg: ExportOptions,
+}
+
+impl Default for BookletOptions {
+    fn default() -> Self {
+        Self {
+            title: "Metro Loop puzzles".to_string(),
+            puzzles_per_page: 4,
+            svg: ExportOptions::default(),
+        }
+    }
+}
+
+/// A printable HTML document with the unsolved puzzles first and their answers at the end.
+/// Each level comes with the label that identifies it in both parts.
+pub fn export_booklet(levels: &[(String, Level)], options: &BookletOptions) -> String {
+    let per_page = options.puzzles_per_page.max(1);
+    let mut html = String::new();
+    writeln!(
+        html,
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n<style>{}</style>\n</head>\n<body>",
+        escape_html(&options.title),
+        BOOKLET_STYLE
+    )
+    .unwrap();
+    let pages = levels.chunks(per_page).collect::<Vec<_>>();
+    for (i_page, page) in pages.iter().enumerate() {
+        html.push_str("<section class=\"page\">\n");
+        if i_page == 0 {
+            writeln!(html, "<h1>{}</h1>", escape_html(&options.title)).unwrap();
+        }
+        legend(&mut html, &options.svg);
+        html.push_str("<div class=\"puzzles\">\n");
+        for (label, level) in page.iter() {
+            let mut caption = format!(
+                "{}<br>Active blocks: {}",
+                escape_html(label),
+                level.constraints.cell_count
+            );
+            if let Some(hint) = &level.metadata.hint {
+                caption += &format!("<br><i>{}</i>", escape_html(hint));
+            }
+            let svg = export_svg(level, &level.initial_grid, &options.svg);
+            figure(&mut html, &svg, &caption);
+        }
+        html.push_str("</div>\n</section>\n");
+    }
+    for (i_page, page) in pages.iter().enumerate() {
+        html.push_str("<section class=\"page answers\">\n");
+        if i_page == 0 {
+            html.push_str("<h1>Answers</h1>\n");
+        }
+        html.push_str("<div class=\"puzzles\">\n");
+        for (label, level) in page.iter() {
+            let svg = export_svg(level, &level.solution, &options.svg);
+            figure(&mut html, &svg, &escape_html(label));
+        }
+        html.push_str("</div>\n</section>\n");
+    }
+    html.push_str("</body>\n</html>\n");
+    html
+}
+
+const BOOKLET_STYLE: &str = "
+body { font-family: sans-serif; margin: 0; }
+.page { padding: 1cm; break-after: page; }
+.page:last-child { break-after: auto; }
+.legend { display: flex; gap: 1cm; align-items: center; font-size: 0.9em; }
+.legend div { display: flex; gap: 0.3cm; align-items: center; }
+.puzzles { display: flex; flex-wrap: wrap; gap: 0.5cm; }
+figure { margin: 0; width: calc(50% - 0.25cm); break-inside: avoid; }
+figure svg { width: 100%; height: auto; }
+.answers figure { width: calc(33% - 0.5cm); }
+";
+
+fn figure(html: &mut String, svg: &str, caption: &str) {
+    writeln!(
+        html,
+        "<figure>\n{}<figcaption>{}</figcaption>\n</figure>",
+        svg, caption
+    )
+    .unwrap();
+}
+
+fn legend(html: &mut String, options: &ExportOptions) {
+    html.push_str("<div class=\"legend\">\n");
+    let entries = [
+        (
+            Constraint::Station(Sense::Forwards),
+            "Station: the loop must run along this rail, in the direction of the arrow.",
+        ),
+        (
+            Constraint::Blockade,
+            "Blockade: the loop can't use this rail, so the blocks at both sides must be equal.",
+        ),
+    ];
+    for (constraint, text) in entries {
+        writeln!(
+            html,
+            "<div>{}<span>{}</span></div>",
+            legend_icon(constraint, options),
+            text
+        )
+        .unwrap();
+    }
+    html.push_str(
+        "<div><span>The loop runs around the active blocks. \
+        Every clue must be satisfied and the number of active blocks must match.</span></div>\n",
+    );
+    html.push_str("</div>\n");
+}
+
+/// Two cells with a satisfied clue on the rail between them.
+fn legend_icon(constraint: Constraint, options: &ExportOptions) -> String {
+    let options = ExportOptions {
+        grid_pad: options.cell_pad,
+        ..options.clone()
+    };
+    let layout = options.layout(1, 2);
+    let mut canvas = SvgCanvas::new(layout.screen_width, layout.screen_height);
+    let right_cell = match constraint {
+        Constraint::Station(_) => DISABLED_CELL,
+        Constraint::Blockade => ENABLED_CELL,
+    };
+    for (column, color) in [(0, ENABLED_CELL), (1, right_cell)] {
+        let top_left = cell_top_left(0, column, &layout);
+        let rect = Rect::new(
+            top_left.x,
+            top_left.y,
+            layout.cell_width,
+            layout.cell_height,
+        );
+        canvas.rect(rect, color);
+    }
+    let start = cell_top_left(0, 1, &layout) - layout.cell_pad * 0.5;
+    let length = vec2(0.0, layout.cell_height + layout.cell_pad);
+    match constraint {
+        Constraint::Station(_) => {
+            draw_rail(&mut canvas, start, start + length, &layout, true);
+            let (success, color, border) = (true, SUCCESS, SUCCESS_DARK);
+            draw_station(
+                &mut canvas,
+                &layout,
+                success,
+                color,
+                border,
+                start,
+                length,
+                true,
+            );
+        }
+        Constraint::Blockade => {
+            let (success, color, border) = (true, SUCCESS, SUCCESS_DARK);
+            let (reverse, enabled, reachable) = (false, true, true);
+            draw_blockade(
+                &mut canvas,
+                &layout,
+                success,
+                color,
+                border,
+                start,
+                length,
+                reverse,
+                enabled,
+                reachable,
+            );
+        }
+    }
+    canvas.finish()
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Two decimals are more than enough for the screen and keep the output stable.
+fn num(value: f32) -> String {
+    let text = format!("{:.2}", value);
+    let text = text.trim_end_matches('0').trim_end_matches('.');
+    if text == "-0" {
+        "0".to_string()
+    } else {
+        text.to_string()
+    }
+}
+
+fn points_attribute(points: &[Vec2]) -> String {
+    let points = points
+        .iter()
+        .map(|p| format!("{},{}", num(p.x), num(p.y)))
+        .collect::<Vec<_>>();
+    points.join(" ")
+}
+
+fn paint(attribute: &str, color: Color) -> String {
+    let [r, g, b, a]: [u8; 4] = color.into();
+    let mut text = format!(r##"{}="#{:02x}{:02x}{:02x}""##, attribute, r, g, b);
+    if a < 255 {
+        write!(
+            text,
+            r#" {}-opacity="{}""#,
+            attribute,
+            num(a as f32 / 255.0)
+        )
+        .unwrap();
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::levels::Levels;
+    use crate::FAILING;
+
+    #[test]
+    fn test_numbers() {
+        assert_eq!(num(30.0), "30");
+        assert_eq!(num(2.5), "2.5");
+        assert_eq!(num(1.0 / 3.0), "0.33");
+        assert_eq!(num(-0.001), "0");
+        assert_eq!(
+            paint("fill", Color::new(1.0, 0.0, 0.0, 0.5)),
+            r##"fill="#ff0000" fill-opacity="0.5""##
+        );
+    }
+
+    #[test]
+    fn test_export() {
+        let levels = Levels::get().unwrap();
+        let level = levels.get_level(0, 0);
+        let options = ExportOptions::default();
+        let puzzle = export_svg(level, &level.initial_grid, &options);
+        assert_eq!(puzzle, export_svg(level, &level.initial_grid, &options));
+        let (rows, columns) = (
+            level.solution.rows() as f32,
+            level.solution.columns() as f32,
+        );
+        let width = columns * (CELL_WIDTH + CELL_PAD) - CELL_PAD + 2.0 * GRID_PAD;
+        let height = rows * (CELL_HEIGHT + CELL_PAD) - CELL_PAD + 2.0 * GRID_PAD;
+        let header = format!(r#"width="{}" height="{}""#, num(width), num(height));
+        assert!(puzzle.starts_with("<svg"));
+        assert!(puzzle.contains(&header), "{}", puzzle);
+        assert!(puzzle.ends_with("</svg>\n"));
+
+        let solved = export_svg(level, &level.solution, &options);
+        assert_ne!(puzzle, solved);
+        assert!(puzzle.contains(&paint("fill", FAILING)));
+        assert!(solved.contains(&paint("fill", SUCCESS)));
+        assert!(!solved.contains(&paint("fill", FAILING)));
+
+        let transparent = ExportOptions {
+            background: false,
+            ..options
+        };
+        let without_background = export_svg(level, &level.solution, &transparent);
+        assert!(!without_background.contains(&paint("fill", BACKGROUND)));
+    }
+
+    #[test]
+    fn test_booklet() {
+        let levels = Levels::get().unwrap();
+        let mut labeled = Vec::new();
+        for i_level in 0..5 {
+            let level = levels.get_level(0, i_level).clone();
+            labeled.push((format!("Level <{}>", i_level), level));
+        }
+        let options = BookletOptions {
+            puzzles_per_page: 2,
+            ..Default::default()
+        };
+        let html = export_booklet(&labeled, &options);
+        assert_eq!(html.matches("class=\"page\"").count(), 3);
+        assert_eq!(html.matches("class=\"page answers\"").count(), 3);
+        assert_eq!(html.matches("<figure>").count(), 10);
+        assert_eq!(html.matches("class=\"legend\"").count(), 3);
+        assert!(html.contains("Level &lt;4&gt;"));
+        let first_puzzle = export_svg(&labeled[0].1, &labeled[0].1.initial_grid, &options.svg);
+        let last_answer = export_svg(&labeled[4].1, &labeled[4].1.solution, &options.svg);
+        assert!(html.contains(&first_puzzle));
+        assert!(html.contains(&last_answer));
+    }
+}
@@ -0,0 +1,801 @@
+use crate::direction::Direction;
+use crate::levels::{Level, Metadata, CURRENT_FORMAT_VERSION};
+use crate::logic::constraints::{count_cells, is_border_rail, Constraint, Constraints, RailCoord};
+use crate::logic::grid::{get, get_cell, get_cell_mut, get_mut, in_range, is_system_fixed, Grid};
+use crate::logic::solver::{solve, SolveLimits};
+use crate::math::{ivec2, IVec2};
+
+pub const MIN_ROWS: i32 = 4;
+pub const MAX_ROWS: i32 = 50;
+pub const MIN_COLUMNS: i32 = 3;
+pub const MAX_COLUMNS: i32 = 51;
+/// How many solutions the analysis looks at to find ambiguous cells.
+pub const ANALYSIS_SOLUTIONS: usize = 16;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Tool {
+    Solution,
+    Lock,
+    PreEnable,
+    Root,
+    Clue,
+}
+impl Tool {
+    pub const ALL: [Tool; 5] = [
+        Tool::Solution,
+        Tool::Lock,
+        Tool::PreEnable,
+        Tool::Root,
+        Tool::Clue,
+    ];
+    pub fn text(&self) -> &'static str {
+        match self {
+            Tool::Solution => "PAINT SOLUTION",
+            Tool::Lock => "LOCK",
+            Tool::PreEnable => "PRE-ENABLE",
+            Tool::Root => "MOVE ROOT",
+            Tool::Clue => "CLUES",
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum EditError {
+    SystemFixed,
+    LockedCell,
+    RootOutOfRange,
+    RootOnBorder,
+    BorderRail,
+    InvalidSolution,
+}
+impl EditError {
+    pub fn text(&self) -> &'static str {
+        match self {
+            EditError::SystemFixed => "Can't change the border or the root",
+            EditError::LockedCell => "Locked blocks can't be pre-enabled, unlock it first",
+            EditError::RootOutOfRange => "The root needs a free block above it",
+            EditError::RootOnBorder => "The root can't go on the border",
+            EditError::BorderRail => "Clues can't go on the border",
+            EditError::InvalidSolution => "The solution has to be a single loop",
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Analysis {
+    /// Solutions found, up to ANALYSIS_SOLUTIONS.
+    pub solutions: usize,
+    /// False if the search stopped early, so there might be more solutions.
+    pub complete: bool,
+    /// Indexes into `constraints.rails` of the clues that can be removed without adding solutions.
+    pub redundant_clues: Vec<usize>,
+    pub redundant_locks: Vec<(i32, i32)>,
+    /// Cells that are enabled in some solutions and disabled in others.
+    pub ambiguous_cells: Vec<(i32, i32)>,
+}
+impl Analysis {
+    pub fn summary(&self) -> String {
+        match (self.solutions, self.complete) {
+            (0, true) => "No solution".to_string(),
+            (0, false) => "No solution found, the search gave up".to_string(),
+            (1, true) => "Unique solution".to_string(),
+            (n, true) => format!("{} solutions", n),
+            (n, false) => format!("At least {} solutions", n),
+        }
+    }
+}
+
+/// A clue or a locked cell that might not be needed.
+enum Candidate {
+    Clue(RailCoord),
+    Lock(i32, i32),
+}
+
+fn same_place(clue: &RailCoord, other: &RailCoord) -> bool {
+    clue.row_column() == other.row_column() && clue.direction() == other.direction()
+}
+
+/// An analysis that checks one candidate per `step`, so that the editor can show the progress
+/// instead of freezing on big levels. Any edit makes it outdated.
+pub struct AnalysisJob {
+    analysis: Analysis,
+    pending: Vec<Candidate>,
+    total: usize,
+}
+impl AnalysisJob {
+    /// Returns false when there is nothing left to check.
+    pub fn step(&mut self, editor: &LevelEditor, limits: &SolveLimits) -> bool {
+        let Some(candidate) = self.pending.pop() else {
+            return false;
+        };
+        let level = &editor.level;
+        let same_solutions = SolveLimits {
+            max_solutions: self.analysis.solutions + 1,
+            ..*limits
+        };
+        let keeps_solutions = |grid: &Grid, constraints: &Constraints| {
+            let without = solve(grid, constraints, &same_solutions);
+            without.complete && without.grids.len() == self.analysis.solutions
+        };
+        match candidate {
+            Candidate::Clue(clue) => {
+                let mut constraints = level.constraints.clone();
+                if let Some(i) = constraints.rails.iter().position(|c| same_place(c, &clue)) {
+                    constraints.rails.remove(i);
+                    if keeps_solutions(&level.initial_grid, &constraints) {
+                        self.analysis.redundant_clues.push(i);
+                    }
+                }
+            }
+            Candidate::Lock(row, column) => {
+                let mut grid = level.initial_grid.clone();
+                *get_mut(&mut grid.fixed_cells, row, column) = false;
+                if keeps_solutions(&grid, &level.constraints) {
+                    self.analysis.redundant_locks.push((row, column));
+                }
+            }
+        }
+        !self.pending.is_empty()
+    }
+    /// Candidates checked, out of the total.
+    pub fn progress(&self) -> (usize, usize) {
+        (self.total - self.pending.len(), self.total)
+    }
+    pub fn into_analysis(self) -> Analysis {
+        self.analysis
+    }
+}
+
+/// An `auto_clue` that tries to remove one lock or clue per `step`. The level stays valid
+/// between steps, it just has more clues than needed until the job finishes.
+pub struct AutoClueJob {
+    before: Box<Level>,
+    unlocked_cells: Vec<Vec<bool>>,
+    pending: Vec<Candidate>,
+    total: usize,
+}
+impl AutoClueJob {
+    /// Returns false when there is nothing left to remove.
+    pub fn step(&mut self, editor: &mut LevelEditor, limits: &SolveLimits) -> bool {
+        let Some(candidate) = self.pending.pop() else {
+            return false;
+        };
+        match candidate {
+            Candidate::Lock(row, column) => {
+                editor.set_locked(row, column, false);
+                *get_cell_mut(&mut editor.level.initial_grid, row, column) =
+                    self.unlocked_cells[row as usize][column as usize];
+                if !editor.is_unique(limits) {
+                    editor.lock_as_solution(row, column);
+                }
+            }
+            Candidate::Clue(clue) => {
+                let rails = &mut editor.level.constraints.rails;
+                if let Some(i) = rails.iter().position(|c| same_place(c, &clue)) {
+                    rails.remove(i);
+                    if !editor.is_unique(limits) {
+                        editor.level.constraints.rails.insert(i, clue);
+                    }
+                }
+            }
+        }
+        if self.pending.is_empty() {
+            editor.refresh();
+        }
+        !self.pending.is_empty()
+    }
+    /// Candidates tried, out of the total.
+    pub fn progress(&self) -> (usize, usize) {
+        (self.total - self.pending.len(), self.total)
+    }
+    /// Puts back the clues and locks that the level had before the job started.
+    pub fn cancel(self, editor: &mut LevelEditor) {
+        editor.level = *self.before;
+    }
+}
+
+/// Keeps a Level printable while it's being edited: locked cells start as their solution,
+/// and clues always take their type from the solution rail they sit on.
+pub struct LevelEditor {
+    level: Level,
+}
+
+impl LevelEditor {
+    pub fn new(rows: i32, columns: i32) -> Self {
+        let rows = rows.clamp(MIN_ROWS, MAX_ROWS);
+        let columns = columns.clamp(MIN_COLUMNS, MAX_COLUMNS);
+        let solution = Grid::new(rows, columns, ivec2(columns / 2, rows / 2));
+        let level = Level {
+            metadata: Metadata::default(),
+            initial_grid: solution.clone(),
+            constraints: Constraints {
+                rails: Vec::new(),
+                cell_count: 0,
+            },
+            solution,
+        };
+        Self::from_level(level)
+    }
+    pub fn from_level(mut level: Level) -> Self {
+        level.metadata.format_version = Some(CURRENT_FORMAT_VERSION);
+        let mut editor = Self { level };
+        editor.refresh();
+        editor
+    }
+    pub fn level(&self) -> &Level {
+        &self.level
+    }
+    pub fn into_level(self) -> Level {
+        self.level
+    }
+    pub fn rows(&self) -> i32 {
+        self.level.solution.rows()
+    }
+    pub fn columns(&self) -> i32 {
+        self.level.solution.columns()
+    }
+    pub fn is_locked(&self, row: i32, column: i32) -> bool {
+        *get(&self.level.solution.fixed_cells, row, column)
+    }
+
+    pub fn toggle_solution(&mut self, row: i32, column: i32) -> Result<(), EditError> {
+        self.check_not_system_fixed(row, column)?;
+        let cell = get_cell_mut(&mut self.level.solution, row, column);
+        *cell = !*cell;
+        let new_value = *cell;
+        if self.is_locked(row, column) {
+            *get_cell_mut(&mut self.level.initial_grid, row, column) = new_value;
+        }
+        self.refresh();
+        Ok(())
+    }
+
+    pub fn toggle_lock(&mut self, row: i32, column: i32) -> Result<(), EditError> {
+        self.check_not_system_fixed(row, column)?;
+        if self.is_locked(row, column) {
+            self.set_locked(row, column, false);
+        } else {
+            self.lock_as_solution(row, column);
+        }
+        self.refresh();
+        Ok(())
+    }
+
+    pub fn toggle_pre_enabled(&mut self, row: i32, column: i32) -> Result<(), EditError> {
+        self.check_not_system_fixed(row, column)?;
+        if self.is_locked(row, column) {
+            return Err(EditError::LockedCell);
+        }
+        let cell = get_cell_mut(&mut self.level.initial_grid, row, column);
+        *cell = !*cell;
+        self.refresh();
+        Ok(())
+    }
+
+    pub fn move_root(&mut self, row: i32, column: i32) -> Result<(), EditError> {
+        let solution = &self.level.solution;
+        if !in_range(solution, row, column) {
+            return Err(EditError::RootOnBorder);
+        }
+        if !in_range(solution, row - 1, column) {
+            return Err(EditError::RootOutOfRange);
+        }
+        let old_root = solution.root;
+        for (old_row, old_column) in [(old_root.y, old_root.x), (old_root.y - 1, old_root.x)] {
+            self.set_locked(old_row, old_column, false);
+            *get_cell_mut(&mut self.level.initial_grid, old_row, old_column) = false;
+        }
+        self.place_root(ivec2(column, row));
+        self.refresh();
+        Ok(())
+    }
+
+    /// Adds a clue on the given rail, or removes it if there was one already.
+    pub fn toggle_clue(
+        &mut self,
+        row: i32,
+        column: i32,
+        direction: Direction,
+    ) -> Result<(), EditError> {
+        if !self.is_clue_position(row, column, direction) {
+            return Err(EditError::BorderRail);
+        }
+        let clue = self.clue_from_solution(row, column, direction);
+        let rails = &mut self.level.constraints.rails;
+        let existing = rails
+            .iter()
+            .position(|clue| clue.row_column() == (row, column) && clue.is(direction));
+        if let Some(index) = existing {
+            rails.remove(index);
+        } else {
+            rails.push(clue);
+        }
+        self.refresh();
+        Ok(())
+    }
+
+    pub fn is_clue_position(&self, row: i32, column: i32, direction: Direction) -> bool {
+        !is_border_rail(&self.level.solution, row, column, direction)
+    }
+
+    /// Keeps the cells that still fit in the new size. The root is moved to the center if
+    /// it doesn't fit anymore.
+    pub fn resize(&mut self, rows: i32, columns: i32) {
+        let rows = rows.clamp(MIN_ROWS, MAX_ROWS);
+        let columns = columns.clamp(MIN_COLUMNS, MAX_COLUMNS);
+        if rows == self.rows() && columns == self.columns() {
+            return;
+        }
+        let old = std::mem::replace(self, Self::new(rows, columns));
+        let old_root = old.level.solution.root;
+        if in_range(&self.level.solution, old_root.y, old_root.x)
+            && in_range(&self.level.solution, old_root.y - 1, old_root.x)
+        {
+            let center = self.level.solution.root;
+            for (row, column) in [(center.y, center.x), (center.y - 1, center.x)] {
+                self.set_locked(row, column, false);
+                *get_cell_mut(&mut self.level.initial_grid, row, column) = false;
+                *get_cell_mut(&mut self.level.solution, row, column) = false;
+            }
+            self.place_root(old_root);
+        }
+        for row in 1..rows.min(old.rows()) - 1 {
+            for column in 1..columns.min(old.columns()) - 1 {
+                if is_system_fixed(&self.level.solution, row, column) {
+                    continue;
+                }
+                *get_cell_mut(&mut self.level.solution, row, column) =
+                    *get_cell(&old.level.solution, row, column);
+                *get_cell_mut(&mut self.level.initial_grid, row, column) =
+                    *get_cell(&old.level.initial_grid, row, column);
+                self.set_locked(row, column, old.is_locked(row, column));
+            }
+        }
+        self.level.metadata = old.level.metadata;
+        self.level.constraints.rails = old
+            .level
+            .constraints
+            .rails
+            .into_iter()
+            .filter(|clue| {
+                let (row, column) = clue.row_column();
+                self.is_clue_position(row, column, clue.direction())
+            })
+            .collect();
+        self.refresh();
+    }
+
+    /// Finds the solutions of the level as a player would see it, and which clues and cells
+    /// could be removed or are still undecided.
+    pub fn analyze(&self, limits: &SolveLimits) -> Analysis {
+        let mut job = self.start_analysis(limits);
+        while job.step(self, limits) {}
+        job.into_analysis()
+    }
+
+    /// Solves the level once. The clues and locks that might be redundant are checked by
+    /// `AnalysisJob::step`.
+    pub fn start_analysis(&self, limits: &SolveLimits) -> AnalysisJob {
+        let level = &self.level;
+        let all_limits = SolveLimits {
+            max_solutions: ANALYSIS_SOLUTIONS,
+            ..*limits
+        };
+        let solutions = solve(&level.initial_grid, &level.constraints, &all_limits);
+        let mut analysis = Analysis {
+            solutions: solutions.grids.len(),
+            complete: solutions.complete,
+            ..Default::default()
+        };
+        for row in 0..self.rows() {
+            for column in 0..self.columns() {
+                let mut values = solutions.grids.iter().map(|g| *get_cell(g, row, column));
+                if let Some(first) = values.next() {
+                    if values.any(|value| value != first) {
+                        analysis.ambiguous_cells.push((row, column));
+                    }
+                }
+            }
+        }
+        let mut pending = Vec::new();
+        if solutions.complete && !solutions.grids.is_empty() {
+            pending.extend(level.constraints.rails.iter().cloned().map(Candidate::Clue));
+            for row in 0..self.rows() {
+                for column in 0..self.columns() {
+                    if self.is_locked(row, column) && !is_system_fixed(&level.solution, row, column)
+                    {
+                        pending.push(Candidate::Lock(row, column));
+                    }
+                }
+            }
+        }
+        pending.reverse();
+        AnalysisJob {
+            analysis,
+            total: pending.len(),
+            pending,
+        }
+    }
+
+    /// Replaces the clues and locked cells with a minimal set that makes the painted solution
+    /// the only one. Starts with every possible clue and removes them one by one while the
+    /// solution stays unique, so the stations are the most likely to be kept. If a search
+    /// gives up, the clue being tried is kept.
+    pub fn auto_clue(&mut self, limits: &SolveLimits) -> Result<(), EditError> {
+        let mut job = self.start_auto_clue()?;
+        while job.step(self, limits) {}
+        Ok(())
+    }
+
+    /// Locks every cell and adds every clue. `AutoClueJob::step` removes them one by one.
+    pub fn start_auto_clue(&mut self) -> Result<AutoClueJob, EditError> {
+        let solution = &self.level.solution;
+        if solution.reachable_rails_count != solution.total_rails {
+            return Err(EditError::InvalidSolution);
+        }
+        let before = Box::new(self.level.clone());
+        let mut unlocked_cells = self.level.initial_grid.cells.clone();
+        let mut lockable = Vec::new();
+        for row in 0..self.rows() {
+            for column in 0..self.columns() {
+                if !is_system_fixed(solution, row, column) {
+                    if self.is_locked(row, column) {
+                        unlocked_cells[row as usize][column as usize] = false;
+                    }
+                    lockable.push((row, column));
+                }
+            }
+        }
+        for &(row, column) in &lockable {
+            self.lock_as_solution(row, column);
+        }
+        let mut clues = Vec::new();
+        for row in 0..=self.rows() {
+            for column in 0..=self.columns() {
+                for direction in [Direction::Horizontal, Direction::Vertical] {
+                    if self.is_clue_position(row, column, direction) {
+                        clues.push(self.clue_from_solution(row, column, direction));
+                    }
+                }
+            }
+        }
+        self.level.constraints.rails = clues;
+        self.refresh();
+
+        let mut pending: Vec<Candidate> = lockable
+            .into_iter()
+            .map(|(row, column)| Candidate::Lock(row, column))
+            .collect();
+        for stations in [false, true] {
+            let clues = self.level.constraints.rails.iter().filter(|clue| {
+                let is_station = !matches!(clue.type_(), Constraint::Blockade);
+                is_station == stations
+            });
+            pending.extend(clues.cloned().map(Candidate::Clue));
+        }
+        pending.reverse();
+        Ok(AutoClueJob {
+            before,
+            unlocked_cells,
+            total: pending.len(),
+            pending,
+        })
+    }
+
+    fn is_unique(&self, limits: &SolveLimits) -> bool {
+        let limits = SolveLimits {
+            max_solutions: 2,
+            ..*limits
+        };
+        let level = &self.level;
+        solve(&level.initial_grid, &level.constraints, &limits).is_unique()
+    }
+
+    fn lock_as_solution(&mut self, row: i32, column: i32) {
+        self.set_locked(row, column, true);
+        let solution_cell = *get_cell(&self.level.solution, row, column);
+        *get_cell_mut(&mut self.level.initial_grid, row, column) = solution_cell;
+    }
+
+    fn check_not_system_fixed(&self, row: i32, column: i32) -> Result<(), EditError> {
+        if is_system_fixed(&self.level.solution, row, column) {
+            Err(EditError::SystemFixed)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn set_locked(&mut self, row: i32, column: i32, locked: bool) {
+        *get_mut(&mut self.level.solution.fixed_cells, row, column) = locked;
+        *get_mut(&mut self.level.initial_grid.fixed_cells, row, column) = locked;
+    }
+
+    fn place_root(&mut self, root: IVec2) {
+        for grid in [&mut self.level.solution, &mut self.level.initial_grid] {
+            grid.root = root;
+            *get_cell_mut(grid, root.y, root.x) = true;
+            *get_cell_mut(grid, root.y - 1, root.x) = false;
+        }
+        self.set_locked(root.y, root.x, true);
+        self.set_locked(root.y - 1, root.x, true);
+    }
+
+    fn clue_from_solution(&self, row: i32, column: i32, direction: Direction) -> RailCoord {
+        let rails = &self.level.solution.rails;
+        match direction {
+            Direction::Horizontal => RailCoord::Horizontal {
+                row,
+                column,
+                sense: rails.get_horiz(row, column),
+            },
+            Direction::Vertical => RailCoord::Vertical {
+                row,
+                column,
+                sense: rails.get_vert(row, column),
+            },
+        }
+    }
+
+    fn refresh(&mut self) {
+        self.level.solution.recalculate_rails();
+        self.level.initial_grid.recalculate_rails();
+        let clues = std::mem::take(&mut self.level.constraints.rails);
+        self.level.constraints.rails = clues
+            .iter()
+            .map(|clue| {
+                let (row, column) = clue.row_column();
+                self.clue_from_solution(row, column, clue.direction())
+            })
+            .collect();
+        self.level.constraints.cell_count = count_cells(&self.level.solution);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::direction::Sense;
+    use crate::logic::constraints::compute_satisfaction;
+
+    fn reparsed(editor: &LevelEditor) -> String {
+        let text = editor.level().to_string();
+        let level = Level::from_str_strict(&text).unwrap();
+        assert_eq!(level.to_string(), text);
+        text
+    }
+
+    #[test]
+    fn test_new_level() {
+        let editor = LevelEditor::new(4, 3);
+        let expected = r#"format=2
+.-.-.
+-----
+.-.-.
+-----
+.-%-.
+-----
+.-.-.
+"#;
+        assert_eq!(reparsed(&editor), expected);
+    }
+
+    #[test]
+    fn test_paint_lock_and_pre_enable() {
+        let mut editor = LevelEditor::new(5, 5);
+        editor.toggle_solution(2, 1).unwrap();
+        editor.toggle_solution(3, 1).unwrap();
+        editor.toggle_solution(3, 3).unwrap();
+        editor.toggle_lock(3, 1).unwrap();
+        editor.toggle_lock(1, 1).unwrap();
+        editor.toggle_pre_enabled(3, 3).unwrap();
+        editor.toggle_pre_enabled(2, 3).unwrap();
+        let expected = r#"format=2
+.-.-.-.-.
+---------
+.-.-.-x-.
+---------
+.-*-%-?-.
+---------
+.-@-x-O-.
+---------
+.-.-.-.-.
+"#;
+        assert_eq!(reparsed(&editor), expected);
+
+        editor.toggle_solution(3, 1).unwrap();
+        assert!(reparsed(&editor).contains(".-.-x-O-."));
+        assert_eq!(editor.level().constraints.cell_count, 3);
+    }
+
+    #[test]
+    fn test_rejected_edits() {
+        let mut editor = LevelEditor::new(5, 5);
+        assert_eq!(editor.toggle_solution(0, 2), Err(EditError::SystemFixed));
+        assert_eq!(editor.toggle_lock(1, 2), Err(EditError::SystemFixed));
+        assert_eq!(editor.toggle_solution(2, 2), Err(EditError::SystemFixed));
+        editor.toggle_lock(3, 3).unwrap();
+        assert_eq!(editor.toggle_pre_enabled(3, 3), Err(EditError::LockedCell));
+        assert_eq!(editor.move_root(1, 1), Err(EditError::RootOutOfRange));
+        assert_eq!(
+            editor.toggle_clue(0, 2, Direction::Vertical),
+            Err(EditError::BorderRail)
+        );
+    }
+
+    #[test]
+    fn test_move_root() {
+        let mut editor = LevelEditor::new(5, 5);
+        editor.toggle_solution(2, 1).unwrap();
+        editor.move_root(3, 1).unwrap();
+        assert_eq!(editor.level().solution.root, ivec2(1, 3));
+        let expected = r#"format=2
+.-.-.-.-.
+---------
+.-x-x-x-.
+---------
+.-.-*-x-.
+---------
+.-%-x-x-.
+---------
+.-.-.-.-.
+"#;
+        assert_eq!(reparsed(&editor), expected);
+
+        assert_eq!(editor.move_root(4, 2), Err(EditError::RootOnBorder));
+        assert_eq!(editor.move_root(2, 0), Err(EditError::RootOnBorder));
+        assert_eq!(editor.move_root(2, 4), Err(EditError::RootOnBorder));
+        assert_eq!(editor.level().solution.root, ivec2(1, 3));
+    }
+
+    #[test]
+    fn test_clues_follow_solution() {
+        let mut editor = LevelEditor::new(5, 5);
+        editor.toggle_clue(2, 2, Direction::Horizontal).unwrap();
+        editor.toggle_clue(3, 2, Direction::Horizontal).unwrap();
+        let types = |editor: &LevelEditor| {
+            editor
+                .level()
+                .constraints
+                .rails
+                .iter()
+                .map(|clue| match clue.type_() {
+                    Constraint::Station(Sense::Forwards) => "forwards",
+                    Constraint::Station(Sense::Backwards) => "backwards",
+                    Constraint::Blockade => "blockade",
+                })
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(types(&editor), vec!["forwards", "backwards"]);
+
+        editor.toggle_solution(3, 2).unwrap();
+        assert_eq!(types(&editor), vec!["forwards", "blockade"]);
+        let level = editor.level();
+        assert!(compute_satisfaction(&level.solution, &level.constraints).success());
+
+        editor.toggle_clue(2, 2, Direction::Horizontal).unwrap();
+        assert_eq!(types(&editor), vec!["blockade"]);
+    }
+
+    #[test]
+    fn test_resize() {
+        let mut editor = LevelEditor::new(6, 6);
+        editor.toggle_solution(4, 4).unwrap();
+        editor.toggle_solution(2, 2).unwrap();
+        editor.toggle_lock(2, 2).unwrap();
+        editor.toggle_clue(3, 2, Direction::Vertical).unwrap();
+        editor.toggle_clue(3, 4, Direction::Vertical).unwrap();
+
+        editor.resize(5, 5);
+        assert_eq!(editor.level().solution.root, ivec2(3, 3));
+        assert_eq!(editor.level().constraints.rails.len(), 2);
+        let expected = r#"format=2
+.-.-.-.-.
+---------
+.-x-x-x-.
+---------
+.-x-@-.-.
+---------
+.-x=x-%v.
+---------
+.-.-.-.-.
+"#;
+        assert_eq!(reparsed(&editor), expected);
+
+        editor.resize(4, 4);
+        assert_eq!(editor.level().solution.root, ivec2(2, 2));
+        reparsed(&editor);
+    }
+
+    #[test]
+    fn test_analysis() {
+        let mut editor = LevelEditor::new(5, 5);
+        editor.toggle_solution(2, 1).unwrap();
+        let limits = SolveLimits::default();
+        let analysis = editor.analyze(&limits);
+        assert!(analysis.solutions > 1);
+        assert!(analysis.ambiguous_cells.contains(&(2, 1)));
+        assert!(analysis.redundant_clues.is_empty());
+
+        editor.toggle_clue(2, 2, Direction::Vertical).unwrap();
+        editor.toggle_clue(2, 1, Direction::Horizontal).unwrap();
+        editor.toggle_lock(3, 3).unwrap();
+        let analysis = editor.analyze(&limits);
+        assert_eq!(analysis.summary(), "Unique solution");
+        assert_eq!(analysis.ambiguous_cells, vec![]);
+        assert_eq!(analysis.redundant_clues, vec![1]);
+        assert_eq!(analysis.redundant_locks, vec![(3, 3)]);
+    }
+
+    #[test]
+    fn test_auto_clue() {
+        let mut editor = LevelEditor::new(6, 6);
+        for (row, column) in [(3, 2), (4, 2), (4, 3), (4, 4), (2, 4)] {
+            editor.toggle_solution(row, column).unwrap();
+        }
+        editor.toggle_lock(1, 1).unwrap();
+        let limits = SolveLimits::default();
+        editor.auto_clue(&limits).unwrap();
+
+        let analysis = editor.analyze(&limits);
+        assert_eq!(analysis.summary(), "Unique solution");
+        assert_eq!(analysis.ambiguous_cells, vec![]);
+        assert_eq!(analysis.redundant_clues, vec![]);
+        assert_eq!(analysis.redundant_locks, vec![]);
+        let level = editor.level();
+        assert!(compute_satisfaction(&level.solution, &level.constraints).success());
+        reparsed(&editor);
+    }
+
+    #[test]
+    fn test_jobs_report_progress() {
+        let mut editor = LevelEditor::new(6, 6);
+        for (row, column) in [(3, 2), (4, 2), (4, 3), (4, 4), (2, 4)] {
+            editor.toggle_solution(row, column).unwrap();
+        }
+        let limits = SolveLimits::default();
+        let mut job = editor.start_auto_clue().unwrap();
+        let (done, total) = job.progress();
+        assert_eq!(done, 0);
+        let mut steps = 0;
+        loop {
+            let pending = job.step(&mut editor, &limits);
+            steps += 1;
+            assert_eq!(job.progress(), (steps, total));
+            if !pending {
+                break;
+            }
+        }
+        assert_eq!(steps, total);
+        assert!(editor.is_unique(&limits));
+
+        let mut job = editor.start_analysis(&limits);
+        let (_, total) = job.progress();
+        assert!(total > 0);
+        while job.step(&editor, &limits) {}
+        assert_eq!(job.progress(), (total, total));
+        assert_eq!(job.into_analysis(), editor.analyze(&limits));
+    }
+
+    #[test]
+    fn test_cancel_auto_clue() {
+        let mut editor = LevelEditor::new(6, 6);
+        for (row, column) in [(3, 2), (4, 2), (4, 3), (4, 4), (2, 4)] {
+            editor.toggle_solution(row, column).unwrap();
+        }
+        editor.toggle_clue(3, 2, Direction::Vertical).unwrap();
+        let before = editor.level().to_string();
+        let mut job = editor.start_auto_clue().unwrap();
+        job.step(&mut editor, &SolveLimits::default());
+        assert_ne!(editor.level().to_string(), before);
+        job.cancel(&mut editor);
+        assert_eq!(reparsed(&editor), before);
+    }
+
+    #[test]
+    fn test_auto_clue_needs_a_loop() {
+        let mut editor = LevelEditor::new(6, 6);
+        editor.toggle_solution(1, 1).unwrap();
+        let limits = SolveLimits::default();
+        assert_eq!(editor.auto_clue(&limits), Err(EditError::InvalidSolution));
+    }
+}
@@ -1,8 +1,10 @@
 use crate::levels::{Level, Levels};
-use crate::logic::constraints::{choose_constraints, count_unreachable_rails};
+use crate::logic::constraints::count_unreachable_rails;
+use crate::logic::generator::{self, level_from_solution};
 use crate::logic::grid::{get, Grid};
 use crate::logic::pixel_grid::Coord;
-use crate::scenes::play::generate_grid;
+use crate::scenes::play::controller::PlaySession;
+use crate::scenes::play::generate_grid_visualized;
 use crate::theme::Theme;
 use crate::{AnyError, VISUALIZE};
 use std::fmt::{Display, Formatter};
@@ -13,12 +15,19 @@ pub struct LevelHistory {
 }
 
 pub type Solved = Vec<Vec<bool>>;
+pub type HintsUsed = Vec<Vec<u32>>;
 
 #[derive(Debug)]
 pub struct GameTrack {
     pub solved: Solved,
+    pub hints: HintsUsed,
+    /// Hints used in the current procedural level.
+    pub procedural_hints: u32,
     pub current: CurrentGame,
     pub in_progress: Grid,
+    /// Boards saved when the player started trying something, innermost last.
+    pub branches: Vec<Grid>,
+    pub session: PlaySession,
     cached_level: Level,
 }
 
@@ -31,9 +40,11 @@ pub enum CurrentGame {
 impl GameTrack {
     pub fn new(section: i32, level: i32, levels: &Levels) -> Result<Self, AnyError> {
         let mut solved = Vec::new();
+        let mut hints = Vec::new();
         for section in &levels.sections {
             let solved_in_section = vec![false; section.levels.len()];
             solved.push(solved_in_section);
+            hints.push(vec![0; section.levels.len()]);
         }
         let current = CurrentGame::Campaign { section, level };
 
@@ -42,7 +53,11 @@ impl GameTrack {
         Ok(Self {
             current,
             solved,
+            hints,
+            procedural_hints: 0,
             in_progress,
+            branches: Vec::new(),
+            session: PlaySession::default(),
             cached_level,
         })
     }
@@ -65,37 +80,65 @@ impl GameTrack {
     pub fn get_current(&self) -> &Level {
         &self.cached_level
     }
+    /// Starts the current level from its initial board, without any branch, hint or solution shown.
+    pub fn restart(&mut self) {
+        self.in_progress = self.cached_level.initial_grid.clone();
+        self.branches.clear();
+        self.session = PlaySession::default();
+    }
+    pub fn start_branch(&mut self) {
+        self.branches.push(self.in_progress.clone());
+    }
+    /// Keeps the changes made in the innermost branch, which become part of the one around it.
+    pub fn commit_branch(&mut self) -> bool {
+        self.branches.pop().is_some()
+    }
+    /// Goes back to the board as it was when the innermost branch started.
+    pub fn discard_branch(&mut self) -> bool {
+        match self.branches.pop() {
+            Some(base) => {
+                self.in_progress = base;
+                true
+            }
+            None => false,
+        }
+    }
+    pub fn level_title(&self) -> String {
+        match &self.cached_level.metadata.name {
+            Some(name) => name.clone(),
+            None => self.current.to_string(),
+        }
+    }
     pub async fn next(&mut self, theme: &Theme) -> &Self {
-        match &mut self.current {
-            CurrentGame::Campaign { section, mut level } => {
-                level += 1;
-                for i_section in (*section as usize)..theme.resources.levels.sections.len() {
-                    for i_level in
-                        level as usize..theme.resources.levels.sections[i_section].levels.len()
-                    {
-                        if !self.solved[i_section][i_level] {
-                            self.current = CurrentGame::Campaign {
-                                section: i_section as i32,
-                                level: i_level as i32,
-                            };
-                            self.cached_level =
-                                theme.resources.levels.get_level(i_section, i_level).clone();
-                            self.in_progress = self.cached_level.initial_grid.clone();
-                            return self;
-                        }
+        if !self.next_in_campaign(&theme.resources.levels) {
+            self.cached_level = generate_procedural(VISUALIZE, theme).await;
+            self.restart();
+            self.current = CurrentGame::Procedural;
+            self.procedural_hints = 0;
+        }
+        self
+    }
+    /// Moves to the next unsolved campaign level. Returns false if there are none left, or if
+    /// the current game is procedural, leaving the current level untouched.
+    pub fn next_in_campaign(&mut self, levels: &Levels) -> bool {
+        if let CurrentGame::Campaign { section, mut level } = self.current {
+            level += 1;
+            for i_section in (section as usize)..levels.sections.len() {
+                for i_level in level as usize..levels.sections[i_section].levels.len() {
+                    if !self.solved[i_section][i_level] {
+                        self.current = CurrentGame::Campaign {
+                            section: i_section as i32,
+                            level: i_level as i32,
+                        };
+                        self.cached_level = levels.get_level(i_section, i_level).clone();
+                        self.restart();
+                        return true;
                     }
-                    level = 0;
                 }
-                self.cached_level = generate_procedural(VISUALIZE, theme).await;
-                self.in_progress = self.cached_level.initial_grid.clone();
-                self.current = CurrentGame::Procedural;
-            }
-            CurrentGame::Procedural => {
-                self.cached_level = generate_procedural(VISUALIZE, theme).await;
-                self.in_progress = self.cached_level.initial_grid.clone();
+                level = 0;
             }
         }
-        self
+        false
     }
     pub fn is_solved(&self, section: i32, level: i32) -> bool {
         if self.is_random_index(section, level) {
@@ -107,6 +150,20 @@ impl GameTrack {
     pub fn is_random_index(&self, section: i32, level: i32) -> bool {
         section == self.solved.len() as i32 && level == 0
     }
+    pub fn hints_used(&self) -> u32 {
+        match self.current {
+            CurrentGame::Campaign { section, level } => *get(&self.hints, section, level),
+            CurrentGame::Procedural => self.procedural_hints,
+        }
+    }
+    pub fn use_hint(&mut self) {
+        match self.current {
+            CurrentGame::Campaign { section, level } => {
+                self.hints[section as usize][level as usize] += 1;
+            }
+            CurrentGame::Procedural => self.procedural_hints += 1,
+        }
+    }
     pub fn solved(&mut self) {
         match self.current {
             CurrentGame::Campaign { section, level } => {
@@ -125,14 +182,15 @@ impl GameTrack {
         if let Some(level_copy) = levels.maybe_get_level(section, level).cloned() {
             self.current = CurrentGame::Campaign { section, level };
             self.cached_level = level_copy;
-            self.in_progress = self.cached_level.initial_grid.clone();
+            self.restart();
             true
         } else if self.is_random_index(section, level) {
             if let CurrentGame::Procedural = self.current {
             } else {
                 self.current = CurrentGame::Procedural;
                 self.cached_level = generate_procedural(VISUALIZE, theme).await;
-                self.in_progress = self.cached_level.initial_grid.clone();
+                self.restart();
+                self.procedural_hints = 0;
             }
             true
         } else {
@@ -142,21 +200,17 @@ impl GameTrack {
 }
 
 pub async fn generate_procedural(visualize: bool, theme: &Theme) -> Level {
-    let mut solution = generate_grid(visualize, theme).await;
+    let (rows, columns) = (theme.preferred_rows(), theme.preferred_columns());
+    if !visualize {
+        return generator::generate_procedural(rows, columns);
+    }
+    let mut solution = generate_grid_visualized(theme).await;
     solution.recalculate_rails();
     while !count_unreachable_rails(&solution).success() {
-        solution = generate_grid(visualize, theme).await;
+        solution = generate_grid_visualized(theme).await;
         solution.recalculate_rails();
     }
-    // println!("tried {} iterations", i);
-    let mut grid = Grid::new(solution.rows(), solution.columns(), solution.root);
-    grid.recalculate_rails();
-    let constraints = choose_constraints(&solution);
-    Level {
-        initial_grid: grid,
-        constraints,
-        solution,
-    }
+    level_from_solution(solution)
 }
 
 impl CurrentGame {
@@ -1,27 +1,33 @@
-pub mod logic {
-    pub mod constraints;
-    pub mod grid;
-    pub mod intersection;
-    pub mod pixel_grid;
-    pub mod rails;
-}
+pub use metro_loop_core::{
+    direction, levels, logic, math, random, AnyError, CLUE_PERCENTAGE, MAX_CELLS_COEF,
+};
+pub mod canvas;
+pub mod export;
+pub mod level_editor;
 pub mod level_history;
-pub mod levels;
 pub mod render;
+pub mod scene_stack;
 pub mod slider;
 pub mod sound;
 pub mod theme;
 
 pub mod scenes {
+    pub mod editor;
+    pub use editor::editor;
     pub mod main_menu;
     pub use main_menu::main_menu;
     pub mod options;
     pub use options::options;
+    pub mod pause_menu;
+    pub use pause_menu::pause_menu;
+    pub mod help;
+    pub use help::help;
     pub mod loading_screen;
     pub use loading_screen::loading_screen;
     pub mod level_selector;
     pub use level_selector::level_selector;
     pub mod play {
+        pub mod controller;
         pub mod panel;
         pub mod play;
         pub use play::*;
@@ -32,7 +38,6 @@ pub mod scenes {
 use crate::logic::constraints::{Constraints, RailCoord};
 use crate::logic::grid::{get, Grid};
 use crate::theme::Layout;
-use juquad::widgets::anchor::Vertical;
 use juquad::widgets::{StateStyle, Style};
 use macroquad::prelude::*;
 
@@ -51,6 +56,11 @@ pub const SHOW_SLIDER: bool = false;
 pub const DEFAULT_VOLUME: f32 = 0.0;
 // pub const DEFAULT_VOLUME: f32 = 0.0;
 pub const TOOLTIP_DELAY: f64 = 2.5;
+/// Opacity of the rails and clues that a cell would have if the hovered cell were toggled.
+pub const PREVIEW_ALPHA: f32 = 0.4;
+/// Period of the lightness pulse of the failing clues, while hovering the goals.
+pub const PULSE_SECONDS: f64 = 1.0;
+pub const TRANSITION_SECONDS: f64 = 0.15;
 
 pub const TRANSPARENT: Color = Color::new(0.0, 0.0, 0.0, 0.0);
 // pub const BACKGROUND: Color = Color::new(0.1, 0.1, 0.1, 1.00);
@@ -69,6 +79,9 @@ pub const FAILING_TRANSPARENT_DARK: Color = color_average_weight(FAILING_DARK, T
 pub const SUCCESS_DARK: Color = color_average(SUCCESS, BLACK);
 pub const SUCCESS_TRANSPARENT: Color = color_average_weight(SUCCESS, TRANSPARENT, 0.2);
 pub const SUCCESS_LIGHT: Color = color_average_weight(SUCCESS, WHITE, 0.8);
+pub const HINT: Color = SUCCESS_LIGHT;
+/// Tint of the blocks changed while trying something.
+pub const BRANCH: Color = VIOLET;
 
 // pub const TRIANGLE: Color = Color::new(0.40, 0.7, 0.9, 1.00); // darker sky blue
 // pub const TRIANGLE: Color = color_average_weight(SUCCESS, FAILING, 0.2);
@@ -77,12 +90,20 @@ pub const TRIANGLE_BORDER: Color = color_average_weight(BLACK, TRIANGLE, 0.25);
 pub const RAIL: Color = TRIANGLE;
 pub const RAIL_BORDER: Color = TRIANGLE_BORDER;
 pub const UNREACHABLE_RAIL: Color = FAILING;
+/// Each loop that doesn't go through the root takes the next of these colours.
+pub const EXTRA_LOOPS: [Color; 4] = [
+    UNREACHABLE_RAIL,
+    color_average_weight(GRAY, PINK, 0.5),
+    color_average_weight(GRAY, LIME, 0.5),
+    color_average_weight(GRAY, GOLD, 0.5),
+];
 
 pub const ENABLED_CELL: Color = color_average_weight(TRIANGLE, DISABLED_CELL, 0.5);
 pub const DISABLED_CELL: Color = DARKGRAY;
 pub const HOVERED_CELL: Color = color_average(ENABLED_CELL, DISABLED_CELL);
 pub const FIX_MARKER: Color = color_average_weight(BLACK, DARKGRAY, 0.3);
 pub const USER_FIX_MARKER: Color = LIGHTLIGHTGRAY;
+pub const NOTE_MARKER: Color = LIGHTLIGHTGRAY;
 
 pub const LIGHTLIGHTGRAY: Color = color_average(LIGHTGRAY, WHITE);
 pub const LIGTHDARKDARKGRAY: Color = color_average_weight(BLACK, DARKGRAY, 0.8);
@@ -114,8 +135,16 @@ pub const TEXT_STYLE: StateStyle = StateStyle {
 
 pub const NUM_ROWS: i32 = 10;
 pub const NUM_COLUMNS: i32 = 11;
-pub const MAX_CELLS_COEF: f32 = 0.5;
-pub const CLUE_PERCENTAGE: u32 = 30;
+
+/// Sizes before scaling to the screen.
+pub const CELL_WIDTH: f32 = 50.0;
+pub const CELL_HEIGHT: f32 = 50.0;
+pub const GRID_PAD: f32 = 30.0;
+pub const CELL_PAD: f32 = 5.0;
+pub const FONT_SIZE: f32 = 16.0;
+/// Half widths of the triangles on rails and stations, relative to the rail length.
+pub const SMALL_TRIANGLE_HALF_WIDTH: f32 = 0.15;
+pub const TRIANGLE_HALF_WIDTH: f32 = 0.3;
 
 // pub const BUTTON_PANEL_WIDTH: f32 = 300.0;
 
@@ -124,15 +153,7 @@ pub const DEFAULT_WINDOW_WIDTH: i32 = 990;
 pub const DEFAULT_WINDOW_HEIGHT: i32 = width_to_height_default(DEFAULT_WINDOW_WIDTH as f32) as i32;
 pub const DEFAULT_WINDOW_TITLE: &str = "Metro Loop";
 
-pub type AnyError = Box<dyn std::error::Error>;
-
 pub fn new_layout(screen_width: f32, screen_height: f32) -> Layout {
-    const FONT_SIZE: f32 = 16.0;
-    const CELL_WIDTH: f32 = 50.0;
-    const CELL_HEIGHT: f32 = 50.0;
-    const GRID_PAD: f32 = 30.0;
-    const CELL_PAD: f32 = 5.0;
-
     let update_scale = |value: f32| choose_scale(screen_width, screen_height, value);
     let font_size = update_scale(FONT_SIZE);
     let grid_pad = update_scale(GRID_PAD);
@@ -152,14 +173,6 @@ pub fn new_layout(screen_width: f32, screen_height: f32) -> Layout {
     .readjust()
 }
 
-pub enum NextStage {
-    MainMenu,
-    LevelSelector,
-    Campaign,
-    Options,
-    Quit,
-}
-
 const fn color_average(color_1: Color, color_2: Color) -> Color {
     color_average_weight(color_1, color_2, 0.5)
 }
@@ -184,11 +197,6 @@ const fn choose_scale(width: f32, height: f32, font_size: f32) -> f32 {
         }
 }
 
-fn generate_nested_vec<T: Clone>(num_rows: usize, num_columns: usize, default: T) -> Vec<Vec<T>> {
-    let row = vec![default; num_columns];
-    let rows = vec![row; num_rows];
-    rows
-}
 pub const fn width_to_height_default(width: f32) -> f32 {
     width_to_height(width, DEFAULT_ASPECT_RATIO)
 }
@@ -1,9 +1,10 @@
 use macroquad::miniquad::date::now;
 use macroquad::prelude::{next_frame, Conf};
-use macroquad::rand::srand;
 use metro_loop::level_history::GameTrack;
+use metro_loop::random::srand;
+use metro_loop::scene_stack::{fade_out, snapshot, SceneStack, Stage, Transition};
 use metro_loop::{
-    scenes, AnyError, NextStage, DEFAULT_WINDOW_HEIGHT, DEFAULT_WINDOW_TITLE, DEFAULT_WINDOW_WIDTH,
+    scenes, AnyError, DEFAULT_WINDOW_HEIGHT, DEFAULT_WINDOW_TITLE, DEFAULT_WINDOW_WIDTH,
     STARTING_LEVEL, STARTING_SECTION,
 };
 
@@ -15,17 +16,31 @@ async fn main() -> Result<(), AnyError> {
     let args = parse_args()?;
     let mut theme = scenes::loading_screen(args.sound_enabled).await?;
     let mut game_track = GameTrack::new(args.section, args.level, &theme.resources.levels)?;
-    let mut next_stage = NextStage::MainMenu;
-    loop {
-        next_stage = match next_stage {
-            NextStage::MainMenu => scenes::main_menu(&mut theme).await?,
-            NextStage::LevelSelector => scenes::level_selector(&mut theme, &mut game_track).await?,
-            NextStage::Campaign => scenes::play(&mut theme, &mut game_track).await?,
-            NextStage::Options => scenes::options(&mut theme).await?,
-            NextStage::Quit => return Ok(()),
+    let mut scene_stack = SceneStack::new(Stage::MainMenu);
+    while let Some(stage) = scene_stack.top() {
+        let transition = match stage {
+            Stage::MainMenu => scenes::main_menu(&mut theme).await?,
+            Stage::LevelSelector => scenes::level_selector(&mut theme, &mut game_track).await?,
+            Stage::Campaign => scenes::play(&mut theme, &mut game_track).await?,
+            Stage::Options => scenes::options(&mut theme, scene_stack.backdrop()).await?,
+            Stage::Editor => scenes::editor(&mut theme).await?,
+            Stage::PauseMenu => {
+                let backdrop = scene_stack.backdrop();
+                scenes::pause_menu(&mut theme, &mut game_track, backdrop).await?
+            }
+            Stage::Help => scenes::help(&mut theme, scene_stack.backdrop()).await?,
         };
+        let snapshot = snapshot();
+        let overlay = matches!(transition, Transition::Overlay(_));
+        if scene_stack.is_full_change(transition) {
+            fade_out(snapshot).await;
+        } else if !overlay {
+            snapshot.delete();
+        }
+        scene_stack.apply(transition, overlay.then_some(snapshot));
         next_frame().await
     }
+    Ok(())
 }
 
 fn window_conf() -> Conf {
@@ -1,12 +1,15 @@
-use crate::logic::constraints::{matches_constraint_and_reachable, Constraint};
-use crate::logic::grid::{get_cell, is_system_fixed, UserFix};
+use crate::canvas::{Canvas, MacroquadCanvas};
+use crate::direction::{Direction, Horizontal, Spot, Vertical};
+use crate::logic::constraints::{find_loops, matches_constraint_and_reachable, Constraint};
+use crate::logic::deduction::{Deduction, Reason};
+use crate::logic::grid::{get_cell, is_system_fixed, Note, UserFix};
 use crate::logic::intersection::{Crossing, Intersection};
 use crate::logic::pixel_grid::Coord;
-use crate::theme::Theme;
+use crate::math::{ivec2, IVec2};
+use crate::theme::{Layout, Theme};
 use crate::*;
 use juquad::draw::{draw_rect, draw_rect_lines};
 use juquad::lazy::add_contour;
-use juquad::widgets::anchor::{Direction, Horizontal, Spot};
 use macroquad::math::f32;
 use macroquad::prelude::*;
 
@@ -14,6 +17,7 @@ use macroquad::prelude::*;
 pub enum RenderRail {
     Some {
         reachable: bool,
+        color: Color,
         start: IVec2,
         end: IVec2,
         coord: IVec2,
@@ -25,7 +29,12 @@ pub fn is_horizontal_center(horizontal: Horizontal) -> bool {
     horizontal.opposite() == horizontal
 }
 
-pub fn render_cells(grid: &Grid, hovered_cell: &Option<Coord>, theme: &Theme) {
+pub fn render_cells(
+    canvas: &mut dyn Canvas,
+    grid: &Grid,
+    hovered_cell: &Option<Coord>,
+    layout: &Layout,
+) {
     for i_row in 0..grid.rows() {
         for i_column in 0..grid.columns() {
             let color = if *hovered_cell == Some(Coord::new_i(i_row, i_column)) {
@@ -38,18 +47,33 @@ pub fn render_cells(grid: &Grid, hovered_cell: &Option<Coord>, theme: &Theme) {
                     DISABLED_CELL
                 }
             };
-            let cell_pos = cell_top_left(i_row, i_column, theme);
-            draw_rectangle(
-                cell_pos.x,
-                cell_pos.y,
-                theme.cell_width(),
-                theme.cell_height(),
-                color,
-            );
+            let cell_pos = cell_top_left(i_row, i_column, layout);
+            let size = vec2(layout.cell_width(), layout.cell_height());
+            canvas.rect(Rect::new(cell_pos.x, cell_pos.y, size.x, size.y), color);
+            let note = *get(&grid.notes, i_row, i_column);
+            render_note(canvas, note, cell_pos, size, layout.cell_pad());
+        }
+    }
+}
+
+/// A small glyph in the top right corner of the cell: a dot for inside, a dash for outside.
+fn render_note(canvas: &mut dyn Canvas, note: Note, cell_pos: Vec2, size: Vec2, pad: f32) {
+    let glyph = size * 0.2;
+    let top_left = cell_pos + vec2(size.x - glyph.x - pad, pad);
+    match note {
+        Note::Unknown => {}
+        Note::Inside => canvas.rect(
+            Rect::new(top_left.x, top_left.y, glyph.x, glyph.y),
+            NOTE_MARKER,
+        ),
+        Note::Outside => {
+            let middle = top_left + vec2(0.0, glyph.y * 0.5);
+            canvas.line(middle, middle + vec2(glyph.x, 0.0), pad * 0.5, NOTE_MARKER);
         }
     }
 }
-pub fn render_grid(grid: &Grid, theme: &Theme) {
+pub fn render_grid(canvas: &mut dyn Canvas, grid: &Grid, layout: &Layout) {
+    let loops = find_loops(grid);
     // fix markers
     for i_row in 0..grid.rows() {
         for i_column in 0..grid.columns() {
@@ -57,21 +81,21 @@ pub fn render_grid(grid: &Grid, theme: &Theme) {
             let system_fixed = is_system_fixed(grid, i_row, i_column);
             let color = if system_fixed { FIX_MARKER } else { TRIANGLE };
             if current_cell {
-                let mut intersection = top_left_rail_intersection(i_row, i_column, theme);
-                intersection += vec2(theme.cell_width(), theme.cell_height()) * 0.5;
+                let mut intersection = top_left_rail_intersection(i_row, i_column, layout);
+                intersection += vec2(layout.cell_width(), layout.cell_height()) * 0.5;
                 let rect = Rect::new(
                     intersection.x,
                     intersection.y,
-                    theme.cell_pad(),
-                    theme.cell_pad(),
+                    layout.cell_pad(),
+                    layout.cell_pad(),
                 );
-                draw_rect(rect, color);
+                canvas.rect(rect, color);
                 let thickness = 2.0;
                 let border_rect = add_contour(rect, Vec2::splat(thickness * 0.5));
                 if !system_fixed {
-                    draw_rect_lines(border_rect, thickness, DISABLED_CELL);
+                    canvas.rect_lines(border_rect, thickness, DISABLED_CELL);
                 } else {
-                    draw_rect_lines(border_rect, thickness, HOVERED_CELL);
+                    canvas.rect_lines(border_rect, thickness, HOVERED_CELL);
                 }
             }
         }
@@ -91,12 +115,13 @@ pub fn render_grid(grid: &Grid, theme: &Theme) {
                 let reachable = grid.reachable_rails.get_horiz(i_row, i_column);
                 RenderRail::Some {
                     reachable,
+                    color: loop_color(loops.rails.get_horiz(i_row, i_column)),
                     start,
                     end,
                     coord: ivec2(i_column, i_row),
                 }
             };
-            render_rail(rail, theme);
+            render_rail(canvas, rail, layout);
         }
     }
 
@@ -115,12 +140,13 @@ pub fn render_grid(grid: &Grid, theme: &Theme) {
                 let reachable = grid.reachable_rails.get_vert(i_row, i_column);
                 RenderRail::Some {
                     reachable,
+                    color: loop_color(loops.rails.get_vert(i_row, i_column)),
                     start,
                     end,
                     coord: ivec2(i_column, i_row),
                 }
             };
-            render_rail(rail, theme);
+            render_rail(canvas, rail, layout);
         }
     }
     // intersections
@@ -133,90 +159,115 @@ pub fn render_grid(grid: &Grid, theme: &Theme) {
                 // above,
                 crossing,
             } = grid.intersections.get(i_row, i_column);
-            let color = if grid.reachable_rails.get_horiz(i_row, i_column)
-                || grid.reachable_rails.get_vert(i_row, i_column)
-                || grid.reachable_rails.get_vert(i_row - 1, i_column)
-                || grid.reachable_rails.get_horiz(i_row, i_column - 1)
-            {
-                RAIL
-            } else {
-                UNREACHABLE_RAIL
-            };
-            let bottom_right = cell_top_left(i_row, i_column, theme);
-            let top_left = bottom_right - theme.cell_pad();
-            let top_right = top_left + vec2(theme.cell_pad(), 0.0);
-            let bottom_left = top_left + vec2(0.0, theme.cell_pad());
+            let around = [
+                loops.rails.get_horiz(i_row, i_column),
+                loops.rails.get_vert(i_row, i_column),
+                loops.rails.get_vert(i_row - 1, i_column),
+                loops.rails.get_horiz(i_row, i_column - 1),
+            ];
+            let color = loop_color(around.into_iter().flatten().min());
+            let bottom_right = cell_top_left(i_row, i_column, layout);
+            let top_left = bottom_right - layout.cell_pad();
+            let top_right = top_left + vec2(layout.cell_pad(), 0.0);
+            let bottom_left = top_left + vec2(0.0, layout.cell_pad());
             let intersection_rect =
-                Rect::new(top_left.x, top_left.y, theme.cell_pad(), theme.cell_pad());
+                Rect::new(top_left.x, top_left.y, layout.cell_pad(), layout.cell_pad());
             match crossing {
                 Crossing::None => {}
-                Crossing::Single => draw_rect(intersection_rect, color),
+                Crossing::Single => canvas.rect(intersection_rect, color),
                 Crossing::TopLeftToBottomRigt => {
-                    draw_rect(intersection_rect, color);
-                    draw_line_v(top_left, bottom_right, TRIANGLE_BORDER);
+                    canvas.rect(intersection_rect, color);
+                    canvas.line(top_left, bottom_right, 1.0, TRIANGLE_BORDER);
                 }
                 Crossing::TopRightToBottomLeft => {
-                    draw_rect(intersection_rect, color);
-                    draw_line_v(top_right, bottom_left, TRIANGLE_BORDER);
+                    canvas.rect(intersection_rect, color);
+                    canvas.line(top_right, bottom_left, 1.0, TRIANGLE_BORDER);
                 }
                 Crossing::VerticalOnTop => {
-                    draw_rect(intersection_rect, color);
+                    canvas.rect(intersection_rect, color);
                     let top_left = top_left + vec2(-0.5, 0.0);
                     let bottom_left = bottom_left + vec2(-0.5, 0.0);
                     let top_right = top_right + vec2(0.5, 0.0);
                     let bottom_right = bottom_right + vec2(0.5, 0.0);
-                    draw_line_v(top_right, bottom_right, TRIANGLE_BORDER);
-                    draw_line_v(top_left, bottom_left, TRIANGLE_BORDER);
+                    canvas.line(top_right, bottom_right, 1.0, TRIANGLE_BORDER);
+                    canvas.line(top_left, bottom_left, 1.0, TRIANGLE_BORDER);
                 }
                 Crossing::HorizontalOnTop => {
-                    draw_rect(intersection_rect, color);
+                    canvas.rect(intersection_rect, color);
                     let top_left = top_left + vec2(0.0, -0.5);
                     let bottom_left = bottom_left + vec2(0.0, 0.5);
                     let top_right = top_right + vec2(0.0, -0.5);
                     let bottom_right = bottom_right + vec2(0.0, 0.5);
-                    draw_line_v(top_right, top_left, TRIANGLE_BORDER);
-                    draw_line_v(bottom_right, bottom_left, TRIANGLE_BORDER);
+                    canvas.line(top_right, top_left, 1.0, TRIANGLE_BORDER);
+                    canvas.line(bottom_right, bottom_left, 1.0, TRIANGLE_BORDER);
                 }
             }
         }
     }
 }
 
-pub fn render_rail(render_rail: RenderRail, theme: &Theme) {
+pub fn render_rail(canvas: &mut dyn Canvas, render_rail: RenderRail, layout: &Layout) {
     match render_rail {
         RenderRail::None => {}
         RenderRail::Some {
             reachable,
+            color,
             start,
             end,
             coord,
         } => {
             let start = coord + start;
             let end = coord + end;
-            let start = top_left_rail_intersection(start.y, start.x, theme);
-            let end = top_left_rail_intersection(end.y, end.x, theme);
-            draw_rail(start, end, theme, reachable);
+            let start = top_left_rail_intersection(start.y, start.x, layout);
+            let end = top_left_rail_intersection(end.y, end.x, layout);
+            draw_colored_rail(canvas, start, end, layout, color, reachable);
         }
     }
 }
 
-pub fn draw_rail(start: Vec2, end: Vec2, theme: &Theme, reachable: bool) {
+/// The loop through the root (index 0) is a normal rail, the others cycle through `EXTRA_LOOPS`.
+pub fn loop_color(loop_index: Option<usize>) -> Color {
+    match loop_index {
+        None | Some(0) => RAIL,
+        Some(index) => EXTRA_LOOPS[(index - 1) % EXTRA_LOOPS.len()],
+    }
+}
+
+pub fn draw_rail(
+    canvas: &mut dyn Canvas,
+    start: Vec2,
+    end: Vec2,
+    layout: &Layout,
+    reachable: bool,
+) {
     let color = if reachable { RAIL } else { UNREACHABLE_RAIL };
-    draw_line(start.x, start.y, end.x, end.y, theme.cell_pad(), color);
+    draw_colored_rail(canvas, start, end, layout, color, reachable);
+}
+
+fn draw_colored_rail(
+    canvas: &mut dyn Canvas,
+    start: Vec2,
+    end: Vec2,
+    layout: &Layout,
+    color: Color,
+    reachable: bool,
+) {
+    canvas.line(start, end, layout.cell_pad(), color);
     let direction = (end - start).normalize();
-    let border_start = start + direction * theme.cell_pad() * 0.5;
+    let border_start = start + direction * layout.cell_pad() * 0.5;
     let leftwards = vec2(direction.y, -direction.x);
-    let left_border_start = border_start + leftwards * (theme.cell_pad() * 0.5 + 0.5);
-    let right_border_start = border_start - leftwards * (theme.cell_pad() * 0.5 + 0.5);
-    let border_end = end - direction * theme.cell_pad() * 0.5;
-    let left_border_end = border_end + leftwards * (theme.cell_pad() * 0.5 + 0.5);
-    let right_border_end = border_end - leftwards * (theme.cell_pad() * 0.5 + 0.5);
-    draw_line_v(left_border_start, left_border_end, TRIANGLE_BORDER);
-    draw_line_v(right_border_start, right_border_end, TRIANGLE_BORDER);
+    let left_border_start = border_start + leftwards * (layout.cell_pad() * 0.5 + 0.5);
+    let right_border_start = border_start - leftwards * (layout.cell_pad() * 0.5 + 0.5);
+    let border_end = end - direction * layout.cell_pad() * 0.5;
+    let left_border_end = border_end + leftwards * (layout.cell_pad() * 0.5 + 0.5);
+    let right_border_end = border_end - leftwards * (layout.cell_pad() * 0.5 + 0.5);
+    canvas.line(left_border_start, left_border_end, 1.0, TRIANGLE_BORDER);
+    canvas.line(right_border_start, right_border_end, 1.0, TRIANGLE_BORDER);
 
     if reachable {
         calculate_and_draw_triangle(
-            theme,
+            canvas,
+            layout,
             color,
             TRIANGLE_BORDER,
             start,
@@ -227,8 +278,10 @@ pub fn draw_rail(start: Vec2, end: Vec2, theme: &Theme, reachable: bool) {
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 fn calculate_and_draw_triangle(
-    theme: &Theme,
+    canvas: &mut dyn Canvas,
+    layout: &Layout,
     color: Color,
     color_border: Color,
     start: Vec2,
@@ -237,66 +290,29 @@ fn calculate_and_draw_triangle(
     leftwards: Vec2,
 ) {
     let mid = start + length * 0.5;
-    let triangle_half_width = length.length() * theme.small_triangle_half_width();
+    let triangle_half_width = length.length() * layout.small_triangle_half_width();
     let left = mid + leftwards * triangle_half_width;
     let right = mid - leftwards * triangle_half_width;
     let tip = mid + triangle_half_width * direction;
-    draw_bordered_triangle(left, right, tip, color, color_border);
+    canvas.bordered_triangle(left, right, tip, color, color_border);
 }
 
-pub fn render_constraints(constraints: &Constraints, grid: &Grid, theme: &Theme) {
+pub fn render_constraints(
+    canvas: &mut dyn Canvas,
+    constraints: &Constraints,
+    grid: &Grid,
+    layout: &Layout,
+) {
     for constraint in &constraints.rails {
-        let (success, reversed_rail, reachable) =
-            matches_constraint_and_reachable(grid, constraint);
-        let (color, color_border) = if success {
-            (SUCCESS, SUCCESS_DARK)
-        } else {
-            (FAILING, FAILING_DARK)
-        };
-
-        let (row, column) = constraint.row_column();
-        let direction = constraint.vec2();
-        let constraint_render = constraint.type_();
-
-        let corner = top_left_rail_intersection(row, column, theme);
-        let reverse = direction.x + direction.y < 0.0;
-        let length = direction * (theme.cell_width() + theme.cell_pad());
-        let start = corner - reverse as i32 as f32 * length;
-
-        match constraint_render {
-            Constraint::Station(_) => {
-                draw_station(
-                    theme,
-                    success,
-                    color,
-                    color_border,
-                    start,
-                    length,
-                    reachable,
-                );
-            }
-            Constraint::Blockade => {
-                let enabled = *get(&grid.cells, row, column);
-                draw_blockade(
-                    theme,
-                    success,
-                    color,
-                    color_border,
-                    start,
-                    length,
-                    reversed_rail.is_reverse(),
-                    enabled,
-                    reachable,
-                );
-            }
-        }
+        render_constraint(canvas, constraint, grid, layout);
     }
     for row in 1..grid.fixed_rails.horiz_rows() {
         for column in 1..grid.fixed_rails.horiz_columns() {
             let user_constraint = grid.fixed_rails.get_horiz(row, column);
             draw_user_rail_constraints(
+                canvas,
                 grid,
-                theme,
+                layout,
                 row,
                 column,
                 user_constraint,
@@ -308,8 +324,9 @@ pub fn render_constraints(constraints: &Constraints, grid: &Grid, theme: &Theme)
         for column in 1..grid.fixed_rails.vert_columns() {
             let user_constraint = grid.fixed_rails.get_vert(row, column);
             draw_user_rail_constraints(
+                canvas,
                 grid,
-                theme,
+                layout,
                 row,
                 column,
                 user_constraint,
@@ -319,9 +336,100 @@ pub fn render_constraints(constraints: &Constraints, grid: &Grid, theme: &Theme)
     }
 }
 
+/// Only the clues that the grid doesn't satisfy, to draw them again on top, highlighted.
+pub fn render_failing_constraints(
+    canvas: &mut dyn Canvas,
+    constraints: &Constraints,
+    grid: &Grid,
+    layout: &Layout,
+) {
+    for constraint in &constraints.rails {
+        if !matches_constraint_and_reachable(grid, constraint).0 {
+            render_constraint(canvas, constraint, grid, layout);
+        }
+    }
+}
+
+fn render_constraint(
+    canvas: &mut dyn Canvas,
+    constraint: &RailCoord,
+    grid: &Grid,
+    layout: &Layout,
+) {
+    let (success, reversed_rail, reachable) = matches_constraint_and_reachable(grid, constraint);
+    let (color, color_border) = if success {
+        (SUCCESS, SUCCESS_DARK)
+    } else {
+        (FAILING, FAILING_DARK)
+    };
+
+    let (row, column) = constraint.row_column();
+    let math::Vec2 { x, y } = constraint.vec2();
+    let direction = vec2(x, y);
+    let constraint_render = constraint.type_();
+
+    let corner = top_left_rail_intersection(row, column, layout);
+    let reverse = direction.x + direction.y < 0.0;
+    let length = direction * (layout.cell_width() + layout.cell_pad());
+    let start = corner - reverse as i32 as f32 * length;
+
+    match constraint_render {
+        Constraint::Station(_) => {
+            draw_station(
+                canvas,
+                layout,
+                success,
+                color,
+                color_border,
+                start,
+                length,
+                reachable,
+            );
+        }
+        Constraint::Blockade => {
+            let enabled = *get(&grid.cells, row, column);
+            draw_blockade(
+                canvas,
+                layout,
+                success,
+                color,
+                color_border,
+                start,
+                length,
+                reversed_rail.is_reverse(),
+                enabled,
+                reachable,
+            );
+        }
+    }
+}
+
+/// The clue drawn under `pos`, if any.
+pub fn constraint_at<'a>(
+    pos: Vec2,
+    constraints: &'a Constraints,
+    layout: &Layout,
+) -> Option<&'a RailCoord> {
+    let radius = layout.cell_width().min(layout.cell_height()) * 0.25;
+    constraints
+        .rails
+        .iter()
+        .find(|constraint| rail_middle(constraint, layout).distance(pos) < radius)
+}
+
+fn rail_middle(constraint: &RailCoord, layout: &Layout) -> Vec2 {
+    let (row, column) = constraint.row_column();
+    let length = match constraint.direction() {
+        Direction::Horizontal => vec2(layout.cell_width() + layout.cell_pad(), 0.0),
+        Direction::Vertical => vec2(0.0, layout.cell_height() + layout.cell_pad()),
+    };
+    top_left_rail_intersection(row, column, layout) + length * 0.5
+}
+
 fn draw_user_rail_constraints(
+    canvas: &mut dyn Canvas,
     grid: &Grid,
-    theme: &Theme,
+    layout: &Layout,
     row: i32,
     column: i32,
     user_constraint: UserFix,
@@ -333,23 +441,25 @@ fn draw_user_rail_constraints(
     };
     if user_constraint.blockade {
         let constraint = RailCoord::from_direction(row, column, Spot::Center, direction);
-        render_user_rail_blockade(row, column, direction_v, constraint, grid, theme);
+        render_user_rail_blockade(canvas, row, column, direction_v, constraint, grid, layout);
     }
-    let length = direction_v * (theme.cell_width() + theme.cell_pad());
-    draw_user_stations(theme, row, column, user_constraint, length);
+    let length = direction_v * (layout.cell_width() + layout.cell_pad());
+    draw_user_stations(canvas, layout, row, column, user_constraint, length);
 }
 
 fn draw_user_stations(
-    theme: &Theme,
+    canvas: &mut dyn Canvas,
+    layout: &Layout,
     row: i32,
     column: i32,
     user_constraint: UserFix,
     length: Vec2,
 ) {
-    let start = top_left_rail_intersection(row, column, theme);
+    let start = top_left_rail_intersection(row, column, layout);
     if user_constraint.station_forward {
         draw_station(
-            theme,
+            canvas,
+            layout,
             false,
             TRIANGLE,
             TRIANGLE_BORDER,
@@ -360,7 +470,8 @@ fn draw_user_stations(
     }
     if user_constraint.station_backwards {
         draw_station(
-            theme,
+            canvas,
+            layout,
             false,
             TRIANGLE,
             TRIANGLE_BORDER,
@@ -371,8 +482,10 @@ fn draw_user_stations(
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn draw_station(
-    theme: &Theme,
+    canvas: &mut dyn Canvas,
+    layout: &Layout,
     success: bool,
     color: Color,
     color_border: Color,
@@ -380,8 +493,8 @@ pub fn draw_station(
     length: Vec2,
     reachable: bool,
 ) {
-    let small_triangle_half_width: f32 = length.length() * theme.small_triangle_half_width();
-    let triangle_half_width: f32 = length.length() * theme.triangle_half_width();
+    let small_triangle_half_width: f32 = length.length() * layout.small_triangle_half_width();
+    let triangle_half_width: f32 = length.length() * layout.triangle_half_width();
     let end = start + length;
     let mid = (start + end) * 0.5;
     let mut diff = (end - start).normalize();
@@ -395,23 +508,35 @@ pub fn draw_station(
 
     // let daltonic_distinction = if success { color } else { color_border };
 
-    draw_triangles(
+    canvas.triangles(
         &[outer_left, left, outer_tip, tip, outer_right, right],
         color,
     );
 
-    draw_lines(
+    canvas.lines(
         &[tip, left, outer_left, outer_tip, outer_right, right, tip],
+        1.0,
         color_border,
     );
     if !success && reachable {
         diff *= -1.0;
-        calculate_and_draw_triangle(theme, color, color_border, start, length, diff, to_left);
+        calculate_and_draw_triangle(
+            canvas,
+            layout,
+            color,
+            color_border,
+            start,
+            length,
+            diff,
+            to_left,
+        );
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn draw_blockade(
-    theme: &Theme,
+    canvas: &mut dyn Canvas,
+    layout: &Layout,
     success: bool,
     color: Color,
     color_border: Color,
@@ -424,12 +549,12 @@ pub fn draw_blockade(
     let mid = start + length * 0.5;
     let mut direction = length.normalize();
     let to_left = vec2(direction.y, -direction.x);
-    let small_triangle_half_width: f32 = length.length() * theme.small_triangle_half_width();
+    let small_triangle_half_width: f32 = length.length() * layout.small_triangle_half_width();
     let forward = direction * small_triangle_half_width * 1.5;
     let forward_long = direction * small_triangle_half_width * 2.5;
     let leftward = to_left * small_triangle_half_width * 0.5;
-    let leftward_gap = to_left * (theme.cell_pad() * 0.5 + 1.0);
-    // let leftward_gap = to_left * theme.cell_pad() * 0.5;
+    let leftward_gap = to_left * (layout.cell_pad() * 0.5 + 1.0);
+    // let leftward_gap = to_left * layout.cell_pad() * 0.5;
     let al = mid + forward_long + leftward;
     let a = mid + forward + leftward;
     let bl = mid + forward_long - leftward;
@@ -446,7 +571,7 @@ pub fn draw_blockade(
     // if success {
     if success {
         let center_color = if enabled { ENABLED_CELL } else { DISABLED_CELL };
-        draw_triangles(
+        canvas.triangles(
             &[
                 mid + forward + leftward_gap,
                 mid + forward - leftward_gap,
@@ -456,27 +581,28 @@ pub fn draw_blockade(
             center_color,
         );
     }
-    draw_triangles(&[al, a, bl, b], color);
-    draw_lines(&[al, a, b, bl, al], color_border);
-    draw_triangles(&[cl, c, dl, d], color);
-    draw_lines(&[cl, c, d, dl, cl], color_border);
+    canvas.triangles(&[al, a, bl, b], color);
+    canvas.lines(&[al, a, b, bl, al], 1.0, color_border);
+    canvas.triangles(&[cl, c, dl, d], color);
+    canvas.lines(&[cl, c, d, dl, cl], 1.0, color_border);
 
     // draw_triangle(a, c, b, blockade_color);
     // draw_triangle(b, c, d, blockade_color);
     // }
-    // draw_lines(&[a, b, d, c, a], color_border);
-    // draw_lines(&[d, c], blockade_color_border);
-    // draw_lines(&[a, b], blockade_color_border);
-    // draw_lines(&[d, c], color_border);
-    // draw_lines(&[a, b], color_border);
+    // canvas.lines(&[a, b, d, c, a], 1.0, color_border);
+    // canvas.lines(&[d, c], 1.0, blockade_color_border);
+    // canvas.lines(&[a, b], 1.0, blockade_color_border);
+    // canvas.lines(&[d, c], 1.0, color_border);
+    // canvas.lines(&[a, b], 1.0, color_border);
 
     if !success && reachable {
         if reverse {
             direction *= -1.0;
         }
-        // calculate_and_draw_triangle(theme, color, start, end, direction, to_left, color_border);
+        // calculate_and_draw_triangle(canvas, layout, color, start, end, direction, to_left, color_border);
         calculate_and_draw_triangle(
-            theme,
+            canvas,
+            layout,
             color,
             color_border,
             start,
@@ -484,10 +610,10 @@ pub fn draw_blockade(
             direction,
             to_left,
         );
-        // calculate_and_draw_triangle(theme, RAIL, start, end, direction, to_left, TRIANGLE_BORDER);
+        // calculate_and_draw_triangle(canvas, layout, RAIL, start, end, direction, to_left, TRIANGLE_BORDER);
 
         //     let forward = diff * thickness * 0.5;
-        //     let leftward = to_left * theme.cell_pad() * 0.75;
+        //     let leftward = to_left * layout.cell_pad() * 0.75;
         //     let a = mid + forward + leftward;
         //     let b = mid + forward - leftward;
         //     let c = mid - forward + leftward;
@@ -498,18 +624,20 @@ pub fn draw_blockade(
 }
 
 fn render_user_rail_blockade(
+    canvas: &mut dyn Canvas,
     row: i32,
     column: i32,
     direction: Vec2,
     constraint: RailCoord,
     grid: &Grid,
-    theme: &Theme,
+    layout: &Layout,
 ) {
-    let start = top_left_rail_intersection(row, column, theme);
-    let length = direction * (theme.cell_width() + theme.cell_pad());
+    let start = top_left_rail_intersection(row, column, layout);
+    let length = direction * (layout.cell_width() + layout.cell_pad());
     let (success, reverse, reachable) = matches_constraint_and_reachable(grid, &constraint);
     draw_blockade(
-        theme,
+        canvas,
+        layout,
         success,
         RAIL,
         TRIANGLE_BORDER,
@@ -521,23 +649,71 @@ fn render_user_rail_blockade(
     );
 }
 
-fn top_left_rail_intersection(i_row: i32, i_column: i32, theme: &Theme) -> Vec2 {
-    cell_top_left(i_row, i_column, theme) - theme.cell_pad() * 0.5
+fn top_left_rail_intersection(i_row: i32, i_column: i32, layout: &Layout) -> Vec2 {
+    cell_top_left(i_row, i_column, layout) - layout.cell_pad() * 0.5
 }
 
-pub fn cell_top_left_coord(coord: Coord, theme: &Theme) -> Vec2 {
-    let pos: Vec2 = coord.into();
-    pos * (vec2(theme.cell_width(), theme.cell_height()) + theme.cell_pad()) + theme.grid_pad()
+/// Outlines the block that a hint is about, and the rail of the clue that forces it.
+pub fn render_hint(canvas: &mut dyn Canvas, hint: &Deduction, layout: &Layout) {
+    let size = vec2(layout.cell_width(), layout.cell_height());
+    let top_left = cell_top_left(hint.row, hint.column, layout);
+    let rect = Rect::new(top_left.x, top_left.y, size.x, size.y);
+    canvas.rect_lines(rect, layout.cell_pad(), HINT);
+    if let Reason::Station { clue, .. } | Reason::Blockade { clue, .. } = &hint.reason {
+        let (row, column) = clue.row_column();
+        let pad = layout.cell_pad();
+        let start = cell_top_left(row, column, layout) - pad * 0.5;
+        let length = match clue.direction() {
+            Direction::Horizontal => vec2(size.x + pad, 0.0),
+            Direction::Vertical => vec2(0.0, size.y + pad),
+        };
+        canvas.line(start, start + length, pad, HINT);
+    }
 }
-pub fn cell_top_left(i_row: i32, i_column: i32, theme: &Theme) -> Vec2 {
-    let x = theme.grid_pad() + i_column as f32 * (theme.cell_width() + theme.cell_pad());
-    let y = theme.grid_pad() + i_row as f32 * (theme.cell_height() + theme.cell_pad());
-    vec2(x, y)
+
+/// Tints the blocks changed inside branches, more strongly the more nested the branch is.
+/// `cells` are (row, column, branch index) and `depth` is how many branches are open.
+pub fn render_branch_cells(
+    canvas: &mut dyn Canvas,
+    grid: &Grid,
+    cells: &[(i32, i32, usize)],
+    depth: usize,
+    layout: &Layout,
+) {
+    let size = vec2(layout.cell_width(), layout.cell_height());
+    for &(row, column, branch) in cells {
+        let cell_color = if *get_cell(grid, row, column) {
+            ENABLED_CELL
+        } else {
+            DISABLED_CELL
+        };
+        let weight = 0.3 + 0.4 * (branch + 1) as f32 / depth as f32;
+        let top_left = cell_top_left(row, column, layout);
+        let rect = Rect::new(top_left.x, top_left.y, size.x, size.y);
+        canvas.rect(rect, color_average_weight(cell_color, BRANCH, weight));
+    }
+}
+
+/// Crosses out the blocks that a check found wrong.
+pub fn render_wrong_cells(canvas: &mut dyn Canvas, cells: &[(i32, i32)], layout: &Layout) {
+    let pad = layout.cell_pad();
+    let size = vec2(layout.cell_width(), layout.cell_height()) - pad * 2.0;
+    for &(row, column) in cells {
+        let top_left = cell_top_left(row, column, layout) + pad;
+        let top_right = top_left + vec2(size.x, 0.0);
+        canvas.line(top_left, top_left + size, pad, FAILING);
+        canvas.line(top_right, top_right + vec2(-size.x, size.y), pad, FAILING);
+    }
 }
 
-pub fn draw_bordered_triangle(p_1: Vec2, p_2: Vec2, p_3: Vec2, color: Color, border: Color) {
-    draw_triangle(p_1, p_2, p_3, color);
-    draw_triangle_lines(p_1, p_2, p_3, 1.0, border);
+pub fn cell_top_left_coord(coord: Coord, layout: &Layout) -> Vec2 {
+    let pos = vec2(coord.column_f(), coord.row_f());
+    pos * (vec2(layout.cell_width(), layout.cell_height()) + layout.cell_pad()) + layout.grid_pad()
+}
+pub fn cell_top_left(i_row: i32, i_column: i32, layout: &Layout) -> Vec2 {
+    let x = layout.grid_pad() + i_column as f32 * (layout.cell_width() + layout.cell_pad());
+    let y = layout.grid_pad() + i_row as f32 * (layout.cell_height() + layout.cell_pad());
+    vec2(x, y)
 }
 
 pub fn render_tick(rect: Rect, theme: &Theme) -> Rect {
@@ -572,31 +748,124 @@ pub fn render_cross(rect: Rect, theme: &Theme) -> Rect {
     rect
 }
 
-pub fn draw_line_v(start: Vec2, end: Vec2, color: Color) {
-    draw_line_thickness(start, end, 1.0, color)
-}
-
 pub fn draw_line_thickness(start: Vec2, end: Vec2, thickness: f32, color: Color) {
-    draw_line(start.x, start.y, end.x, end.y, thickness, color)
-}
-
-pub fn draw_lines(points: &[Vec2], color: Color) {
-    draw_lines_thickness(points, 1.0, color)
+    MacroquadCanvas.line(start, end, thickness, color)
 }
 pub fn draw_lines_thickness(points: &[Vec2], thickness: f32, color: Color) {
-    assert!(points.len() >= 2);
-    for i in 1..points.len() {
-        draw_line_thickness(points[i - 1], points[i], thickness, color);
-    }
+    MacroquadCanvas.lines(points, thickness, color)
 }
-pub fn draw_triangles(vertices: &[Vec2], color: Color) {
-    assert!(vertices.len() >= 3);
-    for i in 2..vertices.len() {
-        let (prev_prev, prev) = if i % 2 == 0 {
-            (i - 2, i - 1)
-        } else {
-            (i - 1, i - 2)
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::canvas::{lightened, translucent, DrawCall, RecordingCanvas};
+    use crate::export::ExportOptions;
+    use crate::levels::Levels;
+
+    #[test]
+    fn test_render_hint() {
+        let levels = Levels::get().unwrap();
+        let level = levels.get_level(0, 0);
+        let layout =
+            ExportOptions::default().layout(level.solution.rows(), level.solution.columns());
+        let hint = crate::logic::deduction::hint(&level.initial_grid, &level.constraints).unwrap();
+        let mut canvas = RecordingCanvas::new();
+        render_hint(&mut canvas, &hint, &layout);
+        assert_eq!(canvas.count_color(HINT), canvas.calls.len());
+        assert!(matches!(canvas.calls[0], DrawCall::RectLines { .. }));
+    }
+
+    #[test]
+    fn test_translucent_preview() {
+        let levels = Levels::get().unwrap();
+        let level = levels.get_level(0, 0);
+        let layout =
+            ExportOptions::default().layout(level.solution.rows(), level.solution.columns());
+        let mut recording = RecordingCanvas::new();
+        let mut canvas = translucent(&mut recording, PREVIEW_ALPHA);
+        render_constraints(&mut canvas, &level.constraints, &level.solution, &layout);
+        assert!(!recording.calls.is_empty());
+        assert!(recording
+            .calls
+            .iter()
+            .all(|call| call.color().a <= PREVIEW_ALPHA));
+    }
+
+    #[test]
+    fn test_failing_constraints() {
+        let levels = Levels::get().unwrap();
+        let level = levels.get_level(0, 0);
+        let layout =
+            ExportOptions::default().layout(level.solution.rows(), level.solution.columns());
+        for constraint in &level.constraints.rails {
+            let middle = rail_middle(constraint, &layout);
+            assert_eq!(
+                constraint_at(middle, &level.constraints, &layout),
+                Some(constraint)
+            );
+        }
+        assert_eq!(constraint_at(Vec2::ZERO, &level.constraints, &layout), None);
+
+        let mut solved = RecordingCanvas::new();
+        render_failing_constraints(&mut solved, &level.constraints, &level.solution, &layout);
+        assert!(solved.calls.is_empty());
+
+        let mut recording = RecordingCanvas::new();
+        let mut pulse = lightened(&mut recording, 0.5);
+        let initial = &level.initial_grid;
+        render_failing_constraints(&mut pulse, &level.constraints, initial, &layout);
+        assert!(!recording.calls.is_empty());
+        assert_eq!(recording.count_color(FAILING), 0);
+    }
+
+    #[test]
+    fn test_render_through_canvas() {
+        let levels = Levels::get().unwrap();
+        let level = levels.get_level(0, 0);
+        let layout =
+            ExportOptions::default().layout(level.solution.rows(), level.solution.columns());
+
+        let mut canvas = RecordingCanvas::new();
+        render_cells(&mut canvas, &level.solution, &None, &layout);
+        let cells = (level.solution.rows() * level.solution.columns()) as usize;
+        assert_eq!(canvas.calls.len(), cells);
+
+        let mut noted = level.solution.clone();
+        noted.notes[1][1] = Note::Inside;
+        noted.notes[1][2] = Note::Outside;
+        let mut with_notes = RecordingCanvas::new();
+        render_cells(&mut with_notes, &noted, &None, &layout);
+        assert_eq!(with_notes.count_color(NOTE_MARKER), 2);
+        let first = DrawCall::Rect {
+            rect: Rect::new(
+                layout.grid_pad,
+                layout.grid_pad,
+                layout.cell_width,
+                layout.cell_height,
+            ),
+            color: DISABLED_CELL,
         };
-        draw_triangle(vertices[prev_prev], vertices[prev], vertices[i], color);
+        assert_eq!(canvas.calls[0], first);
+
+        let mut solved = RecordingCanvas::new();
+        render_constraints(&mut solved, &level.constraints, &level.solution, &layout);
+        assert!(solved.count_color(SUCCESS) > 0);
+        assert_eq!(solved.count_color(FAILING), 0);
+
+        let mut unsolved = RecordingCanvas::new();
+        render_constraints(
+            &mut unsolved,
+            &level.constraints,
+            &level.initial_grid,
+            &layout,
+        );
+        assert!(unsolved.count_color(FAILING) > 0);
+
+        let mut rails = RecordingCanvas::new();
+        render_grid(&mut rails, &level.solution, &layout);
+        let thick_rails = rails.calls.iter().filter(|call| {
+            matches!(call, DrawCall::Line { thickness, color, .. } if *thickness == layout.cell_pad && *color == RAIL)
+        });
+        assert_eq!(thick_rails.count() as i32, level.solution.total_rails);
     }
 }
@@ -0,0 +1,220 @@
+//! The scenes that are open, one on top of the other. Each scene runs its own loop until it
+//! returns a `Transition`, and then the scene on top of the stack runs next. Overlays keep a
+//! snapshot of the scene below, so that they can be drawn on top of it.
+
+use crate::{BACKGROUND, TRANSITION_SECONDS};
+use macroquad::color::{Color, WHITE};
+use macroquad::miniquad::date::now;
+use macroquad::prelude::{
+    clear_background, draw_rectangle, draw_texture_ex, get_screen_data, next_frame, screen_height,
+    screen_width, vec2, DrawTextureParams, Texture2D,
+};
+
+pub const OVERLAY_DIM: Color = Color::new(0.0, 0.0, 0.0, 0.6);
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Stage {
+    MainMenu,
+    LevelSelector,
+    Campaign,
+    Options,
+    Editor,
+    PauseMenu,
+    Help,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Transition {
+    /// Opens a scene. The current one comes back when the new one pops.
+    Push(Stage),
+    /// Like `Push`, but the current scene stays visible, dimmed, behind the new one.
+    Overlay(Stage),
+    Pop,
+    /// Pops scenes until `Stage` is on top. If it's not open, it replaces the whole stack.
+    PopTo(Stage),
+    Replace(Stage),
+    Quit,
+}
+
+struct Entry {
+    stage: Stage,
+    overlay: bool,
+    backdrop: Option<Texture2D>,
+}
+
+pub struct SceneStack {
+    entries: Vec<Entry>,
+}
+
+impl SceneStack {
+    pub fn new(root: Stage) -> Self {
+        Self {
+            entries: vec![Entry {
+                stage: root,
+                overlay: false,
+                backdrop: None,
+            }],
+        }
+    }
+
+    /// The scene to run next, or None if the game should end.
+    pub fn top(&self) -> Option<Stage> {
+        self.entries.last().map(|entry| entry.stage)
+    }
+
+    /// The snapshot of the scene below, if the top scene is an overlay.
+    pub fn backdrop(&self) -> Option<Texture2D> {
+        self.entries.last().and_then(|entry| entry.backdrop)
+    }
+
+    /// Whether going from the current top scene through `transition` changes the whole screen,
+    /// instead of just opening or closing an overlay.
+    pub fn is_full_change(&self, transition: Transition) -> bool {
+        let top_is_overlay = self.entries.last().is_some_and(|entry| entry.overlay);
+        match transition {
+            Transition::Overlay(_) => false,
+            Transition::Pop => !top_is_overlay,
+            Transition::Push(_)
+            | Transition::PopTo(_)
+            | Transition::Replace(_)
+            | Transition::Quit => true,
+        }
+    }
+
+    /// `snapshot` is only needed for `Transition::Overlay`. Without it, the overlay is drawn on
+    /// the plain background.
+    pub fn apply(&mut self, transition: Transition, snapshot: Option<Texture2D>) {
+        match transition {
+            Transition::Push(stage) => self.push(stage, false, None),
+            Transition::Overlay(stage) => self.push(stage, true, snapshot),
+            Transition::Pop => self.pop(),
+            Transition::PopTo(stage) => {
+                if self.entries.iter().any(|entry| entry.stage == stage) {
+                    while self.top() != Some(stage) {
+                        self.pop();
+                    }
+                } else {
+                    self.apply(Transition::Quit, None);
+                    self.push(stage, false, None);
+                }
+            }
+            Transition::Replace(stage) => {
+                self.pop();
+                self.push(stage, false, None);
+            }
+            Transition::Quit => {
+                while !self.entries.is_empty() {
+                    self.pop();
+                }
+            }
+        }
+    }
+
+    fn push(&mut self, stage: Stage, overlay: bool, backdrop: Option<Texture2D>) {
+        self.entries.push(Entry {
+            stage,
+            overlay,
+            backdrop,
+        });
+    }
+
+    fn pop(&mut self) {
+        if let Some(Entry {
+            backdrop: Some(texture),
+            ..
+        }) = self.entries.pop()
+        {
+            texture.delete();
+        }
+    }
+}
+
+/// Copies what has been drawn so far in this frame.
+pub fn snapshot() -> Texture2D {
+    Texture2D::from_image(&get_screen_data())
+}
+
+/// Draws the scene below an overlay, dimmed.
+pub fn draw_backdrop(backdrop: Option<Texture2D>) {
+    clear_background(BACKGROUND);
+    if let Some(texture) = backdrop {
+        draw_snapshot(texture, WHITE);
+        draw_rectangle(0.0, 0.0, screen_width(), screen_height(), OVERLAY_DIM);
+    }
+}
+
+fn draw_snapshot(texture: Texture2D, color: Color) {
+    draw_texture_ex(
+        texture,
+        0.0,
+        0.0,
+        color,
+        DrawTextureParams {
+            dest_size: Some(vec2(screen_width(), screen_height())),
+            flip_y: true,
+            ..Default::default()
+        },
+    );
+}
+
+/// Fades the last frame of a scene into the background, before the next scene starts.
+pub async fn fade_out(snapshot: Texture2D) {
+    let start = now();
+    loop {
+        let progress = ((now() - start) / TRANSITION_SECONDS) as f32;
+        if progress >= 1.0 {
+            break;
+        }
+        clear_background(BACKGROUND);
+        draw_snapshot(snapshot, Color::new(1.0, 1.0, 1.0, 1.0 - progress));
+        next_frame().await;
+    }
+    snapshot.delete();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stack() {
+        let mut stack = SceneStack::new(Stage::MainMenu);
+        stack.apply(Transition::Push(Stage::LevelSelector), None);
+        stack.apply(Transition::Push(Stage::Campaign), None);
+        assert!(!stack.is_full_change(Transition::Overlay(Stage::Options)));
+        stack.apply(Transition::Overlay(Stage::Options), None);
+        assert_eq!(stack.top(), Some(Stage::Options));
+        assert!(!stack.is_full_change(Transition::Pop));
+
+        stack.apply(Transition::Pop, None);
+        assert_eq!(stack.top(), Some(Stage::Campaign));
+        assert!(stack.is_full_change(Transition::Pop));
+
+        stack.apply(Transition::Overlay(Stage::Options), None);
+        stack.apply(Transition::PopTo(Stage::LevelSelector), None);
+        assert_eq!(stack.top(), Some(Stage::LevelSelector));
+
+        stack.apply(Transition::Replace(Stage::Editor), None);
+        stack.apply(Transition::Pop, None);
+        assert_eq!(stack.top(), Some(Stage::MainMenu));
+
+        stack.apply(Transition::PopTo(Stage::Campaign), None);
+        assert_eq!(stack.top(), Some(Stage::Campaign));
+        stack.apply(Transition::Pop, None);
+        assert_eq!(stack.top(), None);
+
+        let mut stack = SceneStack::new(Stage::Campaign);
+        stack.apply(Transition::Overlay(Stage::PauseMenu), None);
+        stack.apply(Transition::Overlay(Stage::Options), None);
+        stack.apply(Transition::Pop, None);
+        assert_eq!(stack.top(), Some(Stage::PauseMenu));
+        assert!(!stack.is_full_change(Transition::Pop));
+        stack.apply(Transition::Pop, None);
+        assert_eq!(stack.top(), Some(Stage::Campaign));
+
+        let mut stack = SceneStack::new(Stage::MainMenu);
+        stack.apply(Transition::Push(Stage::Options), None);
+        stack.apply(Transition::Quit, None);
+        assert_eq!(stack.top(), None);
+    }
+}
@@ -0,0 +1,374 @@
+use crate::canvas::MacroquadCanvas;
+use crate::direction::Direction;
+use crate::level_editor::{Analysis, AnalysisJob, AutoClueJob, LevelEditor, Tool};
+use crate::levels::Level;
+use crate::logic::constraints::count_loops;
+use crate::logic::pixel_grid::Coord;
+use crate::logic::solver::SolveLimits;
+use crate::render::{
+    cell_top_left, draw_line_thickness, render_cells, render_constraints, render_grid,
+};
+use crate::scene_stack::Transition;
+use crate::scenes::options::inc_dec;
+use crate::scenes::play::{pixel_to_coord, ShowingSinceSeconds};
+use crate::theme::{
+    new_button_group_direction, new_text, new_text_group, render_button, render_text,
+    render_tooltip, Theme,
+};
+use crate::{
+    new_layout, AnyError, BACKGROUND, FAILING, FAILING_TRANSPARENT, PANEL_BACKGROUND, SUCCESS,
+    TEXT_STYLE, TOOLTIP_DELAY, TRIANGLE,
+};
+use juquad::draw::{draw_rect, draw_rect_lines};
+use juquad::lazy::add_contour;
+use juquad::widgets::anchor::Anchor;
+use juquad::widgets::button_group;
+use juquad::widgets::Widget;
+use macroquad::input::{
+    is_key_pressed, is_mouse_button_pressed, mouse_position, KeyCode, MouseButton,
+};
+use macroquad::math::{vec2, Rect, Vec2};
+use macroquad::miniquad::date::now;
+use macroquad::prelude::{
+    clear_background, draw_circle_lines, next_frame, screen_height, screen_width,
+};
+
+pub const EDITOR_LEVEL_PATH: &str = "editor_level.txt";
+
+/// Searches that take many solves. They advance one solve per frame.
+enum Job {
+    AutoClue(AutoClueJob),
+    Analysis(AnalysisJob),
+}
+impl Job {
+    fn progress_text(&self) -> String {
+        let (text, (done, total)) = match self {
+            Job::AutoClue(job) => ("Choosing clues", job.progress()),
+            Job::Analysis(job) => ("Analyzing", job.progress()),
+        };
+        format!("{}: {} of {}", text, done, total)
+    }
+}
+
+pub async fn editor(theme: &mut Theme) -> Result<Transition, AnyError> {
+    let mut editor = load(theme);
+    let mut tool = Tool::Solution;
+    let mut tooltip: Option<(String, ShowingSinceSeconds)> = None;
+    let mut analysis: Option<Analysis> = None;
+    let mut job: Option<Job> = None;
+    let limits = SolveLimits::default();
+    let mut screen = Vec2::ZERO;
+    let mut size = (0, 0);
+    loop {
+        let now = now();
+        let new_screen = vec2(screen_width(), screen_height());
+        let new_size = (editor.rows(), editor.columns());
+        if new_screen != screen || new_size != size {
+            screen = new_screen;
+            size = new_size;
+            theme.layout = new_layout(screen.x, screen.y).resize_grid(size.0, size.1);
+        }
+        if is_key_pressed(KeyCode::Escape) {
+            return Ok(Transition::Pop);
+        }
+
+        let pos = Vec2::from(mouse_position());
+        let hovered = pixel_to_coord(pos, &editor.level().solution, theme);
+        if is_mouse_button_pressed(MouseButton::Left) {
+            tooltip = None;
+            if let Some(coord) = hovered {
+                cancel_auto_clue(&mut job, &mut editor);
+                let (row, column) = (coord.row(), coord.column());
+                let result = match tool {
+                    Tool::Solution => editor.toggle_solution(row, column),
+                    Tool::Lock => editor.toggle_lock(row, column),
+                    Tool::PreEnable => editor.toggle_pre_enabled(row, column),
+                    Tool::Root => editor.move_root(row, column),
+                    Tool::Clue => {
+                        let (row, column, direction) = closest_rail(coord);
+                        editor.toggle_clue(row, column, direction)
+                    }
+                };
+                match result {
+                    Ok(()) => (analysis, job) = (None, None),
+                    Err(e) => tooltip = Some((e.text().to_string(), now)),
+                }
+            }
+        }
+
+        clear_background(BACKGROUND);
+        let level = editor.level();
+        let hovered_cell = match tool {
+            Tool::Clue => None,
+            _ => hovered.map(Coord::floor),
+        };
+        render_cells(
+            &mut MacroquadCanvas,
+            &level.solution,
+            &hovered_cell,
+            &theme.layout,
+        );
+        render_grid(&mut MacroquadCanvas, &level.solution, &theme.layout);
+        render_constraints(
+            &mut MacroquadCanvas,
+            &level.constraints,
+            &level.solution,
+            &theme.layout,
+        );
+        render_pre_enabled(&editor, theme);
+        if let (Tool::Clue, Some(coord)) = (tool, hovered) {
+            let (row, column, direction) = closest_rail(coord);
+            if editor.is_clue_position(row, column, direction) {
+                render_hovered_rail(row, column, direction, theme);
+            }
+        }
+        if let Some(analysis) = &analysis {
+            render_analysis(analysis, &editor, theme);
+        }
+
+        let panel = theme.button_panel_rect(&level.solution);
+        draw_rect(panel, PANEL_BACKGROUND);
+        let anchor = Anchor::top_center(panel.center().x, panel.y + theme.button_margin());
+        let tool_texts = new_text_group(anchor, theme).create(Tool::ALL.map(|t| t.text()));
+        let mut bottom = panel.y;
+        for (text_rect, button_tool) in tool_texts.into_iter().zip(Tool::ALL) {
+            let rect = text_rect.rect();
+            bottom = rect.bottom();
+            let mut button = theme.new_button_from_tr(text_rect);
+            if button.interact().is_clicked() {
+                tool = button_tool;
+            }
+            render_button(&button);
+            if button_tool == tool {
+                draw_rect_lines(add_contour(rect, Vec2::splat(2.0)), 2.0, SUCCESS);
+            }
+        }
+
+        let mut point = vec2(panel.center().x, bottom + theme.button_margin());
+        let text = format!("Rows: {}", editor.rows());
+        let (rows, new_point) = inc_dec(theme, point, text, editor.rows(), 1);
+        point = new_point;
+        let text = format!("Columns: {}", editor.columns());
+        let (columns, new_point) = inc_dec(theme, point, text, editor.columns(), 1);
+        point = new_point + vec2(0.0, theme.cell_pad());
+        if (rows, columns) != (editor.rows(), editor.columns()) {
+            cancel_auto_clue(&mut job, &mut editor);
+            editor.resize(rows, columns);
+            (analysis, job) = (None, None);
+        }
+
+        let level = editor.level();
+        let status = format!(
+            "Clues: {}, loops in solution: {}",
+            level.constraints.rails.len(),
+            count_loops(&level.solution)
+        );
+        let status = new_text(&status, Anchor::top_center_v(point), 1.0, theme);
+        render_text(&status, &TEXT_STYLE);
+        point.y = status.rect().bottom() + theme.cell_pad();
+
+        let anchor = Anchor::top_center_v(point);
+        let labels = new_button_group_direction(anchor, theme, button_group::Direction::Right);
+        let [mut auto_clue, mut analyze] = labels.create(["AUTO CLUE", "ANALYZE"]);
+        if auto_clue.interact().is_clicked() {
+            analysis = None;
+            cancel_auto_clue(&mut job, &mut editor);
+            match editor.start_auto_clue() {
+                Ok(auto_clue) => job = Some(Job::AutoClue(auto_clue)),
+                Err(e) => tooltip = Some((e.text().to_string(), now)),
+            }
+        }
+        if analyze.interact().is_clicked() {
+            analysis = None;
+            cancel_auto_clue(&mut job, &mut editor);
+            job = Some(Job::Analysis(editor.start_analysis(&limits)));
+        }
+        job = match job.take() {
+            Some(Job::AutoClue(mut auto_clue)) => {
+                if auto_clue.step(&mut editor, &limits) {
+                    Some(Job::AutoClue(auto_clue))
+                } else {
+                    Some(Job::Analysis(editor.start_analysis(&limits)))
+                }
+            }
+            Some(Job::Analysis(mut analysis_job)) => {
+                if analysis_job.step(&editor, &limits) {
+                    Some(Job::Analysis(analysis_job))
+                } else {
+                    analysis = Some(analysis_job.into_analysis());
+                    None
+                }
+            }
+            None => None,
+        };
+        render_button(&auto_clue);
+        render_button(&analyze);
+        let result_text = if let Some(job) = &job {
+            Some(job.progress_text())
+        } else {
+            analysis.as_ref().map(|analysis| {
+                format!(
+                    "{}. Redundant: {}",
+                    analysis.summary(),
+                    analysis.redundant_clues.len() + analysis.redundant_locks.len()
+                )
+            })
+        };
+        if let Some(text) = result_text {
+            point.y += auto_clue.rect().h + theme.cell_pad();
+            let text = new_text(&text, Anchor::top_center_v(point), 1.0, theme);
+            render_text(&text, &TEXT_STYLE);
+        }
+
+        let anchor = Anchor::bottom_center_v(vec2(
+            panel.center().x,
+            panel.bottom() - theme.button_margin(),
+        ));
+        let labels = new_button_group_direction(anchor, theme, button_group::Direction::Right);
+        let [mut save_button, mut menu] = labels.create(["SAVE", "MENU"]);
+        if is_key_pressed(KeyCode::S) || save_button.interact().is_clicked() {
+            cancel_auto_clue(&mut job, &mut editor);
+            let message = match save(editor.level()) {
+                Ok(message) => message,
+                Err(e) => format!("Could not save the level: {}", e),
+            };
+            tooltip = Some((message, now));
+        }
+        if menu.interact().is_clicked() {
+            return Ok(Transition::Pop);
+        }
+        render_button(&save_button);
+        render_button(&menu);
+
+        if let Some((text, since)) = &tooltip {
+            if since + TOOLTIP_DELAY < now {
+                tooltip = None;
+            } else {
+                let text_rect = new_text(text, Anchor::bottom_left_v(pos), 1.0, theme);
+                render_tooltip(&text_rect, &TEXT_STYLE);
+            }
+        }
+        next_frame().await
+    }
+}
+
+/// An unfinished auto clue would leave every block locked and every clue placed, so it is
+/// undone before anything else touches the level.
+fn cancel_auto_clue(job: &mut Option<Job>, editor: &mut LevelEditor) {
+    if let Some(Job::AutoClue(auto_clue)) = job.take() {
+        auto_clue.cancel(editor);
+    }
+}
+
+/// Returns the rail (row, column, direction) closest to a fractional cell coordinate.
+fn closest_rail(coord: Coord) -> (i32, i32, Direction) {
+    let (row, column) = (coord.row(), coord.column());
+    let row_fract = coord.row_f() - row as f32;
+    let column_fract = coord.column_f() - column as f32;
+    let candidates = [
+        (row_fract, row, column, Direction::Horizontal),
+        (1.0 - row_fract, row + 1, column, Direction::Horizontal),
+        (column_fract, row, column, Direction::Vertical),
+        (1.0 - column_fract, row, column + 1, Direction::Vertical),
+    ];
+    let (_, row, column, direction) = candidates
+        .into_iter()
+        .min_by(|a, b| a.0.total_cmp(&b.0))
+        .unwrap();
+    (row, column, direction)
+}
+
+fn render_pre_enabled(editor: &LevelEditor, theme: &Theme) {
+    let initial = &editor.level().initial_grid;
+    for row in 0..editor.rows() {
+        for column in 0..editor.columns() {
+            let is_root = initial.root.x == column && initial.root.y == row;
+            if initial.cells[row as usize][column as usize]
+                && !is_root
+                && !editor.is_locked(row, column)
+            {
+                let size = vec2(theme.cell_width(), theme.cell_height());
+                let center = cell_top_left(row, column, &theme.layout) + size * 0.5;
+                draw_circle_lines(center.x, center.y, size.min_element() * 0.25, 2.0, TRIANGLE);
+            }
+        }
+    }
+}
+
+fn render_hovered_rail(row: i32, column: i32, direction: Direction, theme: &Theme) {
+    let (start, end) = rail_ends(row, column, direction, theme);
+    draw_line_thickness(start, end, theme.cell_pad(), TRIANGLE);
+}
+
+fn rail_ends(row: i32, column: i32, direction: Direction, theme: &Theme) -> (Vec2, Vec2) {
+    let half_pad = theme.cell_pad() * 0.5;
+    let top_left = cell_top_left(row, column, &theme.layout);
+    match direction {
+        Direction::Horizontal => {
+            let start = top_left - vec2(0.0, half_pad);
+            (start, start + vec2(theme.cell_width(), 0.0))
+        }
+        Direction::Vertical => {
+            let start = top_left - vec2(half_pad, 0.0);
+            (start, start + vec2(0.0, theme.cell_height()))
+        }
+    }
+}
+
+fn render_analysis(analysis: &Analysis, editor: &LevelEditor, theme: &Theme) {
+    let cell_rect = |row: i32, column: i32| {
+        let top_left = cell_top_left(row, column, &theme.layout);
+        Rect::new(
+            top_left.x,
+            top_left.y,
+            theme.cell_width(),
+            theme.cell_height(),
+        )
+    };
+    for &(row, column) in &analysis.ambiguous_cells {
+        draw_rect(cell_rect(row, column), FAILING_TRANSPARENT);
+    }
+    for &(row, column) in &analysis.redundant_locks {
+        let rect = add_contour(cell_rect(row, column), -Vec2::splat(theme.cell_pad()));
+        draw_rect_lines(rect, 2.0, FAILING);
+    }
+    let clues = &editor.level().constraints.rails;
+    for clue in analysis
+        .redundant_clues
+        .iter()
+        .filter_map(|i| clues.get(*i))
+    {
+        let (row, column) = clue.row_column();
+        let (start, end) = rail_ends(row, column, clue.direction(), theme);
+        let center = (start + end) * 0.5;
+        let radius = theme.cell_width().min(theme.cell_height()) * 0.3;
+        draw_circle_lines(center.x, center.y, radius, 2.0, FAILING);
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn load(theme: &Theme) -> LevelEditor {
+    if let Ok(text) = std::fs::read_to_string(EDITOR_LEVEL_PATH) {
+        match Level::from_str(&text) {
+            Ok(level) => return LevelEditor::from_level(level),
+            Err(e) => eprintln!("ignoring {}: {}", EDITOR_LEVEL_PATH, e),
+        }
+    }
+    LevelEditor::new(theme.preferred_rows(), theme.preferred_columns())
+}
+#[cfg(target_arch = "wasm32")]
+fn load(theme: &Theme) -> LevelEditor {
+    LevelEditor::new(theme.preferred_rows(), theme.preferred_columns())
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn save(level: &Level) -> Result<String, AnyError> {
+    let text = level.to_string();
+    std::fs::write(EDITOR_LEVEL_PATH, text)?;
+    Ok(format!("Saved to {}", EDITOR_LEVEL_PATH))
+}
+#[cfg(target_arch = "wasm32")]
+fn save(level: &Level) -> Result<String, AnyError> {
+    println!("{}", level);
+    Ok("Printed the level to the console".to_string())
+}
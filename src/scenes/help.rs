@@ -0,0 +1,61 @@
+use crate::scene_stack::{draw_backdrop, Transition};
+use crate::theme::{new_button, new_text, render_button, render_text, Theme};
+use crate::{AnyError, PANEL_BACKGROUND, TEXT_STYLE};
+use juquad::draw::draw_rect;
+use juquad::widgets::anchor::{Anchor, Horizontal};
+use juquad::widgets::Widget;
+use macroquad::input::{is_key_pressed, KeyCode};
+use macroquad::math::Rect;
+use macroquad::prelude::{next_frame, screen_height, screen_width, vec2, Texture2D};
+
+pub const HOW_TO_PLAY: &[&str] = &[
+    "Click the blocks to turn them on or off, or drag to paint several at once.",
+    "Rails run along the edges between active and inactive blocks.",
+    "Solve the level by reaching the goals in the panel:",
+    "- Stations: the loop must go past them in the direction they point.",
+    "- Bridges: there must be no rail where they are.",
+    "- Active blocks: the number of blocks that must be on.",
+    "- Reachable rails: all the rails must be part of a single loop. Extra loops get other colours.",
+    "Hover a clue to see why it fails, or a goal to make the failing clues pulse.",
+    "Shift click a block to mark it as inside or outside the loop without changing it.",
+    "Right click a block to lock it, or drag between blocks or",
+    "corners to mark your own bridges and stations.",
+    "HINT shows a block that the clues and the locked blocks force.",
+    "CHECK crosses out the blocks you set that are wrong in every solution.",
+    "TRY keeps your board safe while you guess: KEEP or DISCARD what you tried after.",
+    "STEP BY STEP solves the level one deduction at a time, with the arrow keys.",
+    "GIVE UP shows the solution closest to your board. Press S for the next one.",
+    "Press Escape to pause.",
+];
+
+/// Overlay that explains the rules, on top of `backdrop`.
+pub async fn help(theme: &mut Theme, backdrop: Option<Texture2D>) -> Result<Transition, AnyError> {
+    loop {
+        let screen = vec2(screen_width(), screen_height());
+        let panel = Rect::new(
+            theme.grid_pad(),
+            theme.grid_pad(),
+            screen.x - 2.0 * theme.grid_pad(),
+            screen.y - 2.0 * theme.grid_pad(),
+        );
+        let anchor_point = vec2(panel.center().x, panel.bottom() - theme.button_margin());
+        let mut back = new_button("BACK", Anchor::bottom_center_v(anchor_point), theme);
+        if is_key_pressed(KeyCode::Escape) || back.interact().is_clicked() {
+            return Ok(Transition::Pop);
+        }
+
+        draw_backdrop(backdrop);
+        draw_rect(panel, PANEL_BACKGROUND);
+        let mut anchor = Anchor::top_left(
+            panel.x + theme.button_margin(),
+            panel.y + theme.button_margin(),
+        );
+        for line in HOW_TO_PLAY {
+            let text = new_text(line, anchor, 1.0, theme);
+            render_text(&text, &TEXT_STYLE);
+            anchor = Anchor::below(text.rect(), Horizontal::Left, theme.cell_pad());
+        }
+        render_button(&back);
+        next_frame().await
+    }
+}
@@ -1,12 +1,15 @@
+use crate::canvas::MacroquadCanvas;
 use crate::level_history::{GameTrack, Solved};
 use crate::logic::pixel_grid::Coord;
+use crate::math::IVec2;
 use crate::render::{cell_top_left, render_rail, RenderRail};
+use crate::scene_stack::{Stage, Transition};
 use crate::scenes::play::default_pixel_to_coord;
 use crate::scenes::play::panel::{get_icon_rect, render_tick_or_cross};
 use crate::theme::{new_imm_button, new_text, render_text, render_tooltip, Theme};
 use crate::{
-    new_layout, AnyError, NextStage, BACKGROUND, DISABLED_CELL, ENABLED_CELL, HOVERED_CELL,
-    PANEL_BACKGROUND, RAIL, TEXT_STYLE, UNREACHABLE_RAIL,
+    new_layout, AnyError, BACKGROUND, DISABLED_CELL, ENABLED_CELL, HOVERED_CELL, PANEL_BACKGROUND,
+    RAIL, TEXT_STYLE, UNREACHABLE_RAIL,
 };
 use juquad::draw::draw_rect;
 use juquad::widgets::anchor::{Anchor, Horizontal};
@@ -14,7 +17,7 @@ use juquad::widgets::Widget;
 use macroquad::input::{
     is_key_pressed, is_mouse_button_pressed, mouse_position, KeyCode, MouseButton,
 };
-use macroquad::math::{IVec2, Rect, Vec2};
+use macroquad::math::{Rect, Vec2};
 use macroquad::prelude::{
     clear_background, draw_rectangle, next_frame, screen_height, screen_width, vec2,
 };
@@ -22,7 +25,7 @@ use macroquad::prelude::{
 pub async fn level_selector(
     theme: &mut Theme,
     game_track: &mut GameTrack,
-) -> Result<NextStage, AnyError> {
+) -> Result<Transition, AnyError> {
     let mut screen = vec2(screen_width(), screen_height());
     let button_margin_v = Vec2::splat(theme.button_margin());
     let longest_section = theme
@@ -86,14 +89,14 @@ pub async fn level_selector(
 
                 let anchor = Anchor::below(title.rect(), Horizontal::Center, theme.button_margin());
                 if new_imm_button("PLAY", anchor, theme).1.is_clicked() {
-                    return Ok(NextStage::Campaign);
+                    return Ok(Transition::Push(Stage::Campaign));
                 }
                 render_rails_on_selected_level(coord, solved, theme);
             }
         }
         let anchor = Anchor::from_bottom(panel, Horizontal::Center, button_margin_v);
         if is_key_pressed(KeyCode::Escape) || new_imm_button("MENU", anchor, theme).1.is_clicked() {
-            return Ok(NextStage::MainMenu);
+            return Ok(Transition::Pop);
         }
 
         next_frame().await;
@@ -106,18 +109,21 @@ fn render_rails_on_selected_level(coord: Coord, is_solved: bool, theme: &Theme)
     let points = [(0, 0), (1, 0), (1, 1), (0, 1), (0, 0)];
     for i in 1..points.len() {
         let level_solved = is_solved;
+        let color = if level_solved { RAIL } else { UNREACHABLE_RAIL };
         render_rail(
+            &mut MacroquadCanvas,
             RenderRail::Some {
                 reachable: level_solved,
+                color,
                 start: IVec2::from(points[i - 1]),
                 end: IVec2::from(points[i]),
                 coord: IVec2::new(i_column, i_row),
             },
-            theme,
+            &theme.layout,
         );
-        let color = if level_solved { RAIL } else { UNREACHABLE_RAIL };
 
-        let bottom_right = cell_top_left(i_row + points[i].1, i_column + points[i].0, theme);
+        let bottom_right =
+            cell_top_left(i_row + points[i].1, i_column + points[i].0, &theme.layout);
         let top_left = bottom_right - theme.cell_pad();
         let intersection_rect =
             Rect::new(top_left.x, top_left.y, theme.cell_pad(), theme.cell_pad());
@@ -152,7 +158,7 @@ fn render_solved_at(
             DISABLED_CELL
         }
     };
-    let cell_pos = cell_top_left(i_row, i_column, theme);
+    let cell_pos = cell_top_left(i_row, i_column, &theme.layout);
     draw_rectangle(
         cell_pos.x,
         cell_pos.y,
@@ -1,5 +1,6 @@
+use crate::scene_stack::{Stage, Transition};
 use crate::theme::{labels_from_theme, new_button_group_generic, render_button, Theme};
-use crate::{new_layout, AnyError, NextStage, BACKGROUND, PANEL_BACKGROUND};
+use crate::{new_layout, AnyError, BACKGROUND, PANEL_BACKGROUND};
 use juquad::draw::draw_rect;
 use juquad::widgets::anchor::Anchor;
 use juquad::widgets::button::Button;
@@ -11,25 +12,27 @@ use macroquad::prelude::{clear_background, next_frame, screen_height, screen_wid
 pub struct Buttons {
     campaign: Button,
     options: Button,
+    editor: Button,
     quit: Button,
 }
 impl Buttons {
     pub fn list(&self) -> Vec<&Button> {
-        vec![&self.campaign, &self.options, &self.quit]
+        vec![&self.campaign, &self.options, &self.editor, &self.quit]
     }
 }
-impl From<[Button; 3]> for Buttons {
-    fn from(value: [Button; 3]) -> Self {
-        let [campaign, options, quit] = value;
+impl From<[Button; 4]> for Buttons {
+    fn from(value: [Button; 4]) -> Self {
+        let [campaign, options, editor, quit] = value;
         Self {
             campaign,
             options,
+            editor,
             quit,
         }
     }
 }
 
-pub async fn main_menu(theme: &mut Theme) -> Result<NextStage, AnyError> {
+pub async fn main_menu(theme: &mut Theme) -> Result<Transition, AnyError> {
     let mut screen = vec2(screen_width(), screen_height());
     loop {
         let new_screen = vec2(screen_width(), screen_height());
@@ -55,16 +58,21 @@ pub async fn main_menu(theme: &mut Theme) -> Result<NextStage, AnyError> {
                 ..labels_from_theme(theme)
             },
         );
-        let mut buttons: Buttons = labels.create(["CAMPAIGN", "OPTIONS", "QUIT"]).into();
+        let mut buttons: Buttons = labels
+            .create(["CAMPAIGN", "OPTIONS", "EDITOR", "QUIT"])
+            .into();
 
         if buttons.campaign.interact().is_clicked() {
-            return Ok(NextStage::LevelSelector);
+            return Ok(Transition::Push(Stage::LevelSelector));
         }
         if buttons.options.interact().is_clicked() {
-            return Ok(NextStage::Options);
+            return Ok(Transition::Push(Stage::Options));
+        }
+        if buttons.editor.interact().is_clicked() {
+            return Ok(Transition::Push(Stage::Editor));
         }
         if is_key_pressed(KeyCode::Escape) || buttons.quit.interact().is_clicked() {
-            return Ok(NextStage::Quit);
+            return Ok(Transition::Quit);
         }
 
         for b in buttons.list() {
@@ -1,6 +1,7 @@
+use crate::scene_stack::{draw_backdrop, Transition};
 use crate::slider::Slider;
 use crate::theme::{new_button, new_button_group_direction, render_button, render_text, Theme};
-use crate::{new_layout, AnyError, NextStage, BACKGROUND, PANEL_BACKGROUND, STYLE, TEXT_STYLE};
+use crate::{new_layout, AnyError, PANEL_BACKGROUND, STYLE, TEXT_STYLE};
 use juquad::draw::draw_rect;
 use juquad::widgets::anchor::Anchor;
 use juquad::widgets::button_group;
@@ -8,10 +9,14 @@ use juquad::widgets::text::TextRect;
 use juquad::widgets::Widget;
 use macroquad::input::{is_key_pressed, KeyCode};
 use macroquad::math::Rect;
-use macroquad::prelude::{clear_background, next_frame, screen_height, screen_width, vec2, Vec2};
+use macroquad::prelude::{next_frame, screen_height, screen_width, vec2, Texture2D, Vec2};
 use std::ops::{Add, Sub};
 
-pub async fn options(theme: &mut Theme) -> Result<NextStage, AnyError> {
+/// `backdrop` is the scene below, when options are opened as an overlay.
+pub async fn options(
+    theme: &mut Theme,
+    backdrop: Option<Texture2D>,
+) -> Result<Transition, AnyError> {
     let mut screen = vec2(screen_width(), screen_height());
     let (mut title, mut volume) = change_volume(theme, vec2(0.0, 0.0));
     loop {
@@ -28,7 +33,7 @@ pub async fn options(theme: &mut Theme) -> Result<NextStage, AnyError> {
             screen.y - 2.0 * theme.grid_pad(),
         );
 
-        clear_background(BACKGROUND);
+        draw_backdrop(backdrop);
         draw_rect(panel, PANEL_BACKGROUND);
 
         let mut point = vec2(panel.center().x, panel.y + theme.button_margin());
@@ -40,9 +45,9 @@ pub async fn options(theme: &mut Theme) -> Result<NextStage, AnyError> {
         point += vec2(0.0, theme.button_margin());
 
         let anchor_point = vec2(panel.center().x, panel.bottom() - theme.button_margin());
-        let mut back = new_button("MENU", Anchor::bottom_center_v(anchor_point), theme);
+        let mut back = new_button("BACK", Anchor::bottom_center_v(anchor_point), theme);
         if is_key_pressed(KeyCode::Escape) || back.interact().is_clicked() {
-            return Ok(NextStage::MainMenu);
+            return Ok(Transition::Pop);
         }
         render_button(&back);
         next_frame().await
@@ -122,7 +127,7 @@ fn interact_volume(
     anchor_point + vec2(0.0, slider.rect().h)
 }
 
-fn inc_dec<T: Add<Output = T> + Sub<Output = T>>(
+pub fn inc_dec<T: Add<Output = T> + Sub<Output = T>>(
     theme: &Theme,
     anchor_point: Vec2,
     text: String,
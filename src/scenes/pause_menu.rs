@@ -0,0 +1,109 @@
+use crate::level_history::GameTrack;
+use crate::scene_stack::{draw_backdrop, Stage, Transition};
+use crate::theme::{labels_from_theme, new_button_group_generic, render_button, Theme};
+use crate::{AnyError, PANEL_BACKGROUND};
+use juquad::draw::draw_rect;
+use juquad::widgets::anchor::Anchor;
+use juquad::widgets::button::Button;
+use juquad::widgets::button_group::LabelGroup;
+use macroquad::input::{is_key_pressed, KeyCode};
+use macroquad::prelude::{next_frame, screen_height, screen_width, vec2, Texture2D};
+
+pub struct Buttons {
+    resume: Button,
+    restart: Button,
+    options: Button,
+    help: Button,
+    level_selector: Button,
+    quit: Button,
+}
+impl Buttons {
+    pub fn list(&self) -> Vec<&Button> {
+        vec![
+            &self.resume,
+            &self.restart,
+            &self.options,
+            &self.help,
+            &self.level_selector,
+            &self.quit,
+        ]
+    }
+}
+impl From<[Button; 6]> for Buttons {
+    fn from(value: [Button; 6]) -> Self {
+        let [resume, restart, options, help, level_selector, quit] = value;
+        Self {
+            resume,
+            restart,
+            options,
+            help,
+            level_selector,
+            quit,
+        }
+    }
+}
+
+/// Overlay on top of the play scene, which is shown dimmed in `backdrop`.
+pub async fn pause_menu(
+    theme: &mut Theme,
+    game_track: &mut GameTrack,
+    backdrop: Option<Texture2D>,
+) -> Result<Transition, AnyError> {
+    loop {
+        let center = vec2(screen_width(), screen_height()) * 0.5;
+        let labels = new_button_group_generic(
+            theme,
+            LabelGroup {
+                anchor: Anchor::center_v(center),
+                pad_x: Some(theme.button_margin() * 4.0),
+                ..labels_from_theme(theme)
+            },
+        );
+        let mut buttons: Buttons = labels
+            .create([
+                "RESUME",
+                "RESTART",
+                "OPTIONS",
+                "HOW TO PLAY",
+                "LEVEL SELECTOR",
+                "QUIT",
+            ])
+            .into();
+
+        if is_key_pressed(KeyCode::Escape) || buttons.resume.interact().is_clicked() {
+            return Ok(Transition::Pop);
+        }
+        if buttons.restart.interact().is_clicked() {
+            game_track.restart();
+            return Ok(Transition::Pop);
+        }
+        if buttons.options.interact().is_clicked() {
+            return Ok(Transition::Overlay(Stage::Options));
+        }
+        if buttons.help.interact().is_clicked() {
+            return Ok(Transition::Overlay(Stage::Help));
+        }
+        if buttons.level_selector.interact().is_clicked() {
+            return Ok(Transition::PopTo(Stage::LevelSelector));
+        }
+        if buttons.quit.interact().is_clicked() {
+            return Ok(Transition::Quit);
+        }
+
+        draw_backdrop(backdrop);
+        let mut panel = buttons.resume.rect();
+        for b in buttons.list() {
+            panel = panel.combine_with(b.rect());
+        }
+        let margin = theme.button_margin();
+        panel.x -= margin;
+        panel.y -= margin;
+        panel.w += 2.0 * margin;
+        panel.h += 2.0 * margin;
+        draw_rect(panel, PANEL_BACKGROUND);
+        for b in buttons.list() {
+            render_button(b);
+        }
+        next_frame().await
+    }
+}
@@ -0,0 +1,767 @@
+//! The rules of the play scene, driven by abstract inputs instead of mouse and keyboard polling,
+//! so that whole levels can be played without a window.
+
+use crate::direction::{Direction, Sense};
+use crate::level_history::GameTrack;
+use crate::logic::constraints::{compute_satisfaction, find_loops, Constraints, Satisfaction};
+use crate::logic::deduction::{hint, Deduction, Walkthrough};
+use crate::logic::grid::{
+    get_cell, get_cell_coord_mut, get_coord, get_coord_mut, is_system_fixed_v, Grid, UserFix,
+};
+use crate::logic::pixel_grid::Coord;
+use crate::logic::solver::{closest_solutions, wrong_in_every_solution};
+use crate::scenes::play::Tooltips;
+use crate::DEFAULT_SHOW_SOLUTION;
+
+/// A rail between two cells. A horizontal rail is the top edge of the cell at `row`, `column`,
+/// and a vertical rail is its left edge.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Rail {
+    pub direction: Direction,
+    pub row: i32,
+    pub column: i32,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum PlayInput {
+    /// Also starts a stroke that `Paint` continues, until any other input arrives.
+    ToggleCell(Coord),
+    /// Gives the cells the value that the `ToggleCell` starting the stroke gave to its cell.
+    /// Locked cells are skipped.
+    Paint(Vec<Coord>),
+    /// Locks or unlocks the cell so that it can't be toggled by mistake.
+    ToggleLock(Coord),
+    /// Changes the pencil mark of the cell, which doesn't change the cell itself.
+    CycleNote(Coord),
+    ToggleBlockade(Rail),
+    ToggleStation(Rail, Sense),
+    Restart,
+    Next,
+    ShowSolution,
+    /// While the solution is shown, shows the next one, further from the player's board.
+    NextSolution,
+    /// Enters or leaves the step by step mode, which solves the level one deduction at a time.
+    StepByStep,
+    StepForward,
+    StepBack,
+    /// Starts a branch on top of the board, to try something that can be thrown away later.
+    Try,
+    /// Keeps the changes of the innermost branch.
+    KeepTry,
+    /// Undoes the changes of the innermost branch.
+    DiscardTry,
+    /// Points at a block whose value is forced by the clues and the locked blocks.
+    Hint,
+    /// Marks the blocks that are wrong in every solution.
+    Check,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum PlayEffect {
+    /// The player's board changed and has to be rendered again.
+    BoardChanged,
+    Tooltip(Tooltips),
+    /// The level was solved for the first time since it was loaded or restarted.
+    Solved,
+    SolutionToggled,
+    /// The step by step mode was entered or left, or moved to another step.
+    StepChanged,
+    /// The pencil marks changed. The board and its satisfaction didn't.
+    NotesChanged,
+    /// A branch started or ended.
+    BranchChanged,
+    /// `PlayController::hint` has a new hint, and `GameTrack` counted it.
+    HintShown,
+    /// `PlayController::wrong_cells` has new marks.
+    Checked,
+    /// The level started again from its initial board.
+    Restarted,
+    /// The caller has to load the next level into the `GameTrack`, and then call
+    /// `PlayController::level_loaded`. Loading may need to generate a procedural level.
+    LoadNextLevel,
+}
+
+/// How the board would look if the cell at `coord` were toggled.
+pub struct Preview {
+    pub coord: Coord,
+    pub grid: Grid,
+    pub satisfaction: Satisfaction,
+    pub extra_loops: usize,
+}
+
+/// What the play scene shows besides the board. It lives in the `GameTrack`, so that it's
+/// still there when the play scene comes back from the pause menu, and it starts over with
+/// every level.
+#[derive(Debug)]
+pub struct PlaySession {
+    show_solution: bool,
+    /// The solutions of the level, closest to the player's board first, while they are shown.
+    solutions: Vec<Grid>,
+    solution_index: usize,
+    walkthrough: Option<Walkthrough>,
+    solved_reported: bool,
+    hint: Option<Deduction>,
+    wrong_cells: Vec<(i32, i32)>,
+}
+
+impl Default for PlaySession {
+    fn default() -> Self {
+        Self {
+            show_solution: DEFAULT_SHOW_SOLUTION,
+            solutions: Vec::new(),
+            solution_index: 0,
+            walkthrough: None,
+            solved_reported: false,
+            hint: None,
+            wrong_cells: Vec::new(),
+        }
+    }
+}
+
+pub struct PlayController<'a> {
+    game_track: &'a mut GameTrack,
+    /// The value that the current stroke paints.
+    stroke: Option<bool>,
+    satisfaction: Satisfaction,
+}
+
+impl<'a> PlayController<'a> {
+    pub fn new(game_track: &'a mut GameTrack) -> Self {
+        let satisfaction = compute_satisfaction(
+            &game_track.in_progress,
+            &game_track.get_current().constraints,
+        );
+        Self {
+            game_track,
+            stroke: None,
+            satisfaction,
+        }
+    }
+
+    pub fn apply(&mut self, input: PlayInput) -> Vec<PlayEffect> {
+        let mut effects = Vec::new();
+        let stroke = self.stroke.take();
+        match input {
+            PlayInput::Paint(coords) => {
+                if let Some(value) = stroke {
+                    self.stroke = stroke;
+                    self.paint(&coords, value, &mut effects);
+                }
+            }
+            PlayInput::Restart => {
+                self.game_track.restart();
+                self.level_loaded();
+                effects.push(PlayEffect::Restarted);
+            }
+            PlayInput::Next => effects.push(PlayEffect::LoadNextLevel),
+            PlayInput::ShowSolution => {
+                self.game_track.session.show_solution = !self.game_track.session.show_solution;
+                self.game_track.session.walkthrough = None;
+                self.game_track.session.solution_index = 0;
+                self.game_track.session.solutions = if self.game_track.session.show_solution {
+                    let level = self.game_track.get_current();
+                    closest_solutions(
+                        &level.initial_grid,
+                        &level.constraints,
+                        &level.solution,
+                        self.in_progress(),
+                    )
+                } else {
+                    Vec::new()
+                };
+                effects.push(PlayEffect::SolutionToggled);
+            }
+            PlayInput::NextSolution => {
+                if self.game_track.session.solutions.len() > 1 {
+                    self.game_track.session.solution_index =
+                        (self.game_track.session.solution_index + 1)
+                            % self.game_track.session.solutions.len();
+                    effects.push(PlayEffect::SolutionToggled);
+                }
+            }
+            PlayInput::StepByStep => {
+                self.game_track.session.walkthrough =
+                    if self.game_track.session.walkthrough.is_none() {
+                        let level = self.game_track.get_current();
+                        Some(Walkthrough::new(&level.initial_grid, &level.constraints))
+                    } else {
+                        None
+                    };
+                self.game_track.session.show_solution = false;
+                self.game_track.session.solutions.clear();
+                effects.push(PlayEffect::StepChanged);
+            }
+            PlayInput::StepForward | PlayInput::StepBack => {
+                if let Some(walkthrough) = self.game_track.session.walkthrough.as_mut() {
+                    let moved = if input == PlayInput::StepForward {
+                        walkthrough.forward()
+                    } else {
+                        walkthrough.back()
+                    };
+                    if moved {
+                        effects.push(PlayEffect::StepChanged);
+                    }
+                }
+            }
+            _ if self.game_track.session.walkthrough.is_some() => {
+                effects.push(PlayEffect::Tooltip(Tooltips::StepByStep));
+            }
+            _ if self.game_track.session.show_solution => {
+                effects.push(PlayEffect::Tooltip(Tooltips::EditSolution));
+            }
+            PlayInput::Hint => {
+                if self.game_track.session.hint.is_none() && !self.satisfaction.success() {
+                    self.game_track.session.hint = hint(self.in_progress(), self.constraints());
+                    if self.game_track.session.hint.is_some() {
+                        self.game_track.use_hint();
+                        effects.push(PlayEffect::HintShown);
+                    } else {
+                        effects.push(PlayEffect::Tooltip(Tooltips::NoHint));
+                    }
+                }
+            }
+            PlayInput::Check => {
+                let initial_grid = &self.game_track.get_current().initial_grid;
+                let wrong =
+                    wrong_in_every_solution(initial_grid, self.constraints(), self.in_progress());
+                match wrong {
+                    None => effects.push(PlayEffect::Tooltip(Tooltips::TooManySolutions)),
+                    Some(wrong) if wrong.is_empty() => {
+                        effects.push(PlayEffect::Tooltip(Tooltips::NothingWrong))
+                    }
+                    Some(wrong) => {
+                        self.game_track.session.wrong_cells = wrong;
+                        effects.push(PlayEffect::Checked);
+                    }
+                }
+            }
+            PlayInput::Try => {
+                self.game_track.start_branch();
+                effects.push(PlayEffect::BranchChanged);
+            }
+            PlayInput::KeepTry => {
+                if self.game_track.commit_branch() {
+                    effects.push(PlayEffect::BranchChanged);
+                }
+            }
+            PlayInput::DiscardTry => {
+                if self.game_track.discard_branch() {
+                    self.game_track.session.wrong_cells.clear();
+                    effects.push(PlayEffect::BranchChanged);
+                    self.board_changed(&mut effects);
+                }
+            }
+            PlayInput::ToggleCell(coord) => {
+                if self.is_fixed(coord) {
+                    effects.push(PlayEffect::Tooltip(Tooltips::FixedCell));
+                } else if *get_coord(&self.in_progress().fixed_cells, coord) {
+                    effects.push(PlayEffect::Tooltip(Tooltips::UserFixedCell));
+                } else {
+                    let value = !*get_coord(&self.in_progress().cells, coord);
+                    self.stroke = Some(value);
+                    self.paint(&[coord], value, &mut effects);
+                }
+            }
+            PlayInput::CycleNote(coord) => {
+                if self.is_fixed(coord) {
+                    effects.push(PlayEffect::Tooltip(Tooltips::FixedCell));
+                } else {
+                    let note = get_coord_mut(&mut self.game_track.in_progress.notes, coord);
+                    *note = note.next();
+                    effects.push(PlayEffect::NotesChanged);
+                }
+            }
+            PlayInput::ToggleLock(coord) => {
+                if self.is_fixed(coord) {
+                    effects.push(PlayEffect::Tooltip(Tooltips::FixedCell));
+                } else {
+                    let fixed = get_coord_mut(&mut self.game_track.in_progress.fixed_cells, coord);
+                    *fixed = !*fixed;
+                    self.board_changed(&mut effects);
+                }
+            }
+            PlayInput::ToggleBlockade(rail) => {
+                let fix = self.user_fix_mut(rail);
+                fix.blockade = !fix.blockade;
+                self.board_changed(&mut effects);
+            }
+            PlayInput::ToggleStation(rail, sense) => {
+                let fix = self.user_fix_mut(rail);
+                match sense {
+                    Sense::Forwards => fix.station_forward = !fix.station_forward,
+                    Sense::Backwards => fix.station_backwards = !fix.station_backwards,
+                }
+                self.board_changed(&mut effects);
+            }
+        }
+        effects
+    }
+
+    /// Starts the current level of the `GameTrack` from scratch, after it changed.
+    pub fn level_loaded(&mut self) {
+        self.satisfaction = compute_satisfaction(self.in_progress(), self.constraints());
+    }
+
+    /// None if the cell can't be toggled now.
+    pub fn preview(&self, coord: Coord) -> Option<Preview> {
+        if self.is_locked(coord) || !self.shows_board() {
+            return None;
+        }
+        let mut grid = self.in_progress().clone();
+        let cell = get_cell_coord_mut(&mut grid, coord);
+        *cell = !*cell;
+        grid.recalculate_rails();
+        let satisfaction = compute_satisfaction(&grid, self.constraints());
+        let extra_loops = find_loops(&grid).extra();
+        Some(Preview {
+            coord,
+            grid,
+            satisfaction,
+            extra_loops,
+        })
+    }
+
+    /// Whether the level decides the block, so the player can't unlock it or change it.
+    fn is_fixed(&self, coord: Coord) -> bool {
+        let level_grid = &self.game_track.get_current().initial_grid;
+        is_system_fixed_v(coord.into(), level_grid) || *get_coord(&level_grid.fixed_cells, coord)
+    }
+
+    fn is_locked(&self, coord: Coord) -> bool {
+        self.is_fixed(coord) || *get_coord(&self.in_progress().fixed_cells, coord)
+    }
+
+    /// Recalculates the rails once, after setting all the cells that aren't locked.
+    fn paint(&mut self, coords: &[Coord], value: bool, effects: &mut Vec<PlayEffect>) {
+        let mut changed = false;
+        for &coord in coords {
+            let locked = self.is_locked(coord);
+            let cell = get_cell_coord_mut(&mut self.game_track.in_progress, coord);
+            if !locked && *cell != value {
+                *cell = value;
+                changed = true;
+                let painted = (coord.row(), coord.column());
+                self.game_track
+                    .session
+                    .wrong_cells
+                    .retain(|wrong| *wrong != painted);
+            }
+        }
+        if changed {
+            self.game_track.in_progress.recalculate_rails();
+            self.board_changed(effects);
+        }
+    }
+
+    fn board_changed(&mut self, effects: &mut Vec<PlayEffect>) {
+        effects.push(PlayEffect::BoardChanged);
+        self.game_track.session.hint = None;
+        self.satisfaction = compute_satisfaction(self.in_progress(), self.constraints());
+        if self.satisfaction.success() {
+            self.game_track.solved();
+            if !self.game_track.session.solved_reported {
+                self.game_track.session.solved_reported = true;
+                effects.push(PlayEffect::Solved);
+            }
+        }
+    }
+
+    fn user_fix_mut(&mut self, rail: Rail) -> &mut UserFix {
+        let rails = &mut self.game_track.in_progress.fixed_rails;
+        match rail.direction {
+            Direction::Horizontal => rails.get_horiz_mut(rail.row, rail.column),
+            Direction::Vertical => rails.get_vert_mut(rail.row, rail.column),
+        }
+    }
+
+    pub fn game_track(&self) -> &GameTrack {
+        self.game_track
+    }
+    pub fn game_track_mut(&mut self) -> &mut GameTrack {
+        self.game_track
+    }
+    pub fn show_solution(&self) -> bool {
+        self.game_track.session.show_solution
+    }
+    /// The position of the shown solution among all of them, and how many there are.
+    pub fn shown_solution(&self) -> Option<(usize, usize)> {
+        (self.game_track.session.show_solution && !self.game_track.session.solutions.is_empty())
+            .then_some((
+                self.game_track.session.solution_index,
+                self.game_track.session.solutions.len(),
+            ))
+    }
+    /// Whether the player's board is shown, instead of a solution or the step by step mode.
+    pub fn shows_board(&self) -> bool {
+        !self.game_track.session.show_solution && self.game_track.session.walkthrough.is_none()
+    }
+    pub fn walkthrough(&self) -> Option<&Walkthrough> {
+        self.game_track.session.walkthrough.as_ref()
+    }
+    /// How many branches are open.
+    pub fn branch_depth(&self) -> usize {
+        self.game_track.branches.len()
+    }
+    /// Blocks changed inside branches, with the index of the branch that gave them their value.
+    pub fn branch_cells(&self) -> Vec<(i32, i32, usize)> {
+        let board = self.in_progress();
+        let mut cells = Vec::new();
+        for row in 0..board.rows() {
+            for column in 0..board.columns() {
+                let value = *get_cell(board, row, column);
+                let branches = &self.game_track.branches;
+                let changed_in = branches
+                    .iter()
+                    .rposition(|base| *get_cell(base, row, column) != value);
+                if let Some(branch) = changed_in {
+                    cells.push((row, column, branch));
+                }
+            }
+        }
+        cells
+    }
+    /// Blocks that the last check found wrong, and that weren't toggled since.
+    pub fn wrong_cells(&self) -> &[(i32, i32)] {
+        &self.game_track.session.wrong_cells
+    }
+    pub fn hint(&self) -> Option<&Deduction> {
+        self.game_track.session.hint.as_ref()
+    }
+    pub fn satisfaction(&self) -> &Satisfaction {
+        &self.satisfaction
+    }
+    /// Loops of the board that don't go through the root.
+    pub fn extra_loops(&self) -> usize {
+        find_loops(self.in_progress()).extra()
+    }
+    pub fn solution(&self) -> &Grid {
+        &self.game_track.get_current().solution
+    }
+    pub fn in_progress(&self) -> &Grid {
+        &self.game_track.in_progress
+    }
+    pub fn constraints(&self) -> &Constraints {
+        &self.game_track.get_current().constraints
+    }
+    /// The grid to draw: the steps made so far in the step by step mode, the chosen solution
+    /// while they are being shown, or else the player's board.
+    pub fn shown_grid(&self) -> &Grid {
+        if let Some(walkthrough) = &self.game_track.session.walkthrough {
+            return walkthrough.grid();
+        }
+        match self
+            .game_track
+            .session
+            .solutions
+            .get(self.game_track.session.solution_index)
+        {
+            Some(solution) if self.game_track.session.show_solution => solution,
+            _ if self.game_track.session.show_solution => self.solution(),
+            _ => self.in_progress(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::level_history::CurrentGame;
+    use crate::levels::Levels;
+    use crate::logic::grid::Note;
+    use crate::logic::solver::count_different_cells;
+
+    fn play_level(controller: &mut PlayController) -> Vec<PlayEffect> {
+        let mut effects = Vec::new();
+        let solution = controller.solution().clone();
+        for row in 0..solution.rows() {
+            for column in 0..solution.columns() {
+                if get_cell(&solution, row, column)
+                    != get_cell(controller.in_progress(), row, column)
+                {
+                    effects
+                        .extend(controller.apply(PlayInput::ToggleCell(Coord::new_i(row, column))));
+                }
+            }
+        }
+        effects
+    }
+
+    #[test]
+    fn test_play_campaign() {
+        let levels = Levels::get().unwrap();
+        let mut game_track = GameTrack::new(0, 0, &levels).unwrap();
+        let mut controller = PlayController::new(&mut game_track);
+        let mut played = 0;
+        loop {
+            let effects = play_level(&mut controller);
+            let solved = effects.iter().filter(|e| **e == PlayEffect::Solved).count();
+            assert_eq!(solved, 1, "{}", controller.game_track().level_title());
+            assert!(controller.satisfaction().success());
+            played += 1;
+
+            assert_eq!(
+                controller.apply(PlayInput::Next),
+                vec![PlayEffect::LoadNextLevel]
+            );
+            if !controller.game_track_mut().next_in_campaign(&levels) {
+                break;
+            }
+            controller.level_loaded();
+        }
+        let total: usize = levels.sections.iter().map(|s| s.levels.len()).sum();
+        assert_eq!(played, total);
+        assert!(controller.game_track().solved.iter().flatten().all(|s| *s));
+        assert!(matches!(
+            controller.game_track().current,
+            CurrentGame::Campaign { .. }
+        ));
+    }
+
+    #[test]
+    fn test_resume() {
+        let levels = Levels::get().unwrap();
+        let mut game_track = GameTrack::new(0, 0, &levels).unwrap();
+        let mut controller = PlayController::new(&mut game_track);
+        assert_eq!(
+            controller.apply(PlayInput::Hint),
+            vec![PlayEffect::HintShown]
+        );
+        controller.apply(PlayInput::StepByStep);
+        controller.apply(PlayInput::StepForward);
+
+        let mut controller = PlayController::new(&mut game_track);
+        assert!(controller.hint().is_some());
+        assert!(controller.walkthrough().unwrap().current().is_some());
+        controller.apply(PlayInput::StepByStep);
+        let effects = play_level(&mut controller);
+        assert!(effects.contains(&PlayEffect::Solved));
+
+        let mut controller = PlayController::new(&mut game_track);
+        let rail = Rail {
+            direction: Direction::Horizontal,
+            row: 1,
+            column: 1,
+        };
+        let effects = controller.apply(PlayInput::ToggleBlockade(rail));
+        assert!(controller.satisfaction().success());
+        assert_eq!(effects, vec![PlayEffect::BoardChanged]);
+
+        controller.apply(PlayInput::Restart);
+        assert!(controller.hint().is_none());
+        assert!(controller.walkthrough().is_none());
+    }
+
+    #[test]
+    fn test_branches() {
+        let levels = Levels::get().unwrap();
+        let mut game_track = GameTrack::new(0, 0, &levels).unwrap();
+        let mut controller = PlayController::new(&mut game_track);
+        let (first, second) = (Coord::new_i(1, 1), Coord::new_i(1, 2));
+        let initial = controller.in_progress().clone();
+
+        assert_eq!(
+            controller.apply(PlayInput::Try),
+            vec![PlayEffect::BranchChanged]
+        );
+        controller.apply(PlayInput::ToggleCell(first));
+        controller.apply(PlayInput::Try);
+        controller.apply(PlayInput::ToggleCell(second));
+        assert_eq!(controller.branch_depth(), 2);
+        assert_eq!(controller.branch_cells(), vec![(1, 1, 0), (1, 2, 1)]);
+
+        assert_eq!(
+            controller.apply(PlayInput::DiscardTry),
+            vec![PlayEffect::BranchChanged, PlayEffect::BoardChanged]
+        );
+        assert_eq!(controller.branch_cells(), vec![(1, 1, 0)]);
+        assert_eq!(
+            controller.apply(PlayInput::KeepTry),
+            vec![PlayEffect::BranchChanged]
+        );
+        assert_eq!(controller.branch_depth(), 0);
+        assert_eq!(controller.branch_cells(), vec![]);
+        assert_ne!(controller.in_progress().cells, initial.cells);
+        assert_eq!(controller.apply(PlayInput::KeepTry), vec![]);
+
+        controller.apply(PlayInput::Try);
+        controller.apply(PlayInput::Restart);
+        assert_eq!(controller.branch_depth(), 0);
+        assert_eq!(controller.in_progress().cells, initial.cells);
+    }
+
+    #[test]
+    fn test_paint() {
+        let levels = Levels::get().unwrap();
+        let mut game_track = GameTrack::new(0, 0, &levels).unwrap();
+        let mut controller = PlayController::new(&mut game_track);
+        let locked = Coord::new_i(2, 1);
+        controller.apply(PlayInput::ToggleLock(locked));
+        let border = Coord::new_i(0, 1);
+        let row: Vec<Coord> = (1..4).map(|column| Coord::new_i(1, column)).collect();
+        assert_eq!(
+            controller.apply(PlayInput::Paint(row.clone())),
+            vec![],
+            "painting needs a stroke"
+        );
+
+        controller.apply(PlayInput::ToggleCell(row[0]));
+        let mut painted = row.clone();
+        painted.extend([locked, border]);
+        assert_eq!(
+            controller.apply(PlayInput::Paint(painted)),
+            vec![PlayEffect::BoardChanged]
+        );
+        let board = controller.in_progress();
+        assert!(row.iter().all(|coord| *get_coord(&board.cells, *coord)));
+        assert!(!*get_coord(&board.cells, locked));
+        assert!(!*get_coord(&board.cells, border));
+
+        controller.apply(PlayInput::Hint);
+        assert_eq!(controller.apply(PlayInput::Paint(row)), vec![]);
+    }
+
+    #[test]
+    fn test_preview() {
+        let levels = Levels::get().unwrap();
+        let mut game_track = GameTrack::new(0, 0, &levels).unwrap();
+        let mut controller = PlayController::new(&mut game_track);
+        let cell = Coord::new_i(1, 1);
+        let preview = controller.preview(cell).unwrap();
+        assert!(*get_coord(&preview.grid.cells, cell));
+        assert!(!*get_coord(&controller.in_progress().cells, cell));
+
+        controller.apply(PlayInput::ToggleCell(cell));
+        assert_eq!(preview.satisfaction, *controller.satisfaction());
+        assert_eq!(preview.extra_loops, controller.extra_loops());
+        assert_eq!(controller.extra_loops(), 1);
+        assert!(controller.preview(Coord::new_i(0, 1)).is_none());
+        controller.apply(PlayInput::ToggleLock(cell));
+        assert!(controller.preview(cell).is_none());
+    }
+
+    #[test]
+    fn test_rules() {
+        let levels = Levels::get().unwrap();
+        let mut game_track = GameTrack::new(0, 0, &levels).unwrap();
+        let mut controller = PlayController::new(&mut game_track);
+        let root = controller.in_progress().root;
+        let root = Coord::new_i(root.y, root.x);
+        assert_eq!(
+            controller.apply(PlayInput::ToggleCell(root)),
+            vec![PlayEffect::Tooltip(Tooltips::FixedCell)]
+        );
+
+        let cell = Coord::new_i(1, 1);
+        assert_ne!(cell, root);
+        assert_eq!(
+            controller.apply(PlayInput::CycleNote(cell)),
+            vec![PlayEffect::NotesChanged]
+        );
+        assert_eq!(controller.in_progress().notes[1][1], Note::Inside);
+        assert!(!*get_cell(controller.in_progress(), 1, 1));
+        assert_eq!(
+            controller.apply(PlayInput::ToggleLock(cell)),
+            vec![PlayEffect::BoardChanged]
+        );
+        assert_eq!(
+            controller.apply(PlayInput::ToggleCell(cell)),
+            vec![PlayEffect::Tooltip(Tooltips::UserFixedCell)]
+        );
+
+        let rail = Rail {
+            direction: Direction::Vertical,
+            row: 1,
+            column: 1,
+        };
+        controller.apply(PlayInput::ToggleBlockade(rail));
+        controller.apply(PlayInput::ToggleStation(rail, Sense::Backwards));
+        let fix = controller.in_progress().fixed_rails.get_vert(1, 1);
+        assert!(fix.blockade && fix.station_backwards && !fix.station_forward);
+
+        controller.apply(PlayInput::ShowSolution);
+        assert!(controller.show_solution());
+        let (index, count) = controller.shown_solution().unwrap();
+        assert_eq!(index, 0);
+        let distance = |grid: &Grid| count_different_cells(grid, controller.in_progress());
+        assert!(distance(controller.shown_grid()) <= distance(controller.solution()));
+        let cycled = controller.apply(PlayInput::NextSolution);
+        assert_eq!(cycled.is_empty(), count == 1);
+        assert_eq!(controller.shown_solution(), Some((1 % count, count)));
+        for input in [
+            PlayInput::ToggleBlockade(rail),
+            PlayInput::Hint,
+            PlayInput::Check,
+        ] {
+            assert_eq!(
+                controller.apply(input),
+                vec![PlayEffect::Tooltip(Tooltips::EditSolution)]
+            );
+        }
+
+        assert_eq!(
+            controller.apply(PlayInput::StepByStep),
+            vec![PlayEffect::StepChanged]
+        );
+        assert!(!controller.show_solution());
+        assert_eq!(controller.apply(PlayInput::StepBack), vec![]);
+        assert_eq!(
+            controller.apply(PlayInput::StepForward),
+            vec![PlayEffect::StepChanged]
+        );
+        let step = controller.walkthrough().unwrap().current().unwrap().clone();
+        assert_eq!(
+            *get_cell(controller.shown_grid(), step.row, step.column),
+            step.enabled
+        );
+        assert_eq!(
+            controller.apply(PlayInput::Hint),
+            vec![PlayEffect::Tooltip(Tooltips::StepByStep)]
+        );
+
+        assert_eq!(
+            controller.apply(PlayInput::Restart),
+            vec![PlayEffect::Restarted]
+        );
+        assert!(controller.walkthrough().is_none());
+        assert!(!controller.show_solution());
+        let initial = &controller.game_track().get_current().initial_grid;
+        assert_eq!(controller.in_progress().cells, initial.cells);
+
+        assert_eq!(
+            controller.apply(PlayInput::Hint),
+            vec![PlayEffect::HintShown]
+        );
+        assert_eq!(controller.apply(PlayInput::Hint), vec![]);
+        assert_eq!(controller.game_track().hints_used(), 1);
+        let hint = controller.hint().unwrap().clone();
+        let coord = Coord::new_i(hint.row, hint.column);
+        controller.apply(PlayInput::ToggleCell(coord));
+        assert!(controller.hint().is_none());
+        assert_eq!(
+            *get_cell(controller.in_progress(), hint.row, hint.column),
+            hint.enabled
+        );
+
+        controller.apply(PlayInput::Restart);
+        assert_eq!(
+            controller.apply(PlayInput::Check),
+            vec![PlayEffect::Tooltip(Tooltips::NothingWrong)]
+        );
+        // a hint is forced in every solution, so locking the other value is a mistake
+        controller.apply(PlayInput::Hint);
+        let hint = controller.hint().unwrap().clone();
+        let coord = Coord::new_i(hint.row, hint.column);
+        if *get_cell(controller.in_progress(), hint.row, hint.column) == hint.enabled {
+            controller.apply(PlayInput::ToggleCell(coord));
+        }
+        controller.apply(PlayInput::ToggleLock(coord));
+        assert_eq!(
+            controller.apply(PlayInput::Check),
+            vec![PlayEffect::Checked]
+        );
+        assert_eq!(controller.wrong_cells(), [(hint.row, hint.column)]);
+        controller.apply(PlayInput::ToggleLock(coord));
+        controller.apply(PlayInput::ToggleCell(coord));
+        assert!(controller.wrong_cells().is_empty());
+    }
+}
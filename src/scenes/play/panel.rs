@@ -1,5 +1,7 @@
+use crate::canvas::MacroquadCanvas;
 use crate::level_history::GameTrack;
-use crate::logic::constraints::Satisfaction;
+use crate::logic::constraints::{Goal, Satisfaction};
+use crate::logic::deduction::Walkthrough;
 use crate::render::{
     draw_blockade, draw_line_thickness, draw_rail, draw_station, render_cross, render_tick,
 };
@@ -25,30 +27,47 @@ pub struct Panel {
     pub rect: Rect,
     pub level_title: TextRect,
     pub restart_game: Button,
+    pub hint: Button,
+    pub check: Button,
+    pub steps: Button,
+    pub step_controls: Option<StepControls>,
     pub next_game: Button,
     pub main_menu: Button,
+    pub try_branch: Button,
+    /// Only while a branch is open.
+    pub keep_try: Option<Button>,
+    pub discard_try: Option<Button>,
     pub show_solution: Option<Button>,
+    pub next_solution: Option<Button>,
     satisfaction: SatisfactionPanel,
     allow_next: bool,
 }
 
+pub struct StepControls {
+    pub next: Button,
+    pub previous: Button,
+    explanation: Vec<TextRect>,
+}
+
 impl Panel {
     pub fn new(
         panel_rect: Rect,
         satisfaction: Satisfaction,
+        extra_loops: usize,
         theme: &Theme,
         game_track: &GameTrack,
     ) -> Self {
         let button_margin_v = Vec2::splat(theme.button_margin());
         let _half_pad = vec2(theme.cell_pad() * 0.5, 0.0);
 
-        let level_name = game_track.current.to_string();
+        let level_name = game_track.level_title();
         let anchor_name = Anchor::from_top(panel_rect, Horizontal::Center, button_margin_v);
         let level_title = new_text(&level_name, anchor_name, 1.0, theme);
 
         let anchor_below_title =
             Anchor::below(level_title.rect(), Horizontal::Center, theme.cell_pad());
-        let satisfaction_panel = SatisfactionPanel::new(satisfaction, anchor_below_title, theme);
+        let satisfaction_panel =
+            SatisfactionPanel::new(satisfaction, extra_loops, anchor_below_title, theme);
 
         let anchor = Anchor::below_v(
             satisfaction_panel.rect(),
@@ -56,27 +75,67 @@ impl Panel {
             button_margin_v,
         );
         let restart_game = new_button("RESTART", anchor, &theme);
+        let hint = new_hint_button(&restart_game, game_track.hints_used(), theme);
+        let small_margin = Vec2::splat(theme.cell_pad());
+        let anchor = Anchor::below_v(hint.rect(), Horizontal::Center, small_margin);
+        let check = new_button("CHECK", anchor, theme);
+        let steps = new_steps_button(&check, false, theme);
 
         let anchor = Anchor::from_bottom(panel_rect, Horizontal::Right, button_margin_v);
         let next_game = new_button("NEXT", anchor, &theme);
 
         let anchor = Anchor::from_bottom(panel_rect, Horizontal::Left, button_margin_v);
         let main_menu = new_button("CAMPAIGN", anchor, theme);
+        let anchor = Anchor::above(main_menu.rect(), Horizontal::Left, theme.cell_pad());
+        let try_branch = new_button("TRY", anchor, theme);
 
         let mut s = Self {
             rect: panel_rect,
             level_title,
             restart_game,
+            hint,
+            check,
+            steps,
+            step_controls: None,
             next_game,
             main_menu,
+            try_branch,
+            keep_try: None,
+            discard_try: None,
             show_solution: None,
+            next_solution: None,
             satisfaction: satisfaction_panel,
             allow_next: false,
         };
-        s.add_satisfaction(&satisfaction, theme, &mut false);
+        s.add_satisfaction(&satisfaction, extra_loops, theme, None);
+        s.set_branch_depth(game_track.branches.len(), theme);
         s
     }
 
+    pub fn set_hints_used(&mut self, hints_used: u32, theme: &Theme) {
+        self.hint = new_hint_button(&self.restart_game, hints_used, theme);
+    }
+
+    pub fn set_branch_depth(&mut self, depth: usize, theme: &Theme) {
+        let anchor = Anchor::above(self.main_menu.rect(), Horizontal::Left, theme.cell_pad());
+        let text = if depth == 0 {
+            "TRY".to_string()
+        } else {
+            format!("TRY INSIDE ({} open)", depth)
+        };
+        self.try_branch = new_button(&text, anchor, theme);
+        if depth == 0 {
+            self.keep_try = None;
+            self.discard_try = None;
+        } else {
+            let anchor = Anchor::above(self.try_branch.rect(), Horizontal::Left, theme.cell_pad());
+            let keep = new_button("KEEP TRY", anchor, theme);
+            let anchor = Anchor::above(keep.rect(), Horizontal::Left, theme.cell_pad());
+            self.discard_try = Some(new_button("DISCARD TRY", anchor, theme));
+            self.keep_try = Some(keep);
+        }
+    }
+
     pub fn allow_next(&mut self) {
         self.allow_next = true;
     }
@@ -84,15 +143,16 @@ impl Panel {
     pub fn add_satisfaction(
         &mut self,
         satisfaction: &Satisfaction,
+        extra_loops: usize,
         theme: &Theme,
-        show_solution: &mut bool,
+        shown_solution: Option<(usize, usize)>,
     ) {
         let anchor = Anchor::below(
             self.level_title.rect(),
             Horizontal::Center,
             theme.cell_pad(),
         );
-        let satisfaction_panel = SatisfactionPanel::new(*satisfaction, anchor, theme);
+        let satisfaction_panel = SatisfactionPanel::new(*satisfaction, extra_loops, anchor, theme);
         self.satisfaction = satisfaction_panel;
         let mut rect = self.satisfaction.rect();
         self.show_solution = if satisfaction.success() || SEE_SOLUTION_DURING_GAME {
@@ -100,9 +160,8 @@ impl Panel {
             rect.w = self.rect.w;
             let small_margin = Vec2::splat(theme.cell_pad());
 
-            let anchor =
-                Anchor::below_v(self.restart_game.rect(), Horizontal::Center, small_margin);
-            let show_text = if *show_solution {
+            let anchor = Anchor::below_v(self.steps.rect(), Horizontal::Center, small_margin);
+            let show_text = if shown_solution.is_some() {
                 "HIDE SOLUTION"
             } else if satisfaction.success() {
                 "SHOW POSSIBLE SOLUTION"
@@ -115,6 +174,50 @@ impl Panel {
         } else {
             None
         };
+        self.next_solution = match (&self.show_solution, shown_solution) {
+            (Some(show), Some((index, count))) if count > 1 => {
+                let small_margin = Vec2::splat(theme.cell_pad());
+                let anchor = Anchor::below_v(show.rect(), Horizontal::Center, small_margin);
+                let text = format!("NEXT SOLUTION ({}/{})", index + 1, count);
+                Some(new_button(&text, anchor, theme))
+            }
+            _ => None,
+        };
+    }
+    /// Call after `add_satisfaction`, because the controls go below the solution buttons.
+    pub fn set_walkthrough(&mut self, walkthrough: Option<&Walkthrough>, theme: &Theme) {
+        self.steps = new_steps_button(&self.check, walkthrough.is_some(), theme);
+        let Some(walkthrough) = walkthrough else {
+            self.step_controls = None;
+            return;
+        };
+        let above = self.show_solution.as_ref().unwrap_or(&self.steps).rect();
+        let small_margin = Vec2::splat(theme.cell_pad());
+        let (applied, total) = walkthrough.progress();
+        let anchor = Anchor::below_v(above, Horizontal::Center, small_margin);
+        let next = new_button(&format!("NEXT STEP ({}/{})", applied, total), anchor, theme);
+        let anchor = Anchor::below_v(next.rect(), Horizontal::Center, small_margin);
+        let previous = new_button("PREVIOUS STEP", anchor, theme);
+
+        let text = match walkthrough.current() {
+            Some(deduction) => deduction.explain(),
+            None if total == 0 => "Nothing can be deduced from the clues alone".to_string(),
+            None => "Click NEXT STEP to see the first deduction".to_string(),
+        };
+        let max_width = self.rect.w - 2.0 * theme.button_margin();
+        let mut explanation = Vec::new();
+        let mut top = previous.rect().bottom() + theme.button_margin();
+        for line in wrap_words(&text, max_width, theme) {
+            let anchor = Anchor::top_center(self.rect.center().x, top);
+            let line = new_text(&line, anchor, 1.0, theme);
+            top = line.rect().bottom() + theme.cell_pad();
+            explanation.push(line);
+        }
+        self.step_controls = Some(StepControls {
+            next,
+            previous,
+            explanation,
+        });
     }
     pub fn filled_rect(&self) -> Rect {
         let mut rect = self.rect;
@@ -132,6 +235,11 @@ impl Panel {
         draw_rect(self.rect, PANEL_BACKGROUND);
         render_text(&self.level_title, &TEXT_STYLE);
         self.satisfaction.render_static(theme);
+        if let Some(controls) = &self.step_controls {
+            for line in &controls.explanation {
+                render_text(line, &TEXT_STYLE);
+            }
+        }
     }
     pub fn render_interactive(&self) {
         for b in self.buttons() {
@@ -139,27 +247,109 @@ impl Panel {
         }
         self.satisfaction.render_interactive();
     }
+    pub fn render_preview(&self, satisfaction: &Satisfaction, extra_loops: usize, theme: &Theme) {
+        self.satisfaction
+            .render_preview(satisfaction, extra_loops, theme);
+    }
+    pub fn goals_hovered(&self) -> bool {
+        self.satisfaction.is_hovered()
+    }
     pub fn buttons(&self) -> Vec<&Button> {
-        let mut buttons = vec![&self.main_menu, &self.restart_game];
+        let mut buttons = vec![
+            &self.main_menu,
+            &self.restart_game,
+            &self.hint,
+            &self.check,
+            &self.steps,
+            &self.try_branch,
+        ];
         if self.allow_next {
             buttons.push(&self.next_game);
         }
         if let Some(button) = self.show_solution.as_ref() {
             buttons.push(button)
         }
+        if let Some(button) = self.next_solution.as_ref() {
+            buttons.push(button)
+        }
+        buttons.extend(self.keep_try.as_ref());
+        buttons.extend(self.discard_try.as_ref());
+        if let Some(controls) = self.step_controls.as_ref() {
+            buttons.push(&controls.next);
+            buttons.push(&controls.previous);
+        }
         buttons
     }
     pub fn buttons_mut(&mut self) -> Vec<&mut Button> {
-        let mut buttons = vec![&mut self.main_menu, &mut self.restart_game];
+        let mut buttons = vec![
+            &mut self.main_menu,
+            &mut self.restart_game,
+            &mut self.hint,
+            &mut self.check,
+            &mut self.steps,
+            &mut self.try_branch,
+        ];
         if self.allow_next {
             buttons.push(&mut self.next_game);
         }
         if let Some(button) = self.show_solution.as_mut() {
             buttons.push(button)
         }
+        if let Some(button) = self.next_solution.as_mut() {
+            buttons.push(button)
+        }
+        buttons.extend(self.keep_try.as_mut());
+        buttons.extend(self.discard_try.as_mut());
+        if let Some(controls) = self.step_controls.as_mut() {
+            buttons.push(&mut controls.next);
+            buttons.push(&mut controls.previous);
+        }
         buttons
     }
 }
+fn new_hint_button(restart_game: &Button, hints_used: u32, theme: &Theme) -> Button {
+    let small_margin = Vec2::splat(theme.cell_pad());
+    let anchor = Anchor::below_v(restart_game.rect(), Horizontal::Center, small_margin);
+    let text = if hints_used == 0 {
+        "HINT".to_string()
+    } else {
+        format!("HINT ({} used)", hints_used)
+    };
+    new_button(&text, anchor, theme)
+}
+
+fn new_steps_button(check: &Button, active: bool, theme: &Theme) -> Button {
+    let small_margin = Vec2::splat(theme.cell_pad());
+    let anchor = Anchor::below_v(check.rect(), Horizontal::Center, small_margin);
+    let text = if active {
+        "LEAVE STEPS"
+    } else {
+        "STEP BY STEP"
+    };
+    new_button(text, anchor, theme)
+}
+
+/// Splits `text` in lines that fit in `max_width` when rendered, unless a single word is wider.
+fn wrap_words(text: &str, max_width: f32, theme: &Theme) -> Vec<String> {
+    let fits = |line: &str| {
+        new_text(line, Anchor::top_left(0.0, 0.0), 1.0, theme)
+            .rect()
+            .w
+            <= max_width
+    };
+    let mut lines: Vec<String> = Vec::new();
+    for word in text.split_whitespace() {
+        match lines.last_mut() {
+            Some(line) if fits(&format!("{} {}", line, word)) => {
+                line.push(' ');
+                line.push_str(word);
+            }
+            _ => lines.push(word.to_string()),
+        }
+    }
+    lines
+}
+
 pub fn split_tuple<const N: usize, T: Copy, U: Copy>(array: [(T, U); N]) -> ([T; N], [U; N]) {
     let mut ts: [T; N] = [array[0].0; N];
     let mut us: [U; N] = [array[0].1; N];
@@ -177,7 +367,8 @@ enum SatisfactionPanel {
         text: TextRect,
     },
     Unsolved {
-        // satisfaction: Satisfaction,
+        satisfaction: Satisfaction,
+        extra_loops: usize,
         // previous_rect: Rect,
         texts: Vec<TextRect>,
         successes: Vec<bool>,
@@ -203,7 +394,12 @@ pub fn station_render() -> impl Fn(&Theme) {
 }
 
 impl SatisfactionPanel {
-    pub fn new(satisfaction: Satisfaction, anchor: Anchor, theme: &Theme) -> Self {
+    pub fn new(
+        satisfaction: Satisfaction,
+        extra_loops: usize,
+        anchor: Anchor,
+        theme: &Theme,
+    ) -> Self {
         if satisfaction.success() {
             let text = new_text(&"SOLVED!", anchor, 2.0, &theme);
             Self::Solved { text }
@@ -212,7 +408,7 @@ impl SatisfactionPanel {
             let texts_and_tooltips = [
                 ((&satisfaction.stations.format(), satisfaction.stations.success()), "Satisfied bridges and stations"),
                 ((&satisfaction.cell_count.format(), satisfaction.cell_count.success()), "Active blocks"),
-                ((&satisfaction.reachable.format(), satisfaction.reachable.success()), "Reachable rails"),
+                ((&format_reachable(&satisfaction.reachable, extra_loops), satisfaction.reachable.success()), "Reachable rails"),
             ];
             let (texts_success, tooltips) = split_tuple(texts_and_tooltips);
             let (texts, successes) = split_tuple(texts_success);
@@ -234,6 +430,8 @@ impl SatisfactionPanel {
             let text_rects = labels.create(texts);
 
             Self::Unsolved {
+                satisfaction,
+                extra_loops,
                 texts: text_rects
                     .into_iter()
                     .map(|mut t| {
@@ -294,12 +492,28 @@ impl SatisfactionPanel {
                 // let icon_rect = add_contour(icon_rect, Vec2::splat(theme.cell_pad()));
                 let width = vec2(icon_rect.w, 0.0);
                 let start = icon_rect.center() - width * 0.5;
-                draw_rail(start, start + width, theme, true);
-                draw_station(theme, true, SUCCESS, SUCCESS_DARK, start, width, false);
+                draw_rail(
+                    &mut MacroquadCanvas,
+                    start,
+                    start + width,
+                    &theme.layout,
+                    true,
+                );
+                draw_station(
+                    &mut MacroquadCanvas,
+                    &theme.layout,
+                    true,
+                    SUCCESS,
+                    SUCCESS_DARK,
+                    start,
+                    width,
+                    false,
+                );
                 let start_2 = start - width * 1.2;
                 draw_line_thickness(start_2, start_2 + width, theme.cell_pad(), BACKGROUND);
                 draw_blockade(
-                    theme,
+                    &mut MacroquadCanvas,
+                    &theme.layout,
                     true,
                     SUCCESS,
                     SUCCESS_DARK,
@@ -323,7 +537,47 @@ impl SatisfactionPanel {
                 let icon_rect = anchor.get_rect(icon_size);
                 let length = vec2(icon_rect.w, 0.0);
                 let end = icon_rect.center() + length * 0.5;
-                draw_rail(end - length, end, theme, true);
+                draw_rail(&mut MacroquadCanvas, end - length, end, &theme.layout, true);
+            }
+        }
+    }
+
+    /// Whether the pointer was over a goal in the last `interact`.
+    pub fn is_hovered(&self) -> bool {
+        match self {
+            Self::Solved { .. } => false,
+            Self::Unsolved { tooltips, .. } => tooltips
+                .iter()
+                .any(|tooltip| matches!(tooltip, Tooltip::Renderable(_))),
+        }
+    }
+
+    /// Next to each goal that would change, its value after the previewed move.
+    pub fn render_preview(&self, preview: &Satisfaction, preview_loops: usize, theme: &Theme) {
+        if let Self::Unsolved {
+            satisfaction,
+            extra_loops,
+            texts,
+            ..
+        } = self
+        {
+            let reachable_now = format_reachable_change(&satisfaction.reachable, *extra_loops);
+            let reachable_after = format_reachable_change(&preview.reachable, preview_loops);
+            let goals = [
+                (satisfaction.stations, preview.stations),
+                (satisfaction.cell_count, preview.cell_count),
+            ];
+            let changes = goals
+                .iter()
+                .map(|(current, would_be)| (current != would_be, would_be.actual.to_string()))
+                .chain([(reachable_now != reachable_after, reachable_after)]);
+            for (text_rect, (changed, would_be)) in texts.iter().zip(changes) {
+                if changed {
+                    let anchor =
+                        Anchor::rightwards(text_rect.rect(), Vertical::Center, theme.cell_pad());
+                    let text = new_text(&format!("-> {}", would_be), anchor, 1.0, theme);
+                    render_text(&text, &TEXT_STYLE);
+                }
             }
         }
     }
@@ -345,6 +599,31 @@ impl SatisfactionPanel {
     }
 }
 
+/// Rather than counting rails, say how many loops have to be joined to the one through the root.
+fn format_reachable(reachable: &Goal, extra_loops: usize) -> String {
+    if extra_loops == 0 {
+        reachable.format()
+    } else {
+        format_extra_loops(extra_loops)
+    }
+}
+
+fn format_reachable_change(reachable: &Goal, extra_loops: usize) -> String {
+    if extra_loops == 0 {
+        reachable.actual.to_string()
+    } else {
+        format_extra_loops(extra_loops)
+    }
+}
+
+fn format_extra_loops(extra_loops: usize) -> String {
+    if extra_loops == 1 {
+        "1 extra loop".to_string()
+    } else {
+        format!("{} extra loops", extra_loops)
+    }
+}
+
 pub fn get_icon_rect(text_rect: &TextRect) -> Rect {
     let icon_size = text_rect.rect().h;
     let anchor = Anchor::top_right_v(text_rect.rect().point());
@@ -1,57 +1,69 @@
+use crate::canvas::{lightened, translucent, MacroquadCanvas};
+use crate::direction::{Direction, Sense};
 use crate::level_history::GameTrack;
-use crate::levels::Level;
-use crate::logic::constraints::{compute_satisfaction, Constraints, Satisfaction};
-use crate::logic::grid::{
-    count_neighbours, get_cell, get_cell_coord_mut, get_cell_mut, get_coord, get_coord_mut,
-    in_expanded_range_inner, in_range, is_system_fixed_v, Grid,
-};
+use crate::levels::{Level, Metadata, CURRENT_FORMAT_VERSION};
+use crate::logic::constraints::explain_constraint;
+use crate::logic::generator::GridGenerator;
+use crate::logic::grid::{in_expanded_range_inner, Grid};
 use crate::logic::pixel_grid::{manhattan_distance, CellSpot, Coord};
+use crate::math::IVec2;
 use crate::render::{
-    cell_top_left_coord, draw_lines_thickness, render_cells, render_constraints, render_grid,
+    cell_top_left, cell_top_left_coord, constraint_at, draw_lines_thickness, render_branch_cells,
+    render_cells, render_constraints, render_failing_constraints, render_grid, render_hint,
+    render_wrong_cells,
 };
+use crate::scene_stack::{Stage, Transition};
+use crate::scenes::play::controller::{PlayController, PlayEffect, PlayInput, Preview, Rail};
 use crate::scenes::play::panel::Panel;
 use crate::theme::{new_text, render_text, render_tooltip, Theme};
 use crate::{
-    new_layout, AnyError, NextStage, BACKGROUND, BACKGROUND_2, CACHE_TEXTURE,
-    DEFAULT_SHOW_SOLUTION, MAX_CELLS_COEF, SHOW_FPS, SHOW_SLIDER, STEP_GENERATION, STYLE,
-    TEXT_STYLE, TOOLTIP_DELAY, TRIANGLE,
+    new_layout, AnyError, BACKGROUND, BACKGROUND_2, CACHE_TEXTURE, PREVIEW_ALPHA, PULSE_SECONDS,
+    SHOW_FPS, SHOW_SLIDER, STEP_GENERATION, STYLE, TEXT_STYLE, TOOLTIP_DELAY, TRIANGLE,
 };
 use juquad::lazy::{set_positions, Interactable, Renderable, WidgetTrait};
 use juquad::widgets::anchor::{Anchor, Horizontal};
+use juquad::widgets::button::Button;
 use macroquad::camera::{set_camera, set_default_camera, Camera2D};
 use macroquad::color::{Color, WHITE};
 use macroquad::input::{
-    is_key_pressed, is_mouse_button_down, is_mouse_button_pressed, is_mouse_button_released,
-    mouse_position, KeyCode, MouseButton,
+    is_key_down, is_key_pressed, is_mouse_button_down, is_mouse_button_pressed,
+    is_mouse_button_released, mouse_position, KeyCode, MouseButton,
 };
-use macroquad::math::{ivec2, vec2, IVec2, Vec2};
+use macroquad::math::{vec2, Vec2};
 use macroquad::miniquad::date::now;
 use macroquad::miniquad::FilterMode;
 use macroquad::prelude::{
     clear_background, draw_texture, get_fps, next_frame, screen_height, screen_width, RenderTarget,
 };
-use macroquad::rand::rand;
 
 pub struct State<'a> {
-    game_track: &'a mut GameTrack,
-    show_solution: bool,
-    previous_satisfaction: Option<Satisfaction>,
-    success_sound_played: bool,
+    controller: PlayController<'a>,
     ui: UiState,
 }
 pub type ShowingSinceSeconds = f64;
 pub struct UiState {
     tooltip_showing: Option<(Tooltips, ShowingSinceSeconds)>,
 }
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub enum Tooltips {
     FixedCell,
     UserFixedCell,
     EditSolution,
+    NoHint,
+    NothingWrong,
+    TooManySolutions,
+    StepByStep,
 }
 
-pub async fn play(theme: &mut Theme, game_track: &mut GameTrack) -> Result<NextStage, AnyError> {
+pub async fn play(theme: &mut Theme, game_track: &mut GameTrack) -> Result<Transition, AnyError> {
     let (mut sw, mut sh) = (screen_width(), screen_height());
-    let (mut state, mut panel) = setup(theme, game_track);
+    let mut state = State {
+        controller: PlayController::new(game_track),
+        ui: UiState {
+            tooltip_showing: None,
+        },
+    };
+    let mut panel = setup(theme, &state.controller);
 
     let mut render_target_scale = 1.0;
     let mut slider_value = render_target_scale;
@@ -60,6 +72,10 @@ pub async fn play(theme: &mut Theme, game_track: &mut GameTrack) -> Result<NextS
     let mut resize = false;
 
     let mut right_click_pressed = None;
+    let mut stroke_from: Option<Vec2> = None;
+    let mut previewed = None;
+    let mut preview: Option<Preview> = None;
+    let mut effects = Vec::new();
     loop {
         render_target_scale = slider_value;
         let now = now();
@@ -72,32 +88,39 @@ pub async fn play(theme: &mut Theme, game_track: &mut GameTrack) -> Result<NextS
             refresh_render = true;
             sw = new_sw;
             sh = new_sh;
-            theme.layout = new_layout(sw, sh)
-                .resize_grid(state.in_progress().rows(), state.in_progress().columns());
+            let in_progress = state.controller.in_progress();
+            theme.layout =
+                new_layout(sw, sh).resize_grid(in_progress.rows(), in_progress.columns());
             panel = Panel::new(
-                theme.button_panel_rect(state.in_progress()),
-                compute_satisfaction(&state.game_track.in_progress, state.constraints()),
+                theme.button_panel_rect(in_progress),
+                *state.controller.satisfaction(),
+                state.controller.extra_loops(),
                 theme,
-                state.game_track,
+                state.controller.game_track(),
             );
             render_target = reset_render_target(sw, sh, render_target_scale);
         }
         if is_key_pressed(KeyCode::P) {
+            let controller = &state.controller;
             let level = Level {
-                initial_grid: state.in_progress().clone(),
-                constraints: state.constraints().clone(),
-                solution: state.solution().clone(),
+                metadata: Metadata {
+                    format_version: Some(CURRENT_FORMAT_VERSION),
+                    ..controller.game_track().get_current().metadata.clone()
+                },
+                initial_grid: controller.in_progress().clone(),
+                constraints: controller.constraints().clone(),
+                solution: controller.solution().clone(),
             };
             println!("{}", level);
         }
 
         let pos = Vec2::from(mouse_position());
-        let hovered_cell = pixel_to_coord(pos, state.in_progress(), &theme);
+        let hovered_cell = pixel_to_coord(pos, state.controller.in_progress(), &theme);
 
         // draw_text(&format!("pos clicked: {:?}", grid_indexes), 0.0, 16.0, 16.0, BLACK);
         if is_mouse_button_pressed(MouseButton::Right) {
             state.ui.tooltip_showing = None;
-            if !state.show_solution {
+            if state.controller.shows_board() {
                 right_click_pressed = hovered_cell.clone();
             }
         }
@@ -107,53 +130,84 @@ pub async fn play(theme: &mut Theme, game_track: &mut GameTrack) -> Result<NextS
                 (hovered_cell.clone(), right_click_pressed)
             {
                 right_click_pressed = None;
-                if state.show_solution {
-                    state.ui.tooltip_showing = Some((Tooltips::EditSolution, now));
-                } else {
-                    let released: IVec2 = released_coord.into();
-                    let pressed: IVec2 = right_clicked_coord.into();
-                    if is_system_fixed_v(released, state.in_progress()) && pressed == released {
-                        state.ui.tooltip_showing = Some((Tooltips::FixedCell, now));
-                    } else {
-                        add_user_constraint(
-                            right_clicked_coord,
-                            released_coord,
-                            &mut state,
-                            &mut refresh_render,
-                        );
-                    }
+                if let Some(input) = user_constraint(right_clicked_coord, released_coord) {
+                    effects.extend(state.controller.apply(input));
                 }
             }
         }
         if is_mouse_button_pressed(MouseButton::Left) {
             state.ui.tooltip_showing = None;
             if let Some(coord) = hovered_cell.clone() {
-                if state.show_solution {
-                    state.ui.tooltip_showing = Some((Tooltips::EditSolution, now));
+                let input = if is_key_down(KeyCode::LeftShift) || is_key_down(KeyCode::RightShift) {
+                    PlayInput::CycleNote(coord)
                 } else {
-                    let clicked: IVec2 = coord.into();
-                    if is_system_fixed_v(clicked, state.in_progress()) {
-                        state.ui.tooltip_showing = Some((Tooltips::FixedCell, now));
-                    } else {
-                        let fixed = get_coord(&state.in_progress().fixed_cells, coord);
-                        if *fixed {
-                            state.ui.tooltip_showing = Some((Tooltips::UserFixedCell, now));
-                        } else {
-                            let cell = get_cell_coord_mut(&mut state.game_track.in_progress, coord);
-                            *cell = !*cell;
-                            state.game_track.in_progress.recalculate_rails();
-                            refresh_render = true;
-                        }
-                    }
+                    stroke_from = Some(pos);
+                    PlayInput::ToggleCell(coord)
+                };
+                effects.extend(state.controller.apply(input));
+            }
+        } else if !is_mouse_button_down(MouseButton::Left) {
+            stroke_from = None;
+        } else if let Some(from) = stroke_from.filter(|from| *from != pos) {
+            let cells = cells_along(from, pos, state.controller.in_progress(), theme);
+            effects.extend(state.controller.apply(PlayInput::Paint(cells)));
+            stroke_from = Some(pos);
+        }
+
+        for effect in std::mem::take(&mut effects) {
+            match effect {
+                PlayEffect::BoardChanged
+                | PlayEffect::SolutionToggled
+                | PlayEffect::StepChanged => refresh_render = true,
+                PlayEffect::Tooltip(tooltip) => state.ui.tooltip_showing = Some((tooltip, now)),
+                PlayEffect::Solved => theme.resources.sounds.play_correct(),
+                PlayEffect::Checked | PlayEffect::NotesChanged => {}
+                PlayEffect::BranchChanged => {
+                    panel.set_branch_depth(state.controller.branch_depth(), theme);
+                }
+                PlayEffect::HintShown => {
+                    panel.set_hints_used(state.controller.game_track().hints_used(), theme);
+                }
+                PlayEffect::Restarted => {
+                    state.ui.tooltip_showing = None;
+                    panel = setup(theme, &state.controller);
+                    refresh_render = true;
+                }
+                PlayEffect::LoadNextLevel => {
+                    state.controller.game_track_mut().next(theme).await;
+                    state.controller.level_loaded();
+                    state.ui.tooltip_showing = None;
+                    panel = setup(theme, &state.controller);
+                    refresh_render = true;
                 }
             }
         }
 
+        let dragging =
+            is_mouse_button_down(MouseButton::Left) || is_mouse_button_down(MouseButton::Right);
+        if dragging {
+            (previewed, preview) = (None, None);
+        } else if refresh_render || previewed != hovered_cell.map(|c| (c.row(), c.column())) {
+            previewed = hovered_cell.map(|c| (c.row(), c.column()));
+            preview = hovered_cell.and_then(|coord| state.controller.preview(coord));
+        }
+
         clear_background(BACKGROUND);
-        if state.show_solution {
-            render_cells(state.solution(), &hovered_cell, theme);
-        } else {
-            render_cells(state.in_progress(), &hovered_cell, theme);
+        render_cells(
+            &mut MacroquadCanvas,
+            state.controller.shown_grid(),
+            &hovered_cell,
+            &theme.layout,
+        );
+        if state.controller.shows_board() {
+            let controller = &state.controller;
+            render_branch_cells(
+                &mut MacroquadCanvas,
+                controller.in_progress(),
+                &controller.branch_cells(),
+                controller.branch_depth(),
+                &theme.layout,
+            );
         }
         if refresh_render || !CACHE_TEXTURE {
             if let Some(render_target) = render_target {
@@ -170,55 +224,130 @@ pub async fn play(theme: &mut Theme, game_track: &mut GameTrack) -> Result<NextS
                 });
                 clear_background(Color::new(0.0, 0.0, 0.0, 0.0));
             }
-            let satisfaction =
-                compute_satisfaction(&state.game_track.in_progress, state.constraints());
+            let controller = &state.controller;
+            let satisfaction = controller.satisfaction();
             if satisfaction.success() {
-                state.game_track.solved();
                 panel.allow_next();
             }
-            if satisfaction.success() {
-                if !state.success_sound_played {
-                    state.success_sound_played = true;
-                    theme.resources.sounds.play_correct();
-                }
-            }
-            panel.add_satisfaction(&satisfaction, &theme, &mut state.show_solution);
+            panel.add_satisfaction(
+                satisfaction,
+                controller.extra_loops(),
+                &theme,
+                controller.shown_solution(),
+            );
+            panel.set_walkthrough(controller.walkthrough(), theme);
             panel.render_static(theme);
 
-            state.previous_satisfaction = Some(satisfaction);
-
-            if state.show_solution {
-                render_grid(&state.solution(), theme);
-                render_constraints(&state.constraints(), &state.solution(), theme);
-            } else {
-                render_grid(&state.in_progress(), theme);
-                render_constraints(&state.constraints(), &state.in_progress(), theme);
-            }
+            let grid = controller.shown_grid();
+            render_grid(&mut MacroquadCanvas, grid, &theme.layout);
+            render_constraints(
+                &mut MacroquadCanvas,
+                controller.constraints(),
+                grid,
+                &theme.layout,
+            );
         }
 
         if let Some(render_target) = render_target {
             set_default_camera();
             draw_texture(render_target.texture, 0., 0., WHITE);
         }
+        if let Some(preview) = &preview {
+            let mut screen = MacroquadCanvas;
+            let mut canvas = translucent(&mut screen, PREVIEW_ALPHA);
+            let constraints = state.controller.constraints();
+            render_grid(&mut canvas, &preview.grid, &theme.layout);
+            render_constraints(&mut canvas, constraints, &preview.grid, &theme.layout);
+            panel.render_preview(&preview.satisfaction, preview.extra_loops, theme);
+        }
+        if state.controller.shows_board() {
+            let wrong_cells = state.controller.wrong_cells();
+            render_wrong_cells(&mut MacroquadCanvas, wrong_cells, &theme.layout);
+        }
+        if let Some(step) = state.controller.walkthrough().and_then(|w| w.current()) {
+            render_hint(&mut MacroquadCanvas, step, &theme.layout);
+        }
+        if let Some(hint) = state.controller.hint() {
+            if state.controller.shows_board() {
+                render_hint(&mut MacroquadCanvas, hint, &theme.layout);
+                let below = cell_top_left(hint.row + 1, hint.column, &theme.layout);
+                let text = new_text(&hint.explain(), Anchor::top_left_v(below), 1.0, theme);
+                render_tooltip(&text, &TEXT_STYLE);
+            }
+        }
+        if panel.goals_hovered() {
+            let phase = (now / PULSE_SECONDS * std::f64::consts::TAU).sin() as f32;
+            let mut screen = MacroquadCanvas;
+            let mut canvas = lightened(&mut screen, 0.25 + 0.25 * phase);
+            let grid = state.controller.shown_grid();
+            let constraints = state.controller.constraints();
+            render_failing_constraints(&mut canvas, constraints, grid, &theme.layout);
+        }
+        if let Some(clue) = constraint_at(pos, state.controller.constraints(), &theme.layout) {
+            let explanation = explain_constraint(state.controller.shown_grid(), clue);
+            let text = new_text(&explanation, Anchor::bottom_left_v(pos), 1.0, theme);
+            render_tooltip(&text, &TEXT_STYLE);
+        }
 
-        if panel.main_menu.interaction().is_clicked() || is_key_pressed(KeyCode::Escape) {
-            return Ok(NextStage::LevelSelector);
+        if panel.main_menu.interaction().is_clicked() {
+            return Ok(Transition::PopTo(Stage::LevelSelector));
         }
         panel.interact(theme);
+        let mut inputs = Vec::new();
         if is_key_pressed(KeyCode::N) || panel.next_game.interaction().is_clicked() {
-            state.game_track.next(theme).await;
-            (state, panel) = reset(theme, state).await;
-            refresh_render = true;
+            inputs.push(PlayInput::Next);
         }
         if let Some(show) = panel.show_solution.as_mut() {
             if show.interaction().is_clicked() {
-                state.show_solution = !state.show_solution;
-                refresh_render = true;
+                inputs.push(PlayInput::ShowSolution);
             }
         }
+        let next_solution_clicked = panel
+            .next_solution
+            .as_ref()
+            .is_some_and(|next| next.interaction().is_clicked());
+        if is_key_pressed(KeyCode::S) || next_solution_clicked {
+            inputs.push(PlayInput::NextSolution);
+        }
         if panel.restart_game.interaction().is_clicked() {
-            refresh_render = true;
-            (state, panel) = reset(theme, state).await;
+            inputs.push(PlayInput::Restart);
+        }
+        if is_key_pressed(KeyCode::H) || panel.hint.interaction().is_clicked() {
+            inputs.push(PlayInput::Hint);
+        }
+        if is_key_pressed(KeyCode::C) || panel.check.interaction().is_clicked() {
+            inputs.push(PlayInput::Check);
+        }
+        if is_key_pressed(KeyCode::T) || panel.try_branch.interaction().is_clicked() {
+            inputs.push(PlayInput::Try);
+        }
+        let clicked = |button: &Option<Button>| {
+            button
+                .as_ref()
+                .is_some_and(|b| b.interaction().is_clicked())
+        };
+        if clicked(&panel.keep_try) {
+            inputs.push(PlayInput::KeepTry);
+        }
+        if clicked(&panel.discard_try) {
+            inputs.push(PlayInput::DiscardTry);
+        }
+        if panel.steps.interaction().is_clicked() {
+            inputs.push(PlayInput::StepByStep);
+        }
+        let (mut step_forward, mut step_back) = (false, false);
+        if let Some(controls) = &panel.step_controls {
+            step_forward = controls.next.interaction().is_clicked();
+            step_back = controls.previous.interaction().is_clicked();
+        }
+        if is_key_pressed(KeyCode::Right) || step_forward {
+            inputs.push(PlayInput::StepForward);
+        }
+        if is_key_pressed(KeyCode::Left) || step_back {
+            inputs.push(PlayInput::StepBack);
+        }
+        for input in inputs {
+            effects.extend(state.controller.apply(input));
         }
         if let Some(coord) = right_click_pressed {
             draw_diamond(coord, theme);
@@ -251,6 +380,10 @@ pub async fn play(theme: &mut Theme, game_track: &mut GameTrack) -> Result<NextS
                 tooltip.render(pos, &theme);
             }
         }
+        if is_key_pressed(KeyCode::Escape) {
+            // after drawing the whole frame, so that the pause menu shows it behind
+            return Ok(Transition::Overlay(Stage::PauseMenu));
+        }
         next_frame().await
     }
 }
@@ -259,7 +392,7 @@ fn draw_diamond(coord: Coord, theme: &mut Theme) {
     let cell_spot = CellSpot::new(coord.into());
     if cell_spot.is_corner() {
         let corner = cell_spot.corner();
-        let top_left = cell_top_left_coord(corner, theme);
+        let top_left = cell_top_left_coord(corner, &theme.layout);
         let half_w = (theme.cell_pad() + theme.cell_width()) * 0.5;
         let half_h = (theme.cell_pad() + theme.cell_height()) * 0.5;
         draw_lines_thickness(
@@ -275,7 +408,7 @@ fn draw_diamond(coord: Coord, theme: &mut Theme) {
         )
     } else {
         let floored = cell_spot.floored();
-        let top_left = cell_top_left_coord(floored, theme);
+        let top_left = cell_top_left_coord(floored, &theme.layout);
         let half_w = (theme.cell_pad() + theme.cell_width()) * 0.5;
         let half_h = (theme.cell_pad() + theme.cell_height()) * 0.5;
         draw_lines_thickness(
@@ -334,24 +467,16 @@ fn use_debug_slider(
     );
 }
 
-fn add_user_constraint(
-    pressed: Coord,
-    released: Coord,
-    state: &mut State,
-    refresh_render: &mut bool,
-) {
+/// Turns a right click drag into a change of the user constraints: between two adjacent
+/// corners it toggles a station, between two adjacent cells a blockade, and on a single cell
+/// it toggles the lock.
+fn user_constraint(pressed: Coord, released: Coord) -> Option<PlayInput> {
     let pressed_spot = CellSpot::new(pressed);
     let released_spot = CellSpot::new(released);
 
     let diff_rounded = released_spot.diff_rounded(pressed_spot);
     let diff_rounded_abs = diff_rounded.abs();
     let dist_rounded = manhattan_distance(diff_rounded_abs.into());
-    let _diff_floored = manhattan_distance(
-        released_spot
-            .diff_floored(pressed_spot)
-            .into::<IVec2>()
-            .abs(),
-    );
 
     if pressed_spot.is_corner() && released_spot.is_corner() && dist_rounded == 1 {
         let reversed = manhattan_distance(diff_rounded.into()) < 0;
@@ -361,54 +486,49 @@ fn add_user_constraint(
             .min(pressed_spot.rounded().into())
             .into();
 
-        let user_fix = if diff_rounded_abs == Coord::new_i(0, 1) {
-            state
-                .in_progress_mut()
-                .fixed_rails
-                .get_horiz_mut(coord.row(), coord.column())
+        let direction = if diff_rounded_abs == Coord::new_i(0, 1) {
+            Direction::Horizontal
         } else if diff_rounded_abs == Coord::new_i(1, 0) {
-            state
-                .in_progress_mut()
-                .fixed_rails
-                .get_vert_mut(coord.row(), coord.column())
+            Direction::Vertical
         } else {
             panic!("logic error with diff rounded: {:?}", diff_rounded);
         };
-        if reversed {
-            user_fix.station_backwards = !user_fix.station_backwards;
+        let rail = Rail {
+            direction,
+            row: coord.row(),
+            column: coord.column(),
+        };
+        let sense = if reversed {
+            Sense::Backwards
         } else {
-            user_fix.station_forward = !user_fix.station_forward;
-        }
-        *refresh_render = true;
+            Sense::Forwards
+        };
+        Some(PlayInput::ToggleStation(rail, sense))
     } else {
         let diff_vec = pressed_spot.diff_floored(released_spot).abs();
         let diff = manhattan_distance(diff_vec.into());
         if diff == 1 {
-            let rail: Coord = pressed.into::<IVec2>().max(released.into()).into();
-            let fixed = if diff_vec == Coord::new_i(1, 0) {
-                &mut state
-                    .in_progress_mut()
-                    .fixed_rails
-                    .get_horiz_mut(rail.row(), rail.column())
-                    .blockade
+            let coord: Coord = pressed.into::<IVec2>().max(released.into()).into();
+            let direction = if diff_vec == Coord::new_i(1, 0) {
+                Direction::Horizontal
             } else if diff_vec == Coord::new_i(0, 1) {
-                &mut state
-                    .in_progress_mut()
-                    .fixed_rails
-                    .get_vert_mut(rail.row(), rail.column())
-                    .blockade
+                Direction::Vertical
             } else {
                 panic!("logic error with diff_vec: {:?}", diff_vec);
             };
-            *fixed = !*fixed;
-            *refresh_render = true;
+            let rail = Rail {
+                direction,
+                row: coord.row(),
+                column: coord.column(),
+            };
+            Some(PlayInput::ToggleBlockade(rail))
         } else if diff == 0 {
-            let cell = get_coord_mut(&mut state.in_progress_mut().fixed_cells, released);
-            *cell = !*cell;
-            *refresh_render = true;
+            Some(PlayInput::ToggleLock(released))
+        } else {
+            // TODO: diff = 2, diagonal constraints
+            None
         }
     }
-    // TODO: diff = 2, diagonal constraints
 }
 
 impl Tooltips {
@@ -418,45 +538,28 @@ impl Tooltips {
             Tooltips::FixedCell => "Can't change locked blocks",
             Tooltips::UserFixedCell => "Can't change locked blocks, use right click to unlock",
             Tooltips::EditSolution => "Can't change blocks from solution, click 'HIDE SOLUTION'",
+            Tooltips::NoHint => "No block is forced yet, lock the blocks you are sure about",
+            Tooltips::NothingWrong => "No mistakes so far",
+            Tooltips::TooManySolutions => "This level has too many solutions to check",
+            Tooltips::StepByStep => "Can't change blocks in step by step mode, click 'LEAVE STEPS'",
         };
         let text_rect = new_text(text, anchor, 1.0, &theme);
         render_tooltip(&text_rect, &TEXT_STYLE);
     }
 }
 
-async fn reset<'a>(theme: &mut Theme, state: State<'a>) -> (State<'a>, Panel) {
-    let State { game_track, .. } = state;
-    game_track.in_progress = game_track.get_current().initial_grid.clone();
-    setup(theme, game_track)
-}
-
-fn setup<'a>(theme: &mut Theme, game_track: &'a mut GameTrack) -> (State<'a>, Panel) {
-    theme.layout.resize_grid_mut(
-        game_track.in_progress.rows(),
-        game_track.in_progress.columns(),
-    );
-    let show_solution = DEFAULT_SHOW_SOLUTION;
-    let success_sound_played = false;
-    let satisfaction = compute_satisfaction(
-        &game_track.in_progress,
-        &game_track.get_current().constraints,
-    );
-    let state = State {
-        game_track,
-        show_solution,
-        previous_satisfaction: Some(satisfaction),
-        success_sound_played,
-        ui: UiState {
-            tooltip_showing: None,
-        },
-    };
-    let panel = Panel::new(
-        theme.button_panel_rect(&state.game_track.in_progress),
-        satisfaction,
+fn setup(theme: &mut Theme, controller: &PlayController) -> Panel {
+    let in_progress = controller.in_progress();
+    theme
+        .layout
+        .resize_grid_mut(in_progress.rows(), in_progress.columns());
+    Panel::new(
+        theme.button_panel_rect(in_progress),
+        *controller.satisfaction(),
+        controller.extra_loops(),
         theme,
-        &state.game_track,
-    );
-    (state, panel)
+        controller.game_track(),
+    )
 }
 
 fn reset_render_target(sw: f32, sh: f32, render_target_scale: f32) -> Option<RenderTarget> {
@@ -472,87 +575,39 @@ fn reset_render_target(sw: f32, sh: f32, render_target_scale: f32) -> Option<Ren
     }
 }
 
-pub async fn generate_grid(visualize: bool, theme: &Theme) -> Grid {
-    let rows = theme.preferred_rows();
-    let columns = theme.preferred_columns();
-    let mut solution = Grid::new(rows, columns, ivec2(columns / 2, rows / 2));
-    let mut enabled = Vec::new();
-
-    enabled.push((solution.root.y, solution.root.x));
-    let mut i = 0;
-    let max_cells = ((rows - 2) as f32 * (columns - 2) as f32 * MAX_CELLS_COEF) as usize;
-    while enabled.len() < max_cells {
-        if visualize && is_key_pressed(KeyCode::Escape) {
+/// Shows the shape of the loop while it grows. Space advances one step if `STEP_GENERATION`
+/// is enabled, and Escape stops growing.
+pub async fn generate_grid_visualized(theme: &Theme) -> Grid {
+    let mut generator = GridGenerator::new(theme.preferred_rows(), theme.preferred_columns());
+    loop {
+        if is_key_pressed(KeyCode::Escape) {
             break;
         }
-        if is_key_pressed(KeyCode::Space) || !STEP_GENERATION {
-            i += 1;
-            if i > 1000 {
-                break;
-            }
-            // TODO: simplify and allow diagonal cells
+        if (is_key_pressed(KeyCode::Space) || !STEP_GENERATION) && !generator.step() {
+            break;
+        }
+        clear_background(BACKGROUND_2);
+        render_grid(&mut MacroquadCanvas, generator.grid(), &theme.layout);
+        next_frame().await;
+    }
+    generator.into_grid()
+}
 
-            // let index = rand() % enabled.len();
-            let mut index = enabled.len() - 1;
-            let mut low_neighbours_attempts = 0;
-            let (new_row, new_column) = loop {
-                let (row, column) = enabled[index];
-                let candidate = rand() % 8;
-                // println!(
-                //     "index: {} ({}, {}), neighbour {}",
-                //     index, row, column, neighbour
-                // );
-                let (new_row, new_column) = match candidate {
-                    0 => (row - 1, column),
-                    1 => (row, column + 1),
-                    2 => (row + 1, column),
-                    3 => (row, column - 1),
-                    4 => (row - 1, column - 1),
-                    5 => (row - 1, column + 1),
-                    6 => (row + 1, column + 1),
-                    7 => (row + 1, column - 1),
-                    _ => panic!(),
-                };
-                low_neighbours_attempts += 1;
-                if in_range(&solution, new_row, new_column) {
-                    let neighbours = count_neighbours(&solution, new_row, new_column);
-                    let already_enabled = get_cell(&solution, new_row, new_column);
-                    if !already_enabled && neighbours <= 2 || low_neighbours_attempts > 50 {
-                        break (new_row, new_column);
-                    }
-                }
-                // println!("rejected: ({}, {}), neighbours {}", row, column, neighbours);
-                if low_neighbours_attempts % 10 == 0 {
-                    index = rand() as usize % enabled.len();
-                    // println!("trying another endpoint {}, {}", enabled[index].0, enabled[index].1);
-                }
-                if low_neighbours_attempts > 100 {
-                    panic!();
-                }
-            };
-            let above_root = (solution.root.y - 1, solution.root.x);
-            // if the chosen neighbour is already enabled, choose another neighbour
-            if in_range(&solution, new_row, new_column) && (new_row, new_column) != above_root {
-                *get_cell_mut(&mut solution, new_row, new_column) = true;
-                enabled.push((new_row, new_column));
+/// The cells that the pointer went over when moving from `from` to `to` in one frame.
+fn cells_along(from: Vec2, to: Vec2, grid: &Grid, theme: &Theme) -> Vec<Coord> {
+    let step = theme.cell_width().min(theme.cell_height()) * 0.25;
+    let steps = (from.distance(to) / step).ceil().max(1.0) as i32;
+    let mut cells: Vec<Coord> = Vec::new();
+    for i in 1..=steps {
+        let pos = from.lerp(to, i as f32 / steps as f32);
+        if let Some(coord) = pixel_to_coord(pos, grid, theme) {
+            let coord = Coord::new_i(coord.row(), coord.column());
+            if cells.last() != Some(&coord) {
+                cells.push(coord);
             }
-            // for i_row in 0..SIZE {
-            //     for i_column in 0..SIZE {
-            //         let clicked = *get_mut(&mut grid, i_row, i_column);
-            //         let char = if clicked { "0" } else { "-" };
-            //         print!("{}", char)
-            //     }
-            //     println!()
-            // }
-            // println!()
-        }
-        if visualize {
-            clear_background(BACKGROUND_2);
-            render_grid(&solution, theme);
-            next_frame().await;
         }
     }
-    solution
+    cells
 }
 
 pub fn pixel_to_coord(pixel_pos: Vec2, grid: &Grid, theme: &Theme) -> Option<Coord> {
@@ -588,7 +643,7 @@ fn pixel_to_coord_inner(
 ) -> Option<Coord> {
     let grid_indexes =
         (pixel_pos - grid_pad + cell_pad * 0.5) / (vec2(cell_width, cell_height) + cell_pad);
-    let coord: Coord = grid_indexes.into();
+    let coord = Coord::new_f(grid_indexes.y, grid_indexes.x);
     if in_expanded_range_inner(coord.row(), coord.column(), rows, columns) {
         Some(coord)
     } else {
@@ -596,20 +651,6 @@ fn pixel_to_coord_inner(
     }
 }
 
-impl State<'_> {
-    pub fn solution(&self) -> &Grid {
-        &self.game_track.get_current().solution
-    }
-    pub fn in_progress(&self) -> &Grid {
-        &self.game_track.in_progress
-    }
-    pub fn in_progress_mut(&mut self) -> &mut Grid {
-        &mut self.game_track.in_progress
-    }
-    pub fn constraints(&self) -> &Constraints {
-        &self.game_track.get_current().constraints
-    }
-}
 #[cfg(test)]
 mod tests {
     use super::*;
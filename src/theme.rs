@@ -1,6 +1,6 @@
 use crate::logic::grid::Grid;
 use crate::scenes::loading_screen::Resources;
-use crate::{NUM_COLUMNS, NUM_ROWS, STYLE};
+use crate::{NUM_COLUMNS, NUM_ROWS, SMALL_TRIANGLE_HALF_WIDTH, STYLE, TRIANGLE_HALF_WIDTH};
 use juquad::draw::{draw_rect, draw_rect_lines};
 use juquad::widgets::anchor::{Anchor, Horizontal};
 use juquad::widgets::button::Button;
@@ -90,6 +90,18 @@ impl Layout {
     pub fn cell_pad(&self) -> f32 {
         self.cell_pad
     }
+    pub fn cell_width(&self) -> f32 {
+        self.cell_width
+    }
+    pub fn cell_height(&self) -> f32 {
+        self.cell_height
+    }
+    pub fn small_triangle_half_width(&self) -> f32 {
+        SMALL_TRIANGLE_HALF_WIDTH
+    }
+    pub fn triangle_half_width(&self) -> f32 {
+        TRIANGLE_HALF_WIDTH
+    }
 }
 impl Theme {
     pub fn useable_screen_size(&self) -> (f32, f32) {
@@ -193,10 +205,10 @@ impl Theme {
     }
 
     pub fn small_triangle_half_width(&self) -> f32 {
-        0.15
+        self.layout.small_triangle_half_width()
     }
     pub fn triangle_half_width(&self) -> f32 {
-        0.3
+        self.layout.triangle_half_width()
     }
 }
 